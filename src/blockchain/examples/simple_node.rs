@@ -23,14 +23,17 @@ use log::{debug, info};
 use std::{
     error::Error,
     fs,
-    io::{Read, Write},
+    io::Write,
     os::unix::fs::OpenOptionsExt,
     sync::{Arc, Mutex},
 };
+use thiserror::Error;
 use tokio::io;
 
-// use pyrsia_blockchain_network::blockchain::Blockchain;
-use pyrsia_blockchain_network::args::parser::BlockchainNodeArgs;
+use pyrsia_blockchain_network::args::parser::{BlockchainNodeArgs, NodeCommand};
+use pyrsia_blockchain_network::blockchain::{
+    Blockchain, BlockchainBuilder, BlockchainConfig, BlockchainError,
+};
 use pyrsia_blockchain_network::crypto::hash_algorithm::HashDigest;
 use pyrsia_blockchain_network::identities::{
     authority_pen::AuthorityPen, authority_verifier::AuthorityVerifier, key_box::KeyBox,
@@ -38,6 +41,7 @@ use pyrsia_blockchain_network::identities::{
 use pyrsia_blockchain_network::network::{Network, Spawner};
 use pyrsia_blockchain_network::providers::{DataProvider, DataStore, FinalizationProvider};
 use pyrsia_blockchain_network::structures::block::Block;
+use pyrsia_blockchain_network::structures::genesis::read_authorities;
 use pyrsia_blockchain_network::{
     default_config, gen_chain_config, run_blockchain, run_session, NodeIndex,
 };
@@ -53,13 +57,27 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let args = BlockchainNodeArgs::parse();
 
+    if let Some(command) = &args.command {
+        // the stored chain is inspected as is, leftovers of replaced forks included
+        let blockchain = Blockchain::open_skipping_orphans(storage_config(&args))?;
+        println!("{}", run_command(command, &blockchain)?);
+        return Ok(());
+    }
+
     let key_path = get_keyfile_name(args.clone());
 
     // If the key file exists, load the key pair. Otherwise, create a random keypair and save to the keypair file
-    let id_keys = create_ed25519_keypair(key_path);
+    let id_keys = create_ed25519_keypair(key_path)?;
     let ed25519_pair = identity::Keypair::Ed25519(id_keys.clone());
     let _peer_id = PeerId::from(ed25519_pair.public());
 
+    let mut blockchain = open_or_create_blockchain(&args, &id_keys)?;
+    info!(
+        "Blockchain at height {} stored in {:?}",
+        blockchain.height(),
+        args.storage_dir
+    );
+
     info!("Getting network up!");
     let n_members = 3;
     let my_node_ix = NodeIndex(args.peer_index);
@@ -105,7 +123,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         0,
         vec![],
         &id_keys,
-    )));
+    )?));
 
     let data_provider = DataProvider::new(current_block.clone()); // TODO(prince-chrismc): Blend this into blockchain API???
     let (finalization_provider, mut finalized_rx) = FinalizationProvider::new();
@@ -168,9 +186,60 @@ async fn main() -> Result<(), Box<dyn Error>> {
     close_chain.send(()).expect("should send");
     close_network.send(()).expect("should send");
     close_verifier.send(()).expect("should send");
+    // commit what is still pending and make sure it is all on disk before exiting
+    blockchain.flush(&id_keys).await?;
     Ok(())
 }
 
+// The configuration of the chain stored in the storage directory of `args`
+fn storage_config(args: &BlockchainNodeArgs) -> BlockchainConfig {
+    BlockchainConfig {
+        storage_dir: Some(args.storage_dir.clone()),
+        ..Default::default()
+    }
+}
+
+// Open the chain stored in the storage directory of `args`, or start a chain committed by
+// `keypair` there when none is stored yet. A fresh chain is seeded with the initial
+// authorities of `args`, which are ignored once a chain is stored.
+pub fn open_or_create_blockchain(
+    args: &BlockchainNodeArgs,
+    keypair: &identity::ed25519::Keypair,
+) -> Result<Blockchain, Box<dyn Error>> {
+    fs::create_dir_all(&args.storage_dir)?;
+    match Blockchain::open(storage_config(args)) {
+        Err(BlockchainError::MissingGenesis) => {
+            let mut builder = BlockchainBuilder::new(keypair);
+            builder.storage_dir(&args.storage_dir);
+            if !args.initial_authorities.is_empty() {
+                builder.initial_authorities(read_authorities(&args.initial_authorities)?);
+            }
+            Ok(builder.build()?)
+        }
+        opened => Ok(opened?),
+    }
+}
+
+// Answer `command` about `blockchain`, the output to print
+pub fn run_command(
+    command: &NodeCommand,
+    blockchain: &Blockchain,
+) -> Result<String, Box<dyn Error>> {
+    match command {
+        NodeCommand::Info => Ok(format!(
+            "height: {}\ntip: {}\nauthorities: {}",
+            blockchain.height(),
+            blockchain.state_snapshot().tip_hash,
+            blockchain.authorities().len()
+        )),
+        NodeCommand::GetBlock { hash } => match blockchain.get_block_by_hash(hash) {
+            Some(block) => Ok(serde_json::to_string_pretty(block)?),
+            None => Err(format!("no block with hash {}", hash).into()),
+        },
+        NodeCommand::Verify => Ok(serde_json::to_string_pretty(&blockchain.self_check())?),
+    }
+}
+
 pub fn write_block(path: &str, block: Block) {
     let mut file = fs::OpenOptions::new()
         .write(true)
@@ -188,6 +257,7 @@ pub fn write_keypair(path: &String, data: &[u8; 64]) {
     let mut file = fs::OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .mode(0o600)
         .open(path)
         .expect("cannot open file");
@@ -195,14 +265,53 @@ pub fn write_keypair(path: &String, data: &[u8; 64]) {
     file.write_all(data).expect("write failed");
 }
 
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum KeypairFileError {
+    #[error("the keypair file holds {0} bytes, a raw Ed25519 keypair is 64 bytes")]
+    WrongLength(usize),
+}
+
 pub fn read_keypair(path: &String) -> Result<[u8; 64], Box<dyn Error>> {
-    let mut file = std::fs::File::open(path)?;
-    let mut buf = [0u8; 64];
-    let n = file.read(&mut buf)?;
-    if n == 64 {
-        Ok(buf)
-    } else {
-        Err(Box::new(io::Error::from(io::ErrorKind::InvalidData)))
+    let data = fs::read(path)?;
+    let buf: [u8; 64] = data
+        .as_slice()
+        .try_into()
+        .map_err(|_| KeypairFileError::WrongLength(data.len()))?;
+    Ok(buf)
+}
+
+pub fn write_keypair_protobuf(
+    path: &String,
+    keypair: &identity::Keypair,
+) -> Result<(), Box<dyn Error>> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+
+    file.write_all(&keypair.to_protobuf_encoding()?)?;
+    Ok(())
+}
+
+pub fn read_keypair_protobuf(path: &String) -> Result<identity::Keypair, Box<dyn Error>> {
+    let data = fs::read(path)?;
+    Ok(identity::Keypair::from_protobuf_encoding(&data)?)
+}
+
+// Load the Ed25519 keypair at `path`, stored either in the raw 64 bytes layout of
+// `write_keypair`, or protobuf encoded like the keys of other libp2p nodes
+pub fn load_ed25519_keypair(
+    path: &String,
+) -> Result<libp2p::identity::ed25519::Keypair, Box<dyn Error>> {
+    let mut data = fs::read(path)?;
+    if data.len() == 64 {
+        return Ok(libp2p::identity::ed25519::Keypair::decode(&mut data)?);
+    }
+    match identity::Keypair::from_protobuf_encoding(&data)? {
+        identity::Keypair::Ed25519(keypair) => Ok(keypair),
+        _ => Err(Box::new(io::Error::from(io::ErrorKind::InvalidData))),
     }
 }
 
@@ -213,28 +322,39 @@ pub fn get_keyfile_name(args: BlockchainNodeArgs) -> String {
     filepath
 }
 
-pub fn create_ed25519_keypair(filename: String) -> libp2p::identity::ed25519::Keypair {
-    match read_keypair(&filename) {
-        Ok(v) => {
-            let data: &mut [u8] = &mut v.clone();
+// Load the keypair at `filename`, or create one when there is no such file. A key file that
+// exists but can not be loaded is an error rather than being overwritten with a new key.
+pub fn create_ed25519_keypair(
+    filename: String,
+) -> Result<libp2p::identity::ed25519::Keypair, Box<dyn Error>> {
+    match load_ed25519_keypair(&filename) {
+        Ok(keypair) => {
             debug!("Load Keypair from {:?}", filename);
-            libp2p::identity::ed25519::Keypair::decode(data).unwrap()
+            Ok(keypair)
         }
-        Err(_) => {
+        Err(e) if is_not_found(e.as_ref()) => {
             let id_keys = identity::ed25519::Keypair::generate();
 
             let data = id_keys.encode();
             debug!("Create Keypair");
             write_keypair(&filename, &data);
-            id_keys
+            Ok(id_keys)
         }
+        Err(e) => Err(e),
     }
 }
 
+fn is_not_found(error: &(dyn Error + 'static)) -> bool {
+    matches!(error.downcast_ref::<io::Error>(), Some(e) if e.kind() == io::ErrorKind::NotFound)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use pyrsia_blockchain_network::args::parser::DEFAULT_BLOCK_KEYPAIR_FILENAME;
+    use pyrsia_blockchain_network::args::parser::{
+        DEFAULT_BLOCK_KEYPAIR_FILENAME, DEFAULT_BLOCK_STORAGE_DIR,
+    };
+    use pyrsia_blockchain_network::structures::header::Address;
     const TEST_KEYPAIR_FILENAME: &str = "./test_keypair";
     #[test]
     fn test_get_keyfile_name_succeeded() {
@@ -244,6 +364,9 @@ mod tests {
         let args = BlockchainNodeArgs {
             key_filename: DEFAULT_BLOCK_KEYPAIR_FILENAME.to_string(),
             peer_index: 0,
+            initial_authorities: vec![],
+            storage_dir: DEFAULT_BLOCK_STORAGE_DIR.into(),
+            command: None,
         };
         assert_eq!(
             path.into_os_string().into_string().unwrap(),
@@ -267,13 +390,159 @@ mod tests {
         assert!(read_keypair(&file).is_ok());
     }
 
+    #[test]
+    fn test_read_keypair_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("keypair").to_str().unwrap().to_string();
+
+        fs::write(&file, [1u8; 32]).unwrap();
+        let err = read_keypair(&file).unwrap_err();
+        assert_eq!(
+            Some(&KeypairFileError::WrongLength(32)),
+            err.downcast_ref::<KeypairFileError>()
+        );
+
+        fs::write(&file, [1u8; 64]).unwrap();
+        assert_eq!([1u8; 64], read_keypair(&file).unwrap());
+
+        let keypair = identity::Keypair::generate_ed25519();
+        let encoded = keypair.to_protobuf_encoding().unwrap();
+        fs::write(&file, &encoded).unwrap();
+        let err = read_keypair(&file).unwrap_err();
+        assert_eq!(
+            Some(&KeypairFileError::WrongLength(encoded.len())),
+            err.downcast_ref::<KeypairFileError>()
+        );
+    }
+
+    #[test]
+    fn test_create_keypair_keeps_corrupt_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("keypair").to_str().unwrap().to_string();
+
+        fs::write(&file, [1u8; 10]).unwrap();
+        assert!(create_ed25519_keypair(file.clone()).is_err());
+        assert_eq!(vec![1u8; 10], fs::read(&file).unwrap());
+
+        fs::remove_file(&file).unwrap();
+        let created = create_ed25519_keypair(file.clone()).unwrap();
+        assert_eq!(created.encode(), read_keypair(&file).unwrap());
+        let loaded = create_ed25519_keypair(file).unwrap();
+        assert_eq!(created.encode(), loaded.encode());
+    }
+
+    #[test]
+    fn test_load_raw_keypair() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("raw_keypair").to_str().unwrap().to_string();
+        let keypair = identity::ed25519::Keypair::generate();
+        write_keypair(&file, &keypair.encode());
+
+        let loaded = load_ed25519_keypair(&file).unwrap();
+        assert_eq!(keypair.encode(), loaded.encode());
+    }
+
+    #[test]
+    fn test_keypair_protobuf_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir
+            .path()
+            .join("protobuf_keypair")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let keypair = identity::Keypair::generate_ed25519();
+        write_keypair_protobuf(&file, &keypair).unwrap();
+
+        let read = read_keypair_protobuf(&file).unwrap();
+        assert_eq!(PeerId::from(keypair.public()), PeerId::from(read.public()));
+        let loaded = load_ed25519_keypair(&file).unwrap();
+        assert_eq!(
+            keypair.public(),
+            identity::PublicKey::Ed25519(loaded.public())
+        );
+    }
+
+    #[test]
+    fn test_run_command() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let blockchain = Blockchain::in_memory(&keypair);
+        let genesis_hash = blockchain.genesis_hash();
+
+        let info = run_command(&NodeCommand::Info, &blockchain).unwrap();
+        assert_eq!(
+            format!("height: 0\ntip: {}\nauthorities: 1", genesis_hash),
+            info
+        );
+        let block =
+            run_command(&NodeCommand::GetBlock { hash: genesis_hash }, &blockchain).unwrap();
+        assert_eq!(
+            blockchain.get_block_by_ordinal(0),
+            serde_json::from_str::<Block>(&block).ok().as_ref()
+        );
+        let unknown = NodeCommand::GetBlock {
+            hash: HashDigest::new(b"unknown"),
+        };
+        assert!(run_command(&unknown, &blockchain).is_err());
+        let report = run_command(&NodeCommand::Verify, &blockchain).unwrap();
+        assert!(report.contains("\"linkage\": \"Pass\""));
+    }
+
+    #[test]
+    fn test_open_or_create_blockchain() {
+        let dir = tempfile::tempdir().unwrap();
+        let keypair = identity::ed25519::Keypair::generate();
+        let args = BlockchainNodeArgs {
+            key_filename: DEFAULT_BLOCK_KEYPAIR_FILENAME.to_string(),
+            peer_index: 0,
+            initial_authorities: vec![],
+            storage_dir: dir.path().join("chain"),
+            command: Some(NodeCommand::Info),
+        };
+
+        let created = open_or_create_blockchain(&args, &keypair).unwrap();
+        let opened = open_or_create_blockchain(&args, &keypair).unwrap();
+        assert_eq!(created.genesis_hash(), opened.genesis_hash());
+        let inspected = Blockchain::open_skipping_orphans(storage_config(&args)).unwrap();
+        assert!(run_command(&NodeCommand::Info, &inspected)
+            .unwrap()
+            .contains(&created.genesis_hash().to_string()));
+    }
+
+    #[test]
+    fn test_open_or_create_blockchain_with_initial_authorities() {
+        let dir = tempfile::tempdir().unwrap();
+        let keypair = identity::ed25519::Keypair::generate();
+        let authority = identity::Keypair::generate_ed25519().public();
+        let authority_file = dir.path().join("authority.pub");
+        fs::write(&authority_file, authority.to_protobuf_encoding()).unwrap();
+        let mut args = BlockchainNodeArgs {
+            key_filename: DEFAULT_BLOCK_KEYPAIR_FILENAME.to_string(),
+            peer_index: 0,
+            initial_authorities: vec![authority_file],
+            storage_dir: dir.path().join("chain"),
+            command: None,
+        };
+
+        let created = open_or_create_blockchain(&args, &keypair).unwrap();
+        assert!(created.authorities().contains(&Address::from(authority)));
+        assert_eq!(2, created.authorities().len());
+
+        args.initial_authorities = vec![dir.path().join("missing.pub")];
+        assert!(open_or_create_blockchain(&args, &keypair).is_ok());
+        args.storage_dir = dir.path().join("other chain");
+        assert!(open_or_create_blockchain(&args, &keypair).is_err());
+    }
+
     #[test]
     fn test_create_keypair_succeeded() {
         let args = BlockchainNodeArgs {
             key_filename: DEFAULT_BLOCK_KEYPAIR_FILENAME.to_string(),
             peer_index: 0,
+            initial_authorities: vec![],
+            storage_dir: DEFAULT_BLOCK_STORAGE_DIR.into(),
+            command: None,
         };
-        let result = std::panic::catch_unwind(|| create_ed25519_keypair(get_keyfile_name(args)));
-        assert!(result.is_ok());
+        assert!(create_ed25519_keypair(get_keyfile_name(args)).is_ok());
     }
 }
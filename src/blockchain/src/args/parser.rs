@@ -14,9 +14,13 @@
    limitations under the License.
 */
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::crypto::hash_algorithm::HashDigest;
 
 pub const DEFAULT_BLOCK_KEYPAIR_FILENAME: &str = ".block_keypair";
+pub const DEFAULT_BLOCK_STORAGE_DIR: &str = ".block_storage";
 
 /// Application to connect to and participate in the Pyrsia blockchain network
 #[derive(Debug, Parser, Clone)]
@@ -28,4 +32,95 @@ pub struct BlockchainNodeArgs {
     /// An unsigned number to specify the node index on Aleph algorithm.
     #[clap(long, short = 'I', required = true)]
     pub peer_index: usize,
+    /// The file of the protobuf encoded public key of an initial authority of a fresh chain,
+    /// may be repeated
+    #[clap(long = "initial-authority", short = 'A')]
+    pub initial_authorities: Vec<PathBuf>,
+    /// The directory the blocks of the chain are stored in
+    #[clap(long, short = 'S', default_value = DEFAULT_BLOCK_STORAGE_DIR)]
+    pub storage_dir: PathBuf,
+    /// Inspect the stored chain instead of running the node
+    #[clap(subcommand)]
+    pub command: Option<NodeCommand>,
+}
+
+/// The commands inspecting the chain stored by a node
+#[derive(Debug, Subcommand, Clone, PartialEq, Eq)]
+pub enum NodeCommand {
+    /// Print the height, the tip hash and the number of authorities of the chain
+    Info,
+    /// Print the block with the given hash as JSON
+    GetBlock {
+        /// The hex encoded hash of the block
+        hash: HashDigest,
+    },
+    /// Check the health of the chain and print the report
+    Verify,
+}
+
+/// Generate the genesis block of a private Pyrsia blockchain network and print it as JSON
+#[derive(Debug, Parser, Clone)]
+#[clap(long_about = None)]
+pub struct GenesisArgs {
+    /// The file of the keypair which commits the genesis block
+    #[clap(long, short = 'K', default_value = DEFAULT_BLOCK_KEYPAIR_FILENAME)]
+    pub key_filename: String,
+    /// The peer id of an authority of the network, may be repeated
+    #[clap(long = "authority", short = 'A')]
+    pub authorities: Vec<String>,
+    /// The Unix timestamp, in seconds, of the genesis block
+    #[clap(long, short = 'T', required = true)]
+    pub timestamp: u64,
+    /// The nonce of the genesis block
+    #[clap(long, short = 'N', default_value = "0")]
+    pub nonce: u128,
+    /// The id of the network, which scopes the transactions of the genesis block to it
+    #[clap(long)]
+    pub network_id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_without_command() {
+        let args = BlockchainNodeArgs::try_parse_from(["node", "-I", "1"]).unwrap();
+
+        assert_eq!(1, args.peer_index);
+        assert_eq!(PathBuf::from(DEFAULT_BLOCK_STORAGE_DIR), args.storage_dir);
+        assert_eq!(None, args.command);
+    }
+
+    #[test]
+    fn test_parse_info() {
+        let args =
+            BlockchainNodeArgs::try_parse_from(["node", "-I", "0", "-S", "/tmp/chain", "info"])
+                .unwrap();
+
+        assert_eq!(PathBuf::from("/tmp/chain"), args.storage_dir);
+        assert_eq!(Some(NodeCommand::Info), args.command);
+    }
+
+    #[test]
+    fn test_parse_get_block() {
+        let hash = HashDigest::new(b"a block");
+        let args =
+            BlockchainNodeArgs::try_parse_from(["node", "-I", "0", "get-block", &hash.to_string()])
+                .unwrap();
+        assert_eq!(Some(NodeCommand::GetBlock { hash }), args.command);
+
+        assert!(BlockchainNodeArgs::try_parse_from(["node", "-I", "0", "get-block"]).is_err());
+        assert!(
+            BlockchainNodeArgs::try_parse_from(["node", "-I", "0", "get-block", "not a hash"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_verify() {
+        let args = BlockchainNodeArgs::try_parse_from(["node", "-I", "0", "verify"]).unwrap();
+
+        assert_eq!(Some(NodeCommand::Verify), args.command);
+    }
 }
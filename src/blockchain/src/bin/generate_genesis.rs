@@ -0,0 +1,47 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use clap::Parser;
+use libp2p::{identity, PeerId};
+use std::error::Error;
+use std::fs;
+use std::str::FromStr;
+
+use pyrsia_blockchain_network::args::parser::GenesisArgs;
+use pyrsia_blockchain_network::structures::genesis::generate_genesis;
+use pyrsia_blockchain_network::structures::header::Address;
+use pyrsia_blockchain_network::structures::transaction::network_salt;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = GenesisArgs::parse();
+
+    let mut encoded = fs::read(&args.key_filename)?;
+    let keypair = identity::ed25519::Keypair::decode(&mut encoded)?;
+    let authorities = args
+        .authorities
+        .iter()
+        .map(|peer_id| PeerId::from_str(peer_id).map(Address::from))
+        .collect::<Result<Vec<Address>, _>>()?;
+
+    let salt = args
+        .network_id
+        .as_deref()
+        .map(network_salt)
+        .unwrap_or_default();
+    let genesis = generate_genesis(&keypair, authorities, salt, args.timestamp, args.nonce)?;
+    println!("{}", serde_json::to_string_pretty(&genesis)?);
+    Ok(())
+}
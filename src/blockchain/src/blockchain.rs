@@ -16,227 +16,6095 @@
 
 use libp2p::identity;
 use libp2p::identity::Keypair::Ed25519;
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::{self, Debug, Formatter};
+use std::fs;
+use std::future::Future;
+use std::io::{self, BufRead, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tracing::{debug_span, Instrument};
 
 use super::crypto::hash_algorithm::HashDigest;
+use super::storage::{
+    self, BlockStore, BlockWriter, ExportFormat, FileBlockStore, InMemoryBlockStore,
+};
 use super::structures::{
-    block::Block,
-    chain::Chain,
-    header::Address,
-    transaction::{Transaction, TransactionType},
+    block::{Block, BlockError},
+    chain::{median_time, Chain, ChainError, ChainSnapshot, Checkpoint},
+    genesis::{generate_genesis, GenesisError},
+    header::{Address, Header, Ordinal},
+    receipt::Receipt,
+    state::{
+        apply_authority_transactions, apply_revocations, permitted_committers, replay_authorities,
+        replay_revocations, revoked_at, round_robin_committer, Revocations, StateSnapshot,
+    },
+    transaction::{
+        network_salt, Transaction, TransactionBuilder, TransactionError, TransactionType,
+    },
 };
 
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum BlockchainError {
+    #[error("Snapshot tip {0} is not part of the chain")]
+    UnknownSnapshotTip(HashDigest),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Genesis block must have ordinal 0, not {0}")]
+    InvalidGenesis(Ordinal),
+    #[error("Genesis block {0} must have a zero parent hash and a valid signature")]
+    MalformedGenesis(HashDigest),
+    #[error("The chain has no genesis block")]
+    MissingGenesis,
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
+    #[error("Failed to serialize or deserialize a block: {0}")]
+    Serialization(String),
+    #[error("Failure while accessing storage: {0}")]
+    StorageFailure(String),
+    #[error("Expected a block with ordinal {expected}, got {actual}")]
+    UnexpectedOrdinal { expected: Ordinal, actual: Ordinal },
+    #[error("Block {0} does not link to the parent hash of the chain tip")]
+    ParentHashMismatch(Ordinal),
+    #[error(transparent)]
+    Block(#[from] BlockError),
+    #[error("Block {ordinal} was committed by {committer:?} out of turn")]
+    UnexpectedCommitter {
+        ordinal: Ordinal,
+        committer: Address,
+    },
+    #[error(
+        "Block {ordinal} was committed by {committer:?}, whose key is revoked since {revoked_at}"
+    )]
+    RevokedCommitter {
+        ordinal: Ordinal,
+        committer: Address,
+        revoked_at: u64,
+    },
+    #[error("Block {0} is not part of the chain")]
+    UnknownBlock(Ordinal),
+    #[error("The mempool already holds the maximum of {0} pending transactions")]
+    MempoolFull(usize),
+    #[error("Block {ordinal} precedes the finalized block {finalized}")]
+    BelowFinality {
+        ordinal: Ordinal,
+        finalized: Ordinal,
+    },
+    #[error("Block {ordinal} carries several transactions of {submitter:?} with nonce {nonce}")]
+    DuplicateNonce {
+        ordinal: Ordinal,
+        submitter: Address,
+        nonce: u128,
+    },
+    #[error("The payload of {size} bytes exceeds the maximum of {max} bytes")]
+    PayloadTooLarge { size: usize, max: usize },
+    #[error("The chain already holds a block with ordinal {ordinal}")]
+    DuplicateOrdinal { ordinal: Ordinal },
+    #[error("Block {ordinal} carries transaction {transaction} before its dependency {dependency} is settled")]
+    UnsettledDependency {
+        ordinal: Ordinal,
+        transaction: HashDigest,
+        dependency: HashDigest,
+    },
+    #[error("Block {ordinal} carries transaction {transaction}, which is already committed")]
+    CommittedTransaction {
+        ordinal: Ordinal,
+        transaction: HashDigest,
+    },
+    #[error("Block {0} was not received in its canonical serialization")]
+    NonCanonicalBlock(Ordinal),
+    #[error("The authority set holds {count} authorities, fewer than the minimum of {min}")]
+    InsufficientAuthorities { count: usize, min: usize },
+    #[error("Block {ordinal} weighs {weight}, over the maximum block weight of {max}")]
+    OverweightBlock {
+        ordinal: Ordinal,
+        weight: u64,
+        max: u64,
+    },
+    #[error("Block {ordinal} is timestamped {timestamp}, not after the median time past {median}")]
+    TimestampBeforeMedian {
+        ordinal: Ordinal,
+        timestamp: u64,
+        median: u64,
+    },
+    #[error("Block {ordinal} is timestamped {timestamp}, more than {skew} seconds ahead of the local clock")]
+    TimestampInFuture {
+        ordinal: Ordinal,
+        timestamp: u64,
+        skew: u64,
+    },
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+    #[error(transparent)]
+    Genesis(#[from] GenesisError),
+}
+
 /// Define Supported Signature Algorithm
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum SignatureAlgorithm {
     Ed25519,
 }
 
+/// Default time, in seconds, a submitted transaction may stay pending before it expires
+pub const DEFAULT_TRANSACTION_TTL: u64 = 60 * 60;
+
+/// Default maximum number of transactions a block may carry
+pub const DEFAULT_MAX_TRANSACTIONS_PER_BLOCK: usize = 1000;
+
+/// Default maximum number of submitted transactions which may be pending at once
+pub const DEFAULT_MAX_PENDING: usize = 10_000;
+
+/// Default maximum size, in bytes, of the payload of a submitted transaction
+pub const DEFAULT_MAX_PAYLOAD_BYTES: usize = 1024 * 1024;
+
+/// Default tolerance, in seconds, for block timestamps ahead of the local clock
+pub const DEFAULT_TIMESTAMP_SKEW: u64 = 60;
+
+/// Number of blocks whose median timestamp a new block must be timestamped after
+pub const MEDIAN_TIME_PAST_BLOCKS: usize = 11;
+
+/// Default number of appended blocks a `block_events` receiver may lag behind
+pub const DEFAULT_BLOCK_EVENT_CAPACITY: usize = 100;
+
+/// Default minimum number of authorities for the chain to accept blocks
+pub const DEFAULT_MIN_AUTHORITIES: usize = 1;
+
+/// Define how blocks get agreed upon
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsensusMode {
+    /// Blocks are committed as soon as they are built by the local node
+    #[default]
+    SingleNode,
+    /// Blocks are ordered by the Aleph BFT consensus among the authorities. Not supported by
+    /// `Blockchain` yet, building a blockchain in this mode fails.
+    AlephBft,
+}
+
+/// The options a blockchain is built with, see `BlockchainBuilder`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockchainConfig {
+    pub storage_dir: Option<PathBuf>,
+    pub consensus_mode: ConsensusMode,
+    pub max_transactions_per_block: usize,
+    pub max_pending: usize,
+    pub max_payload_bytes: usize,
+    pub timestamp_skew: u64,
+    pub block_event_capacity: usize,
+    pub async_writes: bool,
+    pub committer_timeout: Option<u64>,
+    pub network_id: Option<String>,
+    pub max_block_weight: Option<u64>,
+    pub min_authorities: usize,
+}
+
+impl Default for BlockchainConfig {
+    fn default() -> Self {
+        Self {
+            storage_dir: None,
+            consensus_mode: Default::default(),
+            max_transactions_per_block: DEFAULT_MAX_TRANSACTIONS_PER_BLOCK,
+            max_pending: DEFAULT_MAX_PENDING,
+            max_payload_bytes: DEFAULT_MAX_PAYLOAD_BYTES,
+            timestamp_skew: DEFAULT_TIMESTAMP_SKEW,
+            block_event_capacity: DEFAULT_BLOCK_EVENT_CAPACITY,
+            async_writes: false,
+            committer_timeout: None,
+            network_id: None,
+            max_block_weight: None,
+            min_authorities: DEFAULT_MIN_AUTHORITIES,
+        }
+    }
+}
+
+impl BlockchainConfig {
+    // Check the options are consistent with each other and within their bounds
+    fn validate(&self) -> Result<(), BlockchainError> {
+        if self.consensus_mode != ConsensusMode::SingleNode {
+            return Err(BlockchainError::InvalidConfig(format!(
+                "the consensus mode {:?} is not supported",
+                self.consensus_mode
+            )));
+        }
+        if self.max_transactions_per_block == 0 {
+            return Err(BlockchainError::InvalidConfig(
+                "max_transactions_per_block must be at least 1".to_string(),
+            ));
+        }
+        if self.max_pending == 0 {
+            return Err(BlockchainError::InvalidConfig(
+                "max_pending must be at least 1".to_string(),
+            ));
+        }
+        if let Some(network_id) = &self.network_id {
+            if network_id.is_empty()
+                || network_id == "."
+                || network_id == ".."
+                || network_id.contains(['/', '\\'])
+            {
+                return Err(BlockchainError::InvalidConfig(format!(
+                    "network_id {:?} is not a valid directory name",
+                    network_id
+                )));
+            }
+        }
+        if self.committer_timeout == Some(0) {
+            return Err(BlockchainError::InvalidConfig(
+                "committer_timeout must be at least 1".to_string(),
+            ));
+        }
+        if self.max_block_weight == Some(0) {
+            return Err(BlockchainError::InvalidConfig(
+                "max_block_weight must be at least 1".to_string(),
+            ));
+        }
+        if self.async_writes && self.storage_dir.is_none() {
+            return Err(BlockchainError::InvalidConfig(
+                "async_writes needs a storage_dir and no block_store".to_string(),
+            ));
+        }
+        if self.max_payload_bytes == 0 {
+            return Err(BlockchainError::InvalidConfig(
+                "max_payload_bytes must be at least 1".to_string(),
+            ));
+        }
+        if self.block_event_capacity == 0 {
+            return Err(BlockchainError::InvalidConfig(
+                "block_event_capacity must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The salt scoping the transactions to the network, empty without a network id
+    pub fn network_salt(&self) -> Vec<u8> {
+        self.network_id
+            .as_deref()
+            .map(network_salt)
+            .unwrap_or_default()
+    }
+
+    /// The directory the blocks are stored in, the storage directory or, with a network id,
+    /// its subdirectory named after the network id. None when blocks are not stored.
+    pub fn block_dir(&self) -> Option<PathBuf> {
+        let storage_dir = self.storage_dir.as_ref()?;
+        Some(match &self.network_id {
+            Some(network_id) => storage_dir.join(network_id),
+            None => storage_dir.clone(),
+        })
+    }
+}
+
+/// Define the terminal outcome reported to a transaction observer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionOutcome {
+    /// The transaction was committed to the chain
+    Settled,
+    /// The transaction is invalid and has been dropped without being committed
+    Rejected { reason: String },
+    /// The transaction was pending for longer than the configured TTL and has been dropped
+    Expired,
+}
+
+/// Whether a transaction would be part of the next block, see
+/// `Blockchain::next_block_admission`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Admission {
+    /// The transaction would be committed in the next block produced
+    WillInclude,
+    /// The transaction would wait for a later block, `behind` pending transactions going
+    /// before it
+    Queued { behind: usize },
+    /// The transaction would not be accepted, or would be rejected once pending
+    Rejected { reason: String },
+}
+
+/// The outcome of one category of `Blockchain::self_check`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    Pass,
+    /// The category failed, with one description per failure found
+    Fail(Vec<String>),
+}
+
+impl CheckOutcome {
+    fn from_failures(failures: Vec<String>) -> Self {
+        if failures.is_empty() {
+            CheckOutcome::Pass
+        } else {
+            CheckOutcome::Fail(failures)
+        }
+    }
+
+    pub fn is_pass(&self) -> bool {
+        *self == CheckOutcome::Pass
+    }
+}
+
+/// The health of the chain of a node, one outcome per category checked by
+/// `Blockchain::self_check`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SelfCheckReport {
+    /// Every block links to the hash of its parent
+    pub linkage: CheckOutcome,
+    /// The hash of the tip recomputes from its header
+    pub tip_hash: CheckOutcome,
+    /// Every block of the chain is stored, readable and identical to the block in memory
+    pub block_files: CheckOutcome,
+    /// The authority set is not empty
+    pub authorities: CheckOutcome,
+    /// No ordinal is missing from the chain, but the ones pruned up to the checkpoint
+    pub gaps: CheckOutcome,
+    /// No block file is left over from blocks which are not part of the chain, see
+    /// `Blockchain::list_orphan_files`
+    pub orphans: CheckOutcome,
+}
+
+impl SelfCheckReport {
+    /// Whether every category passed
+    pub fn is_healthy(&self) -> bool {
+        [
+            &self.linkage,
+            &self.tip_hash,
+            &self.block_files,
+            &self.authorities,
+            &self.gaps,
+            &self.orphans,
+        ]
+        .iter()
+        .all(|outcome| outcome.is_pass())
+    }
+}
+
 pub struct Blockchain {
     // trans_observers may be only used internally by blockchain service
-    trans_observers: HashMap<Transaction, Box<dyn FnOnce(Transaction)>>,
+    trans_observers: HashMap<Transaction, Box<dyn FnOnce(Transaction, TransactionOutcome) + Send>>,
+    // pending is the mempool, the submitted transactions which are not settled yet in
+    // submission order
+    pending: Vec<Transaction>,
     // payload_observers used by transparency_log service
-    payload_observers: Vec<Box<dyn FnMut(&Vec<u8>)>>,
-    // chain is the blocks of the blockchain
-    chain: Chain,
+    payload_observers: Vec<Box<dyn FnMut(&Vec<u8>) + Send>>,
+    // block_observers are notified of every block appended to the chain until they break
+    block_observers: Vec<Box<dyn FnMut(&Block) -> ControlFlow<()> + Send>>,
+    // block_event_senders feed the receivers handed out by block_events, until they are dropped
+    block_event_senders: Vec<mpsc::Sender<Block>>,
+    // chain is the blocks of the blockchain, shared with the snapshots taken of it and copied
+    // on the first write while a snapshot is held
+    chain: Arc<Chain>,
+    // transaction_ttl is how long, in seconds, a transaction may stay pending
+    transaction_ttl: u64,
+    // authorities is the authority set derived from the committed blocks
+    authorities: HashSet<Address>,
+    // revocations are the revoked keys derived from the committed blocks
+    revocations: Revocations,
+    // submitter_index locates the transactions of the chain by submitter, in chain order
+    submitter_index: SubmitterIndex,
+    // config holds the options the blockchain was built with
+    config: BlockchainConfig,
+    // store is where the blocks get persisted, if anywhere
+    store: Option<Box<dyn BlockStore>>,
+    // writer persists the blocks to the storage directory from its own thread, instead of
+    // the store, when writes are asynchronous
+    writer: Option<BlockWriter>,
+    // finalized is the last block which, along with every block before it, can not be reorged
+    finalized: Option<Checkpoint>,
+    // idempotency_keys maps the keys of the idempotent submissions to the hash of the
+    // transaction submitted for them
+    idempotency_keys: HashMap<String, HashDigest>,
+    // pruned_state is the state as of the pruning checkpoint, which the derived state is
+    // replayed from once the blocks up to it are pruned
+    pruned_state: Option<StateSnapshot>,
 }
 
+/// A blockchain that can be shared between tasks, e.g. by the RPC server
+pub type SharedBlockchain = Arc<Mutex<Blockchain>>;
+
 impl Debug for Blockchain {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("Blockchain")
             .field("chain", &self.chain)
             .field("trans_observers", &self.trans_observers.len())
+            .field("pending", &self.pending.len())
             .field("payload_observers", &self.payload_observers.len())
+            .field("block_observers", &self.block_observers.len())
+            .field("block_event_senders", &self.block_event_senders.len())
+            .field("transaction_ttl", &self.transaction_ttl)
+            .field("authorities", &self.authorities)
+            .field("revocations", &self.revocations)
+            .field("submitter_index", &self.submitter_index.len())
+            .field("config", &self.config)
+            .field("store", &self.store.is_some())
+            .field("writer", &self.writer.is_some())
+            .field("finalized", &self.finalized)
+            .field("pruned_state", &self.pruned_state)
             .finish()
     }
 }
 
+/// Configure and build a `Blockchain`
+pub struct BlockchainBuilder {
+    keypair: identity::ed25519::Keypair,
+    genesis: Option<Block>,
+    initial_authorities: Option<Vec<Address>>,
+    config: BlockchainConfig,
+    block_store: Option<Box<dyn BlockStore>>,
+}
+
+impl BlockchainBuilder {
+    /// Start from the default configuration, `keypair` signs the default genesis block
+    pub fn new(keypair: &identity::ed25519::Keypair) -> Self {
+        Self {
+            keypair: keypair.clone(),
+            genesis: None,
+            initial_authorities: None,
+            config: Default::default(),
+            block_store: None,
+        }
+    }
+
+    /// Persist the blocks under `storage_dir`, which is created if missing
+    pub fn storage_dir(&mut self, storage_dir: impl Into<PathBuf>) -> &mut Self {
+        self.config.storage_dir = Some(storage_dir.into());
+        self
+    }
+
+    /// Store the blocks of the chain in a subdirectory of the storage directory named after
+    /// `network_id`, so that the chains of several networks can share a storage directory
+    pub fn network_id(&mut self, network_id: impl Into<String>) -> &mut Self {
+        self.config.network_id = Some(network_id.into());
+        self
+    }
+
+    /// Persist the blocks to `block_store` rather than to the storage directory
+    pub fn block_store(&mut self, block_store: impl BlockStore + 'static) -> &mut Self {
+        self.block_store = Some(Box::new(block_store));
+        self
+    }
+
+    pub fn consensus_mode(&mut self, consensus_mode: ConsensusMode) -> &mut Self {
+        self.config.consensus_mode = consensus_mode;
+        self
+    }
+
+    /// Use `genesis` instead of a freshly generated genesis block
+    pub fn genesis(&mut self, genesis: Block) -> &mut Self {
+        self.genesis = Some(genesis);
+        self
+    }
+
+    /// Seed the freshly generated genesis block with one `AddAuthority` transaction per
+    /// address of `authorities`, to bootstrap a private network. There must be at least one,
+    /// and the genesis block must not be given with `genesis`. The local keypair commits the
+    /// genesis block, which makes it an authority too.
+    pub fn initial_authorities(&mut self, authorities: Vec<Address>) -> &mut Self {
+        self.initial_authorities = Some(authorities);
+        self
+    }
+
+    pub fn max_transactions_per_block(&mut self, max_transactions_per_block: usize) -> &mut Self {
+        self.config.max_transactions_per_block = max_transactions_per_block;
+        self
+    }
+
+    /// How many submitted transactions may be pending at once, further submissions are refused
+    pub fn max_pending(&mut self, max_pending: usize) -> &mut Self {
+        self.config.max_pending = max_pending;
+        self
+    }
+
+    /// How large, in bytes, the payload of a submitted transaction may be, larger ones are
+    /// refused
+    pub fn max_payload_bytes(&mut self, max_payload_bytes: usize) -> &mut Self {
+        self.config.max_payload_bytes = max_payload_bytes;
+        self
+    }
+
+    /// How far, in seconds, a block timestamp may be ahead of the local clock
+    pub fn timestamp_skew(&mut self, timestamp_skew: u64) -> &mut Self {
+        self.config.timestamp_skew = timestamp_skew;
+        self
+    }
+
+    /// How many appended blocks a `block_events` receiver may lag behind before appending
+    /// waits for it
+    pub fn block_event_capacity(&mut self, block_event_capacity: usize) -> &mut Self {
+        self.config.block_event_capacity = block_event_capacity;
+        self
+    }
+
+    /// Store the blocks from a dedicated thread, so that appending a block returns before the
+    /// block is on disk, see `Blockchain::flush_writes`. Only blocks stored under the storage
+    /// directory can be written asynchronously.
+    pub fn async_writes(&mut self, async_writes: bool) -> &mut Self {
+        self.config.async_writes = async_writes;
+        self
+    }
+
+    /// How long, in seconds after its parent, the authority whose turn it is has to commit a
+    /// block before the next authority in the rotation may commit it instead. Without it the
+    /// chain waits for the authority whose turn it is.
+    pub fn committer_timeout(&mut self, committer_timeout: u64) -> &mut Self {
+        self.config.committer_timeout = Some(committer_timeout);
+        self
+    }
+
+    /// The maximum weight of a block, see `Block::weight`, which bounds the cost of verifying
+    /// it. Without it the weight of a block is only bounded by `max_transactions_per_block`.
+    pub fn max_block_weight(&mut self, max_block_weight: u64) -> &mut Self {
+        self.config.max_block_weight = Some(max_block_weight);
+        self
+    }
+
+    /// The minimum number of authorities below which no block is accepted anymore, so that
+    /// the authority set shrinking does not leave the chain to too few authorities. 0 lets
+    /// the chain go on whatever the size of the authority set.
+    pub fn min_authorities(&mut self, min_authorities: usize) -> &mut Self {
+        self.config.min_authorities = min_authorities;
+        self
+    }
+
+    /// Build the blockchain, a block store set with `block_store` is handed over to it
+    pub fn build(&mut self) -> Result<Blockchain, BlockchainError> {
+        self.config.validate()?;
+        if self.config.async_writes && self.block_store.is_some() {
+            return Err(BlockchainError::InvalidConfig(
+                "async_writes needs a storage_dir and no block_store".to_string(),
+            ));
+        }
+        match (&self.initial_authorities, &self.genesis) {
+            (Some(authorities), _) if authorities.is_empty() => {
+                return Err(BlockchainError::InvalidConfig(
+                    "initial_authorities must name at least one authority".to_string(),
+                ))
+            }
+            (Some(_), Some(_)) => {
+                return Err(BlockchainError::InvalidConfig(
+                    "initial_authorities can not seed a given genesis block".to_string(),
+                ))
+            }
+            _ => (),
+        }
+
+        if let Some(block_dir) = self.config.block_dir() {
+            fs::create_dir_all(block_dir)
+                .map_err(|e| BlockchainError::StorageFailure(e.to_string()))?;
+        }
+
+        let genesis = match &self.genesis {
+            Some(genesis) if genesis.header.ordinal != 0 => {
+                return Err(BlockchainError::InvalidGenesis(genesis.header.ordinal))
+            }
+            Some(genesis) if !genesis.is_valid_genesis() => {
+                return Err(BlockchainError::MalformedGenesis(genesis.header.hash()))
+            }
+            Some(genesis) => genesis.clone(),
+            None => match &self.initial_authorities {
+                Some(authorities) => generate_genesis(
+                    &self.keypair,
+                    authorities.clone(),
+                    self.config.network_salt(),
+                    unix_timestamp(),
+                    rand::random::<u128>(),
+                )?,
+                None => {
+                    let transaction = TransactionBuilder::new(TransactionType::Create)
+                        .payload("this is the first reserved transaction".as_bytes().to_vec())
+                        .network_salt(self.config.network_salt())
+                        .sign(&self.keypair)?;
+                    Block::new(
+                        HashDigest::zero(),
+                        0,
+                        Vec::from([transaction]),
+                        &self.keypair,
+                    )?
+                }
+            },
+        };
+        if genesis.transactions.len() > self.config.max_transactions_per_block {
+            return Err(BlockchainError::InvalidConfig(format!(
+                "genesis carries {} transactions, more than the maximum of {}",
+                genesis.transactions.len(),
+                self.config.max_transactions_per_block
+            )));
+        }
+
+        let store = match (self.block_store.take(), self.config.block_dir()) {
+            (Some(store), _) => Some(store),
+            (None, Some(block_dir)) => {
+                Some(Box::new(FileBlockStore::new(block_dir)) as Box<dyn BlockStore>)
+            }
+            (None, None) => None,
+        };
+        Blockchain::from_genesis(genesis, self.config.clone(), store)
+    }
+}
+
 impl Blockchain {
+    // Start a chain from `genesis`, storing the blocks in `store` if there is one
+    fn from_genesis(
+        genesis: Block,
+        config: BlockchainConfig,
+        mut store: Option<Box<dyn BlockStore>>,
+    ) -> Result<Self, BlockchainError> {
+        if let Some(store) = store.as_mut() {
+            store
+                .put(&genesis)
+                .map_err(|e| BlockchainError::StorageFailure(e.to_string()))?;
+        }
+
+        let writer = match config.block_dir() {
+            Some(block_dir) if config.async_writes => {
+                Some(BlockWriter::spawn(FileBlockStore::new(block_dir)))
+            }
+            _ => None,
+        };
+
+        let authorities = replay_authorities(vec![&genesis]);
+        let revocations = replay_revocations(vec![&genesis]);
+        let submitter_index = build_submitter_index(vec![&genesis]);
+        let chain = Chain::genesis_only(genesis);
+        Ok(Blockchain {
+            trans_observers: Default::default(),
+            pending: vec![],
+            payload_observers: vec![],
+            block_observers: vec![],
+            block_event_senders: vec![],
+            chain: Arc::new(chain),
+            transaction_ttl: DEFAULT_TRANSACTION_TTL,
+            authorities,
+            revocations,
+            submitter_index,
+            config,
+            store,
+            writer,
+            finalized: None,
+            idempotency_keys: Default::default(),
+            pruned_state: None,
+        })
+    }
+
+    /// Build a blockchain with the default configuration
     pub fn new(keypair: &identity::ed25519::Keypair) -> Self {
-        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
-        let transaction = Transaction::new(
-            TransactionType::Create,
-            local_id,
-            "this is the first reserved transaction".as_bytes().to_vec(),
-            keypair,
+        BlockchainBuilder::new(keypair)
+            .build()
+            .expect("the default configuration to be valid")
+    }
+
+    /// Build a blockchain with the default configuration which stores its blocks in memory,
+    /// so that it never touches the filesystem
+    pub fn in_memory(keypair: &identity::ed25519::Keypair) -> Self {
+        BlockchainBuilder::new(keypair)
+            .block_store(InMemoryBlockStore::new())
+            .build()
+            .expect("the default configuration to be valid")
+    }
+
+    pub fn config(&self) -> &BlockchainConfig {
+        &self.config
+    }
+
+    /// The store the blocks are persisted to, None when they are only kept in memory
+    pub fn block_store(&self) -> Option<&dyn BlockStore> {
+        self.store.as_deref()
+    }
+
+    pub fn set_transaction_ttl(&mut self, ttl: u64) -> &mut Self {
+        self.transaction_ttl = ttl;
+        self
+    }
+
+    /// Add `trans` to the pending transactions, `on_done` is called once it is settled,
+    /// rejected or expired. A transaction with an invalid signature, already committed to the
+    /// chain, or reusing the nonce of a pending transaction of its submitter, is rejected
+    /// right away. Fails with
+    /// `MempoolFull`, without calling `on_done`, when the maximum of pending transactions
+    /// is reached.
+    pub fn submit_transaction<
+        CallBack: 'static + Send + FnOnce(Transaction, TransactionOutcome),
+    >(
+        &mut self,
+        trans: Transaction,
+        on_done: CallBack,
+    ) -> Result<&mut Self, BlockchainError> {
+        self.check_payload_size(trans.payload_len())?;
+        if self.pending.len() >= self.config.max_pending && !self.pending.contains(&trans) {
+            return Err(BlockchainError::MempoolFull(self.config.max_pending));
+        }
+        if let Err(e) = trans
+            .verify_with_revocations(&self.revocations)
+            .and_then(|_| trans.verify_network(&self.config.network_salt()))
+        {
+            let reason = e.to_string();
+            on_done(trans, TransactionOutcome::Rejected { reason });
+            return Ok(self);
+        }
+        if self.chain.contains_transaction(&trans.hash()) {
+            let reason = committed_reason(&trans);
+            on_done(trans, TransactionOutcome::Rejected { reason });
+            return Ok(self);
+        }
+        if self.pending.iter().any(|pending| {
+            *pending != trans
+                && pending.submitter() == trans.submitter()
+                && pending.nonce() == trans.nonce()
+        }) {
+            let reason = format!(
+                "a pending transaction of {:?} already has nonce {}",
+                trans.submitter(),
+                trans.nonce()
+            );
+            on_done(trans, TransactionOutcome::Rejected { reason });
+            return Ok(self);
+        }
+        if self
+            .trans_observers
+            .insert(trans.clone(), Box::new(on_done))
+            .is_none()
+        {
+            self.pending.push(trans);
+        }
+        Ok(self)
+    }
+
+    /// Build one `Create` transaction per payload, serialized to JSON and signed with
+    /// `keypair`, and submit them all in order with a copy of `on_done` registered as the
+    /// observer of each. None of them is submitted when they do not all fit in the mempool.
+    pub fn submit_transactions<
+        T: Serialize,
+        CallBack: 'static + Clone + Send + FnOnce(Transaction, TransactionOutcome),
+    >(
+        &mut self,
+        payloads: Vec<T>,
+        keypair: &identity::ed25519::Keypair,
+        on_done: CallBack,
+    ) -> Result<Vec<Transaction>, BlockchainError> {
+        if self.pending.len() + payloads.len() > self.config.max_pending {
+            return Err(BlockchainError::MempoolFull(self.config.max_pending));
+        }
+        let payloads = payloads
+            .iter()
+            .map(|payload| {
+                serde_json::to_vec(payload)
+                    .map_err(|e| BlockchainError::Serialization(e.to_string()))
+            })
+            .collect::<Result<Vec<Vec<u8>>, BlockchainError>>()?;
+        for payload in payloads.iter() {
+            self.check_payload_size(payload.len())?;
+        }
+        let network_salt = self.config.network_salt();
+        let transactions = payloads
+            .into_iter()
+            .map(|payload| {
+                TransactionBuilder::new(TransactionType::Create)
+                    .payload(payload)
+                    .network_salt(network_salt.clone())
+                    .sign(keypair)
+            })
+            .collect::<Result<Vec<Transaction>, TransactionError>>()?;
+
+        for trans in transactions.iter() {
+            self.submit_transaction(trans.clone(), on_done.clone())?;
+        }
+
+        Ok(transactions)
+    }
+
+    /// Submit a `Create` transaction of `payload` signed with `keypair`, unless a transaction
+    /// was already submitted for `idempotency_key` and is still pending or in the chain, in
+    /// which case that transaction is returned and nothing is submitted, so that callers may
+    /// retry a submission safely. `on_done` is only registered as an observer when a new
+    /// transaction is submitted.
+    pub fn submit_transaction_idempotent<
+        T: Serialize,
+        CallBack: 'static + Send + FnOnce(Transaction, TransactionOutcome),
+    >(
+        &mut self,
+        idempotency_key: &str,
+        payload: T,
+        keypair: &identity::ed25519::Keypair,
+        on_done: CallBack,
+    ) -> Result<Transaction, BlockchainError> {
+        if let Some(hash) = self.idempotency_keys.get(idempotency_key) {
+            let existing = self
+                .pending
+                .iter()
+                .find(|trans| trans.hash() == *hash)
+                .or_else(|| self.chain.get_transaction(hash));
+            if let Some(trans) = existing {
+                return Ok(trans.clone());
+            }
+        }
+
+        let payload = serde_json::to_vec(&payload)
+            .map_err(|e| BlockchainError::Serialization(e.to_string()))?;
+        let trans = TransactionBuilder::new(TransactionType::Create)
+            .payload(payload)
+            .network_salt(self.config.network_salt())
+            .sign(keypair)?;
+        self.submit_transaction(trans.clone(), on_done)?;
+        self.idempotency_keys
+            .insert(idempotency_key.to_string(), trans.hash());
+        Ok(trans)
+    }
+
+    /// Estimate whether `trans`, once submitted or if already pending, would be committed in
+    /// the next block produced, from the priority of the pending transactions, their order of
+    /// submission and the limits of the configuration. A transaction whose dependencies are
+    /// not settled yet is queued, whatever its priority.
+    pub fn next_block_admission(&self, trans: &Transaction) -> Admission {
+        let is_pending = self.pending.contains(trans);
+        if let Err(e) = self.check_payload_size(trans.payload_len()) {
+            return Admission::Rejected {
+                reason: e.to_string(),
+            };
+        }
+        if !is_pending && self.pending.len() >= self.config.max_pending {
+            return Admission::Rejected {
+                reason: BlockchainError::MempoolFull(self.config.max_pending).to_string(),
+            };
+        }
+        if let Err(e) = trans
+            .verify_with_revocations(&self.revocations)
+            .and_then(|_| trans.verify_network(&self.config.network_salt()))
+        {
+            return Admission::Rejected {
+                reason: e.to_string(),
+            };
+        }
+        if self.chain.contains_transaction(&trans.hash()) {
+            return Admission::Rejected {
+                reason: committed_reason(trans),
+            };
+        }
+        if !self.may_submit(trans) {
+            return Admission::Rejected {
+                reason: unauthorized_reason(trans),
+            };
+        }
+        if !self.fits_in_block(trans.weight()) {
+            return Admission::Rejected {
+                reason: format!(
+                    "the transaction weighs {}, over the maximum block weight",
+                    trans.weight()
+                ),
+            };
+        }
+
+        // produce_block sorts by priority with a stable sort, so the transactions going first
+        // are those of higher priority and those of equal priority submitted earlier
+        let position = self
+            .pending
+            .iter()
+            .position(|pending| pending == trans)
+            .unwrap_or(self.pending.len());
+        let ahead: Vec<&Transaction> = self
+            .pending
+            .iter()
+            .enumerate()
+            .filter(|(i, pending)| {
+                *pending != trans
+                    && self.dependencies_settled(pending)
+                    && self.may_submit(pending)
+                    && self.fits_in_block(pending.weight())
+                    && (pending.priority() > trans.priority()
+                        || (pending.priority() == trans.priority() && *i < position))
+            })
+            .map(|(_, pending)| pending)
+            .collect();
+        let behind = ahead.len();
+        let weight = ahead.iter().map(|pending| pending.weight()).sum::<u64>() + trans.weight();
+        if self.dependencies_settled(trans)
+            && behind < self.config.max_transactions_per_block
+            && self.fits_in_block(weight)
+        {
+            Admission::WillInclude
+        } else {
+            Admission::Queued { behind }
+        }
+    }
+
+    fn check_payload_size(&self, size: usize) -> Result<(), BlockchainError> {
+        if size > self.config.max_payload_bytes {
+            return Err(BlockchainError::PayloadTooLarge {
+                size,
+                max: self.config.max_payload_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn notify_transaction_settled(&mut self, trans: Transaction) {
+        let _span = debug_span!("settle_transaction", hash = ?trans.hash()).entered();
+        self.pending.retain(|pending| *pending != trans);
+        // if there were no observers, we don't care
+        if let Some(on_settled) = self.trans_observers.remove(&trans) {
+            on_settled(trans, TransactionOutcome::Settled)
+        }
+    }
+
+    /// Drop the pending transactions whose timestamp is older than `now` minus the TTL, with
+    /// or without an observer, and notify their observers of the expiry
+    pub fn expire_pending(&mut self, now: u64) -> Vec<Transaction> {
+        let cutoff = now.saturating_sub(self.transaction_ttl);
+        let (expired, pending): (Vec<Transaction>, Vec<Transaction>) = self
+            .pending
+            .drain(..)
+            .partition(|trans| trans.timestamp() < cutoff);
+        self.pending = pending;
+
+        for trans in expired.iter() {
+            if let Some(on_expired) = self.trans_observers.remove(trans) {
+                on_expired(trans.clone(), TransactionOutcome::Expired)
+            }
+        }
+
+        expired
+    }
+
+    pub fn add_payload_listener<CallBack: 'static + Send + FnMut(&Vec<u8>)>(
+        &mut self,
+        on_payload: CallBack,
+    ) -> &mut Self {
+        self.payload_observers.push(Box::new(on_payload));
+        self
+    }
+
+    pub async fn notify_payload_event(&mut self, payload: &Vec<u8>) -> &mut Self {
+        self.payload_observers
+            .iter_mut()
+            .for_each(|notify| notify(payload));
+        self
+    }
+
+    pub fn add_block_listener<CallBack: 'static + Send + FnMut(&Block)>(
+        &mut self,
+        on_block: CallBack,
+    ) -> &mut Self {
+        let mut on_block = on_block;
+        self.block_observers.push(Box::new(move |block: &Block| {
+            on_block(block);
+            ControlFlow::Continue(())
+        }));
+        self
+    }
+
+    /// Register a block listener which is removed as soon as it returns `ControlFlow::Break`
+    pub fn add_block_listener_until<CallBack: 'static + Send + FnMut(&Block) -> ControlFlow<()>>(
+        &mut self,
+        on_block: CallBack,
+    ) -> &mut Self {
+        self.block_observers.push(Box::new(on_block));
+        self
+    }
+
+    /// Receive every block appended to the chain from now on, e.g. to observe blocks on a
+    /// separate task. Unlike the block listeners, which run while the block is appended, the
+    /// receiver may lag behind by `block_event_capacity` blocks, beyond which appending a
+    /// block waits for the receiver to catch up.
+    pub fn block_events(&mut self) -> mpsc::Receiver<Block> {
+        let (sender, receiver) = mpsc::channel(self.config.block_event_capacity);
+        self.block_event_senders.push(sender);
+        receiver
+    }
+
+    /// Receive the transactions of type `type_id` as the blocks carrying them are appended to
+    /// the chain. The subscription is a block listener, which is removed with the first block
+    /// appended after the receiver is dropped.
+    pub fn subscribe_transaction_type(
+        &mut self,
+        type_id: TransactionType,
+    ) -> mpsc::UnboundedReceiver<Transaction> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.add_block_listener_until(move |block: &Block| {
+            if sender.is_closed() {
+                return ControlFlow::Break(());
+            }
+            for trans in block.transactions.iter() {
+                if trans.type_id() == type_id && sender.send(trans.clone()).is_err() {
+                    return ControlFlow::Break(());
+                }
+            }
+            ControlFlow::Continue(())
+        });
+        receiver
+    }
+
+    /// Resolve once the chain reaches a height of at least `target`, right away when it already
+    /// has. The wait is a block listener, removed with the block reaching `target`, or with the
+    /// first block appended after the future is dropped.
+    pub fn on_height(&mut self, target: Ordinal) -> impl Future<Output = ()> {
+        let (sender, receiver) = oneshot::channel();
+        if self.height() >= target {
+            let _ = sender.send(());
+        } else {
+            let mut sender = Some(sender);
+            self.add_block_listener_until(move |block: &Block| {
+                if block.header.ordinal >= target {
+                    if let Some(sender) = sender.take() {
+                        let _ = sender.send(());
+                    }
+                    return ControlFlow::Break(());
+                }
+                match &sender {
+                    Some(sender) if !sender.is_closed() => ControlFlow::Continue(()),
+                    _ => ControlFlow::Break(()),
+                }
+            });
+        }
+        async move {
+            let _ = receiver.await;
+        }
+    }
+
+    fn notify_block_event(&mut self, block: &Block) -> &mut Self {
+        self.block_observers
+            .retain_mut(|notify| notify(block).is_continue());
+        self
+    }
+
+    // Send `block` to the receivers of block_events, forgetting the dropped ones
+    async fn send_block_event(&mut self, block: &Block) {
+        let senders = std::mem::take(&mut self.block_event_senders);
+        let mut open = Vec::with_capacity(senders.len());
+        for sender in senders {
+            if sender.send(block.clone()).await.is_ok() {
+                open.push(sender);
+            }
+        }
+        self.block_event_senders = open;
+    }
+
+    /// The ordinal of the last block of the chain
+    pub fn height(&self) -> Ordinal {
+        self.chain
+            .last_block()
+            .map(|block| block.header.ordinal)
+            .unwrap_or_default()
+    }
+
+    /// The number of transactions committed to the chain
+    pub fn transaction_count(&self) -> usize {
+        self.chain.transaction_count()
+    }
+
+    /// The size of the block files in the storage directory, 0 when blocks are not stored
+    pub fn total_bytes_on_disk(&self) -> Result<u64, BlockchainError> {
+        let block_dir = match self.config.block_dir() {
+            Some(block_dir) => block_dir,
+            None => return Ok(0),
+        };
+        let storage_failure = |e: std::io::Error| BlockchainError::StorageFailure(e.to_string());
+        let mut total = 0;
+        for (_, path) in storage::list_block_files(&block_dir).map_err(storage_failure)? {
+            total += fs::metadata(path).map_err(storage_failure)?.len();
+        }
+        for (_, path) in storage::list_segment_files(&block_dir).map_err(storage_failure)? {
+            total += fs::metadata(path).map_err(storage_failure)?.len();
+        }
+        Ok(total)
+    }
+
+    /// The block files under `dir` for blocks which are not part of the chain anymore, e.g.
+    /// after a reorg, or which were pruned while blocks were written asynchronously, followed
+    /// by the segment files holding at least one such block
+    pub fn list_orphan_files(
+        &self,
+        dir: impl AsRef<Path>,
+    ) -> Result<Vec<PathBuf>, BlockchainError> {
+        let storage_failure = |e: io::Error| BlockchainError::StorageFailure(e.to_string());
+        let mut orphans: Vec<PathBuf> = storage::list_block_files(dir.as_ref())
+            .map_err(storage_failure)?
+            .into_iter()
+            .filter(
+                |(ordinal, path)| match self.chain.get_block_by_ordinal(*ordinal) {
+                    Some(block) => {
+                        storage::parse_hash(path) != Some(block.header.hash().to_string().as_str())
+                    }
+                    None => true,
+                },
+            )
+            .map(|(_, path)| path)
+            .collect();
+        for (_, path) in storage::list_segment_files(dir.as_ref()).map_err(storage_failure)? {
+            let blocks = storage::read_segment(&path).map_err(storage_failure)?;
+            if !blocks.iter().all(|block| self.holds(block)) {
+                orphans.push(path);
+            }
+        }
+        Ok(orphans)
+    }
+
+    // Whether `block` is the block of the chain at its ordinal
+    fn holds(&self, block: &Block) -> bool {
+        self.chain
+            .get_block_by_ordinal(block.header.ordinal)
+            .map_or(false, |held| held.header.hash() == block.header.hash())
+    }
+
+    /// Delete the orphaned block files of the storage directory, see `list_orphan_files`, and
+    /// rewrite the segment files holding orphaned blocks without them. Returns the deleted and
+    /// rewritten files.
+    pub fn gc_orphans(&self) -> Result<Vec<PathBuf>, BlockchainError> {
+        let block_dir = match self.config.block_dir() {
+            Some(block_dir) => block_dir,
+            None => return Ok(vec![]),
+        };
+        let orphans = self.list_orphan_files(block_dir)?;
+        for path in orphans.iter() {
+            if storage::is_segment_file(path) {
+                storage::retain_in_segment(path, |block| self.holds(block))
+            } else {
+                fs::remove_file(path)
+            }
+            .map_err(|e| BlockchainError::StorageFailure(e.to_string()))?;
+        }
+        debug!("Blockchain: deleted {} orphaned block files", orphans.len());
+        Ok(orphans)
+    }
+
+    /// Fold `f` over every committed transaction in chain order, starting from `init`, e.g. to
+    /// rebuild application state out of the transactions
+    pub fn fold_transactions<S>(&self, init: S, f: impl FnMut(S, &Transaction) -> S) -> S {
+        self.chain
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .fold(init, f)
+    }
+
+    /// The committed transactions with a timestamp within `from..=to`, in chain order. Every
+    /// block is scanned, as a block may carry a transaction of any age.
+    pub fn transactions_between(&self, from: u64, to: u64) -> Vec<&Transaction> {
+        self.chain
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .filter(|trans| (from..=to).contains(&trans.timestamp()))
+            .collect()
+    }
+
+    /// The blocks holding at least one transaction of type `type_id`, in chain order, e.g. to
+    /// find the latest block which added an authority
+    pub fn blocks_with_transaction_type(
+        &self,
+        type_id: TransactionType,
+    ) -> impl Iterator<Item = &Block> {
+        self.chain.iter().filter(move |block| {
+            block
+                .transactions
+                .iter()
+                .any(|trans| trans.type_id() == type_id)
+        })
+    }
+
+    /// The number of submitted transactions which have not been settled yet
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// A digest of the pending transactions, computed over their sorted hashes so that it does
+    /// not depend on the order they were submitted in. Peers with the same digest hold the
+    /// same pending transactions.
+    pub fn mempool_digest(&self) -> HashDigest {
+        let mut hashes: Vec<HashDigest> = self.pending.iter().map(|trans| trans.hash()).collect();
+        hashes.sort();
+        let bytes: Vec<u8> = hashes.iter().flat_map(|hash| hash.to_slice()).collect();
+        HashDigest::new(&bytes)
+    }
+
+    pub fn get_block_by_hash(&self, hash: &HashDigest) -> Option<&Block> {
+        self.chain.get_block_by_hash(hash)
+    }
+
+    pub fn get_block_by_ordinal(&self, ordinal: Ordinal) -> Option<&Block> {
+        self.chain.get_block_by_ordinal(ordinal)
+    }
+
+    /// The current authority set, derived from the `AddAuthority` and `RemoveAuthority`
+    /// transactions of the chain
+    pub fn authorities(&self) -> &HashSet<Address> {
+        &self.authorities
+    }
+
+    /// The transactions of the chain submitted by `addr`, in chain order. The transactions of
+    /// pruned blocks are not returned.
+    pub fn transactions_by_submitter(&self, addr: &Address) -> Vec<&Transaction> {
+        self.submitter_index
+            .get(addr)
+            .map(|located| {
+                located
+                    .iter()
+                    .filter_map(|(ordinal, hash)| {
+                        self.chain
+                            .get_block_by_ordinal(*ordinal)?
+                            .transactions
+                            .iter()
+                            .find(|trans| trans.hash() == *hash)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The receipt proving the transaction with `tx_hash` is part of the finalized chain,
+    /// anchored on the finalized block, see `Receipt::verify`. It holds the headers from the
+    /// block carrying the transaction to the finalized block only. None when nothing is
+    /// finalized yet, when no block up to the finalized one carries the transaction, or when
+    /// the blocks linking it to the finalized block were pruned.
+    pub fn receipt_for(&self, tx_hash: &HashDigest) -> Option<Receipt> {
+        let finalized = self.finalized?;
+        let (block, proof) = self
+            .chain
+            .blocks_in_range(0, finalized.ordinal)
+            .into_iter()
+            .find_map(|block| Some((block, block.inclusion_proof(tx_hash)?)))?;
+        let ordinal = block.header.ordinal;
+        let descendants: Vec<Header> = self
+            .chain
+            .blocks_in_range(ordinal + 1, finalized.ordinal)
+            .into_iter()
+            .map(|block| block.header)
+            .collect();
+        if descendants.len() as Ordinal != finalized.ordinal - ordinal {
+            return None;
+        }
+        Some(Receipt {
+            tx_hash: *tx_hash,
+            header: block.header,
+            signature: block.signature(),
+            proof,
+            descendants,
+        })
+    }
+
+    /// The revoked keys, derived from the `RevokeKey` transactions of the chain, with the
+    /// timestamp from which each is revoked
+    pub fn revocations(&self) -> &Revocations {
+        &self.revocations
+    }
+
+    /// The timestamp of a block built now on the tip of the chain: the current time, unless
+    /// that is not after the median time past, in which case the second after it
+    pub fn next_block_timestamp(&self) -> u64 {
+        let now = unix_timestamp();
+        match self.chain.median_time_past(MEDIAN_TIME_PAST_BLOCKS) {
+            Some(median) => now.max(median.saturating_add(1)),
+            None => now,
+        }
+    }
+
+    /// The authority whose turn it is to commit the block at `ordinal`, in a round-robin over
+    /// the current authority set. None when there are no authorities.
+    pub fn expected_committer(&self, ordinal: Ordinal) -> Option<Address> {
+        round_robin_committer(&self.authorities, ordinal)
+    }
+
+    // Whether `committer` may commit the block following `parent` at `timestamp`, which is
+    // its turn or, once the committer timeout elapsed, the turn of an authority it stands in for
+    fn may_commit_after(&self, parent: &Header, committer: &Address, timestamp: u64) -> bool {
+        permitted_committers(
+            &self.authorities,
+            parent.ordinal + 1,
+            timestamp.saturating_sub(parent.timestamp),
+            self.config.committer_timeout,
+        )
+        .contains(committer)
+    }
+
+    /// How many of the last `window` blocks each authority committed, e.g. to spot the
+    /// authorities which stopped committing. Every current authority is counted, those which
+    /// committed none of the blocks with 0.
+    pub fn recent_committers(&self, window: u128) -> HashMap<Address, usize> {
+        let mut counts: HashMap<Address, usize> = self
+            .authorities
+            .iter()
+            .map(|authority| (*authority, 0))
+            .collect();
+        let height = self.height();
+        let from = height.saturating_add(1).saturating_sub(window);
+        for block in self.chain.blocks_in_range(from, height) {
+            *counts.entry(block.header.committer).or_default() += 1;
+        }
+        counts
+    }
+
+    /// The time, in seconds, between the timestamps of consecutive blocks, from the genesis
+    /// block to block 1 on. Blocks separated by pruning are not consecutive, and a block
+    /// timestamped before its parent counts as an interval of 0.
+    pub fn block_intervals(&self) -> Vec<u64> {
+        let blocks: Vec<&Block> = self.chain.iter().collect();
+        blocks
+            .windows(2)
+            .filter(|pair| pair[1].header.ordinal == pair[0].header.ordinal + 1)
+            .map(|pair| {
+                pair[1]
+                    .header
+                    .timestamp
+                    .saturating_sub(pair[0].header.timestamp)
+            })
+            .collect()
+    }
+
+    /// The mean of the `block_intervals`, None when the chain holds only its genesis block
+    pub fn mean_block_interval(&self) -> Option<f64> {
+        let intervals = self.block_intervals();
+        if intervals.is_empty() {
+            return None;
+        }
+        Some(intervals.iter().sum::<u64>() as f64 / intervals.len() as f64)
+    }
+
+    /// The longest of the `block_intervals`, revealing stalls of block production
+    pub fn max_block_interval(&self) -> Option<u64> {
+        self.block_intervals().into_iter().max()
+    }
+
+    /// Capture the chain as it is now, to be queried e.g. by another task without holding the
+    /// lock of the blockchain. Taking the snapshot does not copy the blocks, the next block
+    /// appended while the snapshot is held does.
+    pub fn snapshot(&self) -> ChainSnapshot {
+        ChainSnapshot::new(self.chain.clone())
+    }
+
+    /// Capture the authority set together with the tip it reflects
+    pub fn state_snapshot(&self) -> StateSnapshot {
+        let tip = self.chain.last_block();
+        StateSnapshot {
+            authorities: self.authorities.clone(),
+            tip_hash: tip
+                .as_ref()
+                .map(|block| block.header.hash())
+                .unwrap_or_else(|| HashDigest::new(b"")),
+            tip_ordinal: tip.map(|block| block.header.ordinal).unwrap_or_default(),
+            revocations: self.revocations.clone(),
+        }
+    }
+
+    /// Restore the authority set from a trusted `snapshot`, replaying only the blocks of the
+    /// chain which come after the snapshot's tip. The tip must be a block of the chain.
+    pub fn restore_from_snapshot(
+        &mut self,
+        snapshot: StateSnapshot,
+    ) -> Result<(), BlockchainError> {
+        match self.chain.get_block_by_hash(&snapshot.tip_hash) {
+            Some(tip) if tip.header.ordinal == snapshot.tip_ordinal => (),
+            _ => return Err(BlockchainError::UnknownSnapshotTip(snapshot.tip_hash)),
+        }
+
+        let mut authorities = snapshot.authorities;
+        let mut revocations = snapshot.revocations;
+        for block in self
+            .chain
+            .blocks_in_range(snapshot.tip_ordinal + 1, Ordinal::MAX)
+        {
+            apply_authority_transactions(&mut authorities, block);
+            apply_revocations(&mut revocations, block);
+        }
+        self.authorities = authorities;
+        self.revocations = revocations;
+        Ok(())
+    }
+
+    /// The blocks with an ordinal within `from_ordinal..=to_ordinal`, clamped to the blocks
+    /// available, e.g. to send a peer that is behind the blocks it misses
+    pub fn blocks_in_range(&self, from_ordinal: Ordinal, to_ordinal: Ordinal) -> Vec<&Block> {
+        self.chain.blocks_in_range(from_ordinal, to_ordinal)
+    }
+
+    /// Append `blocks` received from a peer after the tip of the chain. The blocks must follow
+    /// each other without gaps and link to their parent, otherwise none of them is appended.
+    /// The pending transactions carried by the blocks are settled.
+    pub async fn apply_blocks(&mut self, blocks: Vec<Block>) -> Result<(), BlockchainError> {
+        let parent = match self.chain.last_block() {
+            Some(block) => block.header,
+            None => return Err(BlockchainError::MissingGenesis),
+        };
+        let mut acceptance = BlockAcceptance::new(
+            &self.chain,
+            parent,
+            self.authorities.clone(),
+            self.revocations.clone(),
+        );
+        for block in blocks.iter() {
+            acceptance.accept(&self.config, block)?;
+        }
+
+        for block in blocks {
+            self.commit_block(block.clone()).await;
+            for trans in block.transactions {
+                self.notify_transaction_settled(trans);
+            }
+        }
+        Ok(())
+    }
+
+    /// Append `block`, built and signed by a peer, after the tip of the chain. The block is
+    /// checked like the blocks of `apply_blocks`, including that it is committed by the
+    /// authority whose turn it is, and appended as is. A block taking the ordinal of a block
+    /// of the chain is rejected, replacing blocks is left to `consider_chain`.
+    pub async fn append_block(&mut self, block: Block) -> Result<(), BlockchainError> {
+        if self
+            .chain
+            .get_block_by_ordinal(block.header.ordinal)
+            .is_some()
+        {
+            let e = BlockchainError::DuplicateOrdinal {
+                ordinal: block.header.ordinal,
+            };
+            log_rejected_block(&block, &e);
+            return Err(e);
+        }
+        self.apply_blocks(vec![block]).await
+    }
+
+    /// Append the block received from a peer as the JSON `raw`, like `append_block`. The block
+    /// is parsed with `Block::try_parse` and rejected unless `raw` is its canonical
+    /// serialization, see `Block::is_canonical`.
+    pub async fn append_raw_block(&mut self, raw: &[u8]) -> Result<(), BlockchainError> {
+        let block = Block::try_parse(raw).map_err(|e| match e {
+            BlockError::Deserialization(e) => BlockchainError::Serialization(e),
+            e => BlockchainError::Block(e),
+        })?;
+        if !block.is_canonical(raw) {
+            let e = BlockchainError::NonCanonicalBlock(block.header.ordinal);
+            log_rejected_block(&block, &e);
+            return Err(e);
+        }
+        self.append_block(block).await
+    }
+
+    pub fn genesis_hash(&self) -> HashDigest {
+        self.chain
+            .get_block_by_ordinal(0)
+            .map(|genesis| genesis.header.hash())
+            .expect("a blockchain to always have a genesis block")
+    }
+
+    /// Mark the block at `ordinal`, and every block before it, as final so that no competing
+    /// chain can replace them anymore
+    pub fn finalize(&mut self, ordinal: Ordinal) -> Result<Checkpoint, BlockchainError> {
+        if let Some(finalized) = self.finalized {
+            if ordinal < finalized.ordinal {
+                return Err(BlockchainError::BelowFinality {
+                    ordinal,
+                    finalized: finalized.ordinal,
+                });
+            }
+        }
+        let block = self
+            .chain
+            .get_block_by_ordinal(ordinal)
+            .ok_or(BlockchainError::UnknownBlock(ordinal))?;
+        let checkpoint = Checkpoint {
+            ordinal,
+            hash: block.header.hash(),
+        };
+        self.finalized = Some(checkpoint);
+        Ok(checkpoint)
+    }
+
+    /// The last finalized block, None when nothing was finalized yet
+    pub fn finalized(&self) -> Option<Checkpoint> {
+        self.finalized
+    }
+
+    /// Adopt `chain` received from a peer when it has a higher total difficulty than ours,
+    /// returning whether it was adopted. A chain starting from another genesis, or diverging
+    /// from our chain before the finalized block, is rejected right away. The blocks from the
+    /// fork on are checked like the blocks of `apply_blocks`, and the chain is rejected unless
+    /// every one of them is accepted. Once adopted, the pending transactions carried by the
+    /// chain are settled and those of our replaced blocks are pending again, like after
+    /// `rollback_to`, and the replaced blocks are dropped from the store. Once our chain is
+    /// pruned, `chain` shares our pruned blocks when it holds the pruning checkpoint block, a
+    /// chain diverging before it is not adopted.
+    pub async fn consider_chain(&mut self, chain: Chain) -> Result<bool, BlockchainError> {
+        self.check_chain(&chain).map_err(|e| {
+            if let Some(block) = e
+                .ordinal()
+                .and_then(|ordinal| chain.get_block_by_ordinal(ordinal))
+            {
+                log_rejected_block(block, &e);
+            }
+            e
+        })?;
+        // the blocks both chains share weigh the same, comparing the difficulty of the blocks
+        // after the common ancestor is comparing the total difficulty of the chains
+        let ancestor = match self.fork_point(&chain) {
+            Some(ancestor) => ancestor,
+            None => return Ok(false),
+        };
+        let fork_ordinal = ancestor + 1;
+        if chain.difficulty_from(fork_ordinal) <= self.chain.difficulty_from(fork_ordinal) {
+            return Ok(false);
+        }
+        let new_blocks: Vec<Block> = chain
+            .iter()
+            .filter(|block| block.header.ordinal >= fork_ordinal)
+            .cloned()
+            .collect();
+
+        let parent = chain
+            .get_block_by_ordinal(ancestor)
+            .map(|block| block.header)
+            .expect("the common ancestor to be part of both chains");
+        let (authorities, revocations) = match self.replay_state(ancestor) {
+            Some(state) => state,
+            None => return Ok(false),
+        };
+        let mut acceptance = BlockAcceptance::new(&chain, parent, authorities, revocations);
+        for block in new_blocks.iter() {
+            acceptance.accept(&self.config, block)?;
+        }
+        let BlockAcceptance {
+            authorities,
+            revocations,
+            ..
+        } = acceptance;
+
+        // our blocks up to the ancestor are kept, along with our pruning checkpoint
+        let ours = Arc::make_mut(&mut self.chain);
+        let replaced = ours.truncate(ancestor);
+        for block in new_blocks.iter() {
+            ours.add_block(block.clone());
+        }
+        self.authorities = authorities;
+        self.revocations = revocations;
+        self.submitter_index = build_submitter_index(self.chain.iter());
+        self.requeue_transactions(&replaced);
+        self.remove_stored_blocks(&replaced);
+        for trans in new_blocks
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+        {
+            self.notify_transaction_settled(trans.clone());
+        }
+        debug!(
+            "Blockchain: replaced {} blocks from {} by {} blocks",
+            replaced.len(),
+            fork_ordinal,
+            new_blocks.len()
+        );
+        for block in new_blocks {
+            self.store_block(&block);
+            self.notify_block_event(&block);
+            self.send_block_event(&block).await;
+            for trans in block.transactions {
+                self.notify_payload_event(&trans.payload()).await;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Write the blocks of the chain, genesis first, to `writer` in `format`
+    pub fn export(
+        &self,
+        mut writer: impl Write,
+        format: ExportFormat,
+    ) -> Result<(), BlockchainError> {
+        for block in self.chain.blocks() {
+            storage::write_exported_block(&mut writer, &block, format)?;
+        }
+        writer
+            .flush()
+            .map_err(|e| BlockchainError::StorageFailure(e.to_string()))
+    }
+
+    /// Build a blockchain out of the blocks exported to `reader` in `format`, checking every
+    /// block links to the previous one as it is read
+    pub fn import(
+        mut reader: impl BufRead,
+        format: ExportFormat,
+    ) -> Result<Blockchain, BlockchainError> {
+        let genesis = storage::read_exported_block(&mut reader, format)?
+            .ok_or(BlockchainError::MissingGenesis)?;
+        check_genesis(&genesis)?;
+        let mut blockchain = Blockchain::from_genesis(genesis, Default::default(), None)?;
+        while let Some(block) = storage::read_exported_block(&mut reader, format)? {
+            blockchain.restore_block(block)?;
+        }
+        Ok(blockchain)
+    }
+
+    /// Rebuild the blockchain with `config` out of the blocks stored in its block directory,
+    /// see `BlockchainConfig::block_dir`, checking every block links to the previous one as
+    /// it is read. The blocks appended afterwards are stored there too. Blocks left over from
+    /// a replaced fork fail the opening, see `open_skipping_orphans`.
+    pub fn open(config: BlockchainConfig) -> Result<Blockchain, BlockchainError> {
+        Self::open_blocks(config, None)
+    }
+
+    /// Rebuild the blockchain like `open`, out of the stored blocks making up the chain of the
+    /// highest total difficulty from the stored genesis block. The blocks of replaced forks,
+    /// and the blocks which do not link to a stored parent, are left out and left on disk, see
+    /// `list_orphan_files`. Meant to inspect a storage directory which can not be opened.
+    pub fn open_skipping_orphans(config: BlockchainConfig) -> Result<Blockchain, BlockchainError> {
+        let chain = match config.block_dir() {
+            Some(block_dir) => Some(find_stored_chain(&block_dir)?),
+            None => None,
+        };
+        Self::open_blocks(config, chain)
+    }
+
+    // Rebuild the blockchain with `config` out of its stored blocks, only those with a hash
+    // of `chain` when given
+    fn open_blocks(
+        config: BlockchainConfig,
+        chain: Option<HashSet<HashDigest>>,
+    ) -> Result<Blockchain, BlockchainError> {
+        config.validate()?;
+        let block_dir = config.block_dir().ok_or_else(|| {
+            BlockchainError::InvalidConfig("opening a blockchain needs a storage_dir".to_string())
+        })?;
+        let mut blocks =
+            Self::iter_blocks_from_disk(&block_dir).filter(|block| match (block, &chain) {
+                (Ok(block), Some(chain)) => chain.contains(&block.header.hash()),
+                _ => true,
+            });
+        let genesis = blocks.next().ok_or(BlockchainError::MissingGenesis)??;
+        check_genesis(&genesis)?;
+        let store = Box::new(FileBlockStore::new(block_dir)) as Box<dyn BlockStore>;
+        let mut blockchain = Blockchain::from_genesis(genesis, config, Some(store))?;
+        for block in blocks {
+            blockchain.restore_block(block?)?;
+        }
+        Ok(blockchain)
+    }
+
+    // Append `block`, read back from an export or the storage, after the tip of the chain
+    fn restore_block(&mut self, block: Block) -> Result<(), BlockchainError> {
+        let parent = self
+            .chain
+            .last_block()
+            .ok_or(BlockchainError::MissingGenesis)?;
+        check_successor(&parent.header, &block)?;
+        apply_authority_transactions(&mut self.authorities, &block);
+        apply_revocations(&mut self.revocations, &block);
+        index_submitters(&mut self.submitter_index, &block);
+        Arc::make_mut(&mut self.chain).add_block(block);
+        Ok(())
+    }
+
+    /// Read the blocks stored under `dir` in ordinal order, one file at a time, so the whole
+    /// chain never has to be held in memory
+    pub fn iter_blocks_from_disk(
+        dir: impl AsRef<Path>,
+    ) -> impl Iterator<Item = Result<Block, BlockchainError>> {
+        storage::read_block_files(dir.as_ref())
+            .map(|block| block.map_err(|e| BlockchainError::StorageFailure(e.to_string())))
+    }
+
+    /// Check the chain is consistent on its own, from the genesis block to the tip: the blocks
+    /// link to each other, their ordinals follow each other and every block passes
+    /// `Block::verify`. The error names the first block found failing and what it fails.
+    pub fn validate_chain(&self) -> Result<(), ChainError> {
+        self.chain.verify_integrity()
+    }
+
+    /// Check the health of the chain, reporting every failure found rather than stopping at
+    /// the first one. The blocks are checked against their stored copy when they are stored.
+    pub fn self_check(&self) -> SelfCheckReport {
+        let checkpoint = self.chain.checkpoint();
+        let blocks: Vec<&Block> = self.chain.iter().collect();
+        let mut linkage = vec![];
+        let mut gaps = vec![];
+        for pair in blocks.windows(2) {
+            let (parent, child) = (&pair[0].header, &pair[1].header);
+            let expected_parent_hash = match checkpoint {
+                _ if child.ordinal == parent.ordinal + 1 => parent.hash(),
+                Some(checkpoint)
+                    if parent.ordinal < checkpoint.ordinal
+                        && child.ordinal == checkpoint.ordinal + 1 =>
+                {
+                    checkpoint.hash
+                }
+                _ => {
+                    gaps.push(format!(
+                        "blocks {} to {} are missing",
+                        parent.ordinal + 1,
+                        child.ordinal - 1
+                    ));
+                    continue;
+                }
+            };
+            if child.parent_hash != expected_parent_hash {
+                linkage.push(format!(
+                    "block {} does not link to its parent",
+                    child.ordinal
+                ));
+            }
+        }
+
+        let mut tip_hash = vec![];
+        match blocks.last() {
+            Some(tip) if !tip.header.verify_hash() => tip_hash.push(format!(
+                "the hash of block {} does not match its header",
+                tip.header.ordinal
+            )),
+            Some(_) => (),
+            None => tip_hash.push("the chain has no blocks".to_string()),
+        }
+
+        let mut block_files = vec![];
+        if let Some(store) = &self.store {
+            let hashes: Vec<HashDigest> = blocks.iter().map(|block| block.header.hash()).collect();
+            for (block, stored) in blocks.iter().zip(store.get_all(&hashes)) {
+                let ordinal = block.header.ordinal;
+                match stored {
+                    Ok(Some(stored)) if stored == **block => (),
+                    Ok(Some(_)) => block_files
+                        .push(format!("block {} is stored with another content", ordinal)),
+                    Ok(None) => block_files.push(format!("block {} is not stored", ordinal)),
+                    Err(e) => block_files.push(format!("block {} can not be read: {}", ordinal, e)),
+                }
+            }
+        }
+
+        let mut authorities = vec![];
+        if self.authorities.is_empty() {
+            authorities.push("the authority set is empty".to_string());
+        }
+
+        let mut orphans = vec![];
+        if let Some(block_dir) = self.config.block_dir() {
+            match self.list_orphan_files(block_dir) {
+                Ok(files) => orphans.extend(files.iter().map(|path| {
+                    if storage::is_segment_file(path) {
+                        format!(
+                            "{} holds blocks which are not part of the chain",
+                            path.display()
+                        )
+                    } else {
+                        format!("{} is not part of the chain", path.display())
+                    }
+                })),
+                Err(e) => orphans.push(format!("the block files can not be listed: {}", e)),
+            }
+        }
+
+        SelfCheckReport {
+            linkage: CheckOutcome::from_failures(linkage),
+            tip_hash: CheckOutcome::from_failures(tip_hash),
+            block_files: CheckOutcome::from_failures(block_files),
+            authorities: CheckOutcome::from_failures(authorities),
+            gaps: CheckOutcome::from_failures(gaps),
+            orphans: CheckOutcome::from_failures(orphans),
+        }
+    }
+
+    /// Merge the stored blocks into segment files of `segment_size` consecutive blocks, so a
+    /// long chain is not stored as one file per block. The blocks appended afterwards are
+    /// stored in their own file until the next compaction.
+    pub fn compact(&mut self, segment_size: u128) -> Result<(), BlockchainError> {
+        if segment_size == 0 {
+            return Err(BlockchainError::InvalidConfig(
+                "segment_size must be at least 1".to_string(),
+            ));
+        }
+        if let Some(store) = self.store.as_mut() {
+            store
+                .compact(segment_size)
+                .map_err(|e| BlockchainError::StorageFailure(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Drop all blocks but genesis and the last `keep_last` blocks, at least the tip, from
+    /// memory and from the store. The authority set and revocations as of the last pruned
+    /// block are kept, for the state to be replayed from it. When blocks are written
+    /// asynchronously the pruned blocks are left to `gc_orphans`. The storage directory of a
+    /// pruned chain lacks the blocks `open` needs to rebuild the chain from genesis.
+    pub fn prune(&mut self, keep_last: u128) -> &mut Self {
+        let pruned = Arc::make_mut(&mut self.chain).prune(keep_last);
+        if let Some(last_pruned) = pruned.last() {
+            let (mut authorities, mut revocations) = match self.pruned_state.take() {
+                Some(state) => (state.authorities, state.revocations),
+                None => {
+                    let genesis = self.chain.get_block_by_ordinal(0);
+                    (replay_authorities(genesis), replay_revocations(genesis))
+                }
+            };
+            for block in pruned.iter() {
+                apply_authority_transactions(&mut authorities, block);
+                apply_revocations(&mut revocations, block);
+            }
+            self.pruned_state = Some(StateSnapshot {
+                authorities,
+                tip_hash: last_pruned.header.hash(),
+                tip_ordinal: last_pruned.header.ordinal,
+                revocations,
+            });
+            self.submitter_index = build_submitter_index(self.chain.iter());
+        }
+        self.remove_stored_blocks(&pruned);
+        debug!("Blockchain: pruned {} blocks", pruned.len());
+        self
+    }
+
+    /// Revert the chain to the block at `ordinal`, removing the blocks after it and returning
+    /// them in chain order. Their transactions are pending again, ahead of the transactions
+    /// already pending, and the authority set, revocations and submitter index are derived
+    /// anew from the remaining blocks. The removed blocks are dropped from the store, when
+    /// blocks are written asynchronously they are left to `gc_orphans`. Reverting the
+    /// finalized block is rejected with `BelowFinality`, and reverting to a block before the
+    /// pruning checkpoint, genesis included, with `UnknownBlock`.
+    pub fn rollback_to(&mut self, ordinal: Ordinal) -> Result<Vec<Block>, BlockchainError> {
+        if let Some(finalized) = self.finalized {
+            if ordinal < finalized.ordinal {
+                return Err(BlockchainError::BelowFinality {
+                    ordinal,
+                    finalized: finalized.ordinal,
+                });
+            }
+        }
+        if self.chain.get_block_by_ordinal(ordinal).is_none() {
+            return Err(BlockchainError::UnknownBlock(ordinal));
+        }
+        let (authorities, revocations) = self
+            .replay_state(ordinal)
+            .ok_or(BlockchainError::UnknownBlock(ordinal))?;
+
+        let removed = Arc::make_mut(&mut self.chain).truncate(ordinal);
+        self.authorities = authorities;
+        self.revocations = revocations;
+        self.submitter_index = build_submitter_index(self.chain.iter());
+        self.requeue_transactions(&removed);
+        self.remove_stored_blocks(&removed);
+        debug!(
+            "Blockchain: rolled back {} blocks to {}",
+            removed.len(),
+            ordinal
+        );
+        Ok(removed)
+    }
+
+    /// Add block after receiving payload and keypair
+    pub async fn add_block(
+        &mut self,
+        payload: Vec<u8>,
+        local_key: identity::Keypair,
+    ) -> anyhow::Result<()> {
+        let submitter = Address::from(local_key.public());
+        let ed25519_key = match local_key {
+            Ed25519(some) => some,
+            _ => {
+                anyhow::bail!(
+                    "Blockchain: Key {:?} is not valid Ed25519 format",
+                    local_key
+                );
+            }
+        };
+        let trans_vec = vec![TransactionBuilder::new(TransactionType::Create)
+            .submitter(submitter)
+            .payload(payload)
+            .network_salt(self.config.network_salt())
+            .sign(&ed25519_key)?];
+
+        let last_block = match self.chain.last_block() {
+            Some(block) => block,
+            None => {
+                anyhow::bail!("Blockchain: Local blockchain does non exist!!");
+            }
+        };
+
+        let ordinal = last_block.header.ordinal + 1;
+        let timestamp = self.next_block_timestamp();
+        if !self.may_commit_after(&last_block.header, &submitter, timestamp) {
+            return Err(BlockchainError::UnexpectedCommitter {
+                ordinal,
+                committer: submitter,
+            }
+            .into());
+        }
+
+        let block = Block::new_with(
+            last_block.header.hash(),
+            ordinal,
+            trans_vec,
+            &ed25519_key,
+            timestamp,
+            rand::random::<u128>(),
+        )?;
+        check_authority_count(self.config.min_authorities, &self.authorities)?;
+        check_weight(self.config.max_block_weight, &block)?;
+
+        let span = debug_span!(
+            "add_block",
+            ordinal = %block.header.ordinal,
+            hash = ?block.header.hash(),
+            transactions = block.transactions.len()
+        );
+        // TODO: Consensus algorithm will be refactored
+        self.commit_block(block).instrument(span).await;
+        Ok(())
+    }
+
+    /// Build a block out of the pending transactions, signed with `local_key`, and commit it.
+    /// The transactions of the highest priority are included first, in submission order for
+    /// an equal priority, up to the maximum number of transactions per block. Authority
+    /// changes submitted by a non-authority are rejected. Returns None when there are no
+    /// pending transactions left to commit.
+    pub async fn produce_block(
+        &mut self,
+        local_key: &identity::ed25519::Keypair,
+    ) -> Result<Option<Block>, BlockchainError> {
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+        check_authority_count(self.config.min_authorities, &self.authorities)?;
+        let parent = self
+            .chain
+            .last_block()
+            .ok_or(BlockchainError::MissingGenesis)?;
+        let ordinal = parent.header.ordinal + 1;
+        let committer = Address::from(identity::PublicKey::Ed25519(local_key.public()));
+        let timestamp = self.next_block_timestamp();
+        if !self.may_commit_after(&parent.header, &committer, timestamp) {
+            return Err(BlockchainError::UnexpectedCommitter { ordinal, committer });
+        }
+
+        // the transactions whose dependencies are not settled yet stay pending
+        let (mut by_priority, unauthorized): (Vec<Transaction>, Vec<Transaction>) = self
+            .pending
+            .iter()
+            .filter(|trans| self.dependencies_settled(trans))
+            .cloned()
+            .partition(|trans| self.may_submit(trans));
+        for trans in unauthorized {
+            let reason = unauthorized_reason(&trans);
+            self.reject_transaction(trans, reason);
+        }
+        // a transaction weighing more than a whole block can never be committed
+        let (mut by_priority, overweight): (Vec<Transaction>, Vec<Transaction>) = by_priority
+            .into_iter()
+            .partition(|trans| self.fits_in_block(trans.weight()));
+        for trans in overweight {
+            let reason = format!(
+                "the transaction weighs {}, over the maximum block weight",
+                trans.weight()
+            );
+            self.reject_transaction(trans, reason);
+        }
+        if by_priority.is_empty() {
+            return Ok(None);
+        }
+
+        // a stable sort keeps the submission order among transactions of equal priority
+        by_priority.sort_by_key(|trans| std::cmp::Reverse(trans.priority()));
+        by_priority.truncate(self.config.max_transactions_per_block);
+        // the transactions following the first one not fitting in the block wait for the next
+        let mut weight = 0;
+        let by_priority: Vec<Transaction> = by_priority
+            .into_iter()
+            .take_while(|trans| {
+                weight += trans.weight();
+                self.fits_in_block(weight)
+            })
+            .collect();
+
+        let block = Block::new_with(
+            parent.header.hash(),
+            ordinal,
+            by_priority,
+            local_key,
+            timestamp,
+            rand::random::<u128>(),
+        )?;
+        let span = debug_span!(
+            "produce_block",
+            ordinal = %block.header.ordinal,
+            hash = ?block.header.hash(),
+            transactions = block.transactions.len()
+        );
+        self.commit_block(block.clone()).instrument(span).await;
+        for trans in block.transactions.iter() {
+            self.notify_transaction_settled(trans.clone());
+        }
+        Ok(Some(block))
+    }
+
+    /// Commit all pending transactions into blocks signed with `local_key` and make sure every
+    /// block is durably stored, e.g. before the node shuts down. Returns the blocks produced.
+    pub async fn flush(
+        &mut self,
+        local_key: &identity::ed25519::Keypair,
+    ) -> Result<Vec<Block>, BlockchainError> {
+        let mut blocks = vec![];
+        while let Some(block) = self.produce_block(local_key).await? {
+            blocks.push(block);
+        }
+        self.flush_writes().await?;
+        if let Some(store) = self.store.as_mut() {
+            store
+                .flush()
+                .map_err(|e| BlockchainError::StorageFailure(e.to_string()))?;
+        }
+        Ok(blocks)
+    }
+
+    /// Wait until every block appended so far is durably stored, when blocks are written
+    /// asynchronously. Returns right away otherwise, the blocks being stored as they are
+    /// appended.
+    pub async fn flush_writes(&self) -> Result<(), BlockchainError> {
+        if let Some(writer) = &self.writer {
+            writer
+                .flush()
+                .await
+                .map_err(|e| BlockchainError::StorageFailure(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    // The authority set and revocations as of the block at `ordinal` of the chain, replayed
+    // from the state at the pruning checkpoint once blocks were pruned. None when the block
+    // precedes the checkpoint, its state being pruned with it.
+    fn replay_state(&self, ordinal: Ordinal) -> Option<(HashSet<Address>, Revocations)> {
+        let (mut authorities, mut revocations, from) = match &self.pruned_state {
+            Some(state) if ordinal < state.tip_ordinal => return None,
+            Some(state) => (
+                state.authorities.clone(),
+                state.revocations.clone(),
+                state.tip_ordinal + 1,
+            ),
+            None => (HashSet::new(), Revocations::new(), 0),
+        };
+        for block in self.chain.blocks_in_range(from, ordinal) {
+            apply_authority_transactions(&mut authorities, block);
+            apply_revocations(&mut revocations, block);
+        }
+        Some((authorities, revocations))
+    }
+
+    // Whether a block of `weight` is within the maximum block weight
+    fn fits_in_block(&self, weight: u64) -> bool {
+        self.config
+            .max_block_weight
+            .map_or(true, |max_block_weight| weight <= max_block_weight)
+    }
+
+    // Whether every transaction `trans` depends on is committed to the chain
+    fn dependencies_settled(&self, trans: &Transaction) -> bool {
+        trans
+            .depends_on()
+            .iter()
+            .all(|dependency| self.chain.contains_transaction(dependency))
+    }
+
+    // Whether the submitter of `trans` may submit a transaction of its type
+    fn may_submit(&self, trans: &Transaction) -> bool {
+        match trans.type_id() {
+            TransactionType::Create => true,
+            // any key may revoke itself
+            TransactionType::RevokeKey if trans.payload() == trans.submitter().to_bytes() => true,
+            _ => self.authorities.contains(&trans.submitter()),
+        }
+    }
+
+    // Make the transactions of `removed`, blocks removed from the chain, pending again ahead of
+    // the transactions already pending, unless the chain carries them anyway
+    fn requeue_transactions(&mut self, removed: &[Block]) {
+        let requeued: Vec<Transaction> = removed
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .filter(|trans| {
+                !self.pending.contains(trans) && !self.chain.contains_transaction(&trans.hash())
+            })
+            .cloned()
+            .collect();
+        self.pending.splice(0..0, requeued);
+    }
+
+    // Drop `removed`, blocks removed from the chain, from the block store. When blocks are
+    // written asynchronously they are left to `gc_orphans`.
+    fn remove_stored_blocks(&mut self, removed: &[Block]) {
+        if let Some(store) = self.store.as_mut() {
+            for block in removed.iter() {
+                if let Err(e) = store.remove(&block.header.hash()) {
+                    warn!(
+                        "Blockchain: failed to remove block {}: {}",
+                        block.header.ordinal, e
+                    );
+                }
+            }
+        }
+    }
+
+    // Drop the pending `trans` and notify its observer of the rejection
+    fn reject_transaction(&mut self, trans: Transaction, reason: String) {
+        self.pending.retain(|pending| *pending != trans);
+        if let Some(on_rejected) = self.trans_observers.remove(&trans) {
+            on_rejected(trans, TransactionOutcome::Rejected { reason })
+        }
+    }
+
+    /// Commit block and notify block listeners
+    async fn commit_block(&mut self, block: Block) {
+        Arc::make_mut(&mut self.chain).add_block(block.clone());
+        apply_authority_transactions(&mut self.authorities, &block);
+        apply_revocations(&mut self.revocations, &block);
+        index_submitters(&mut self.submitter_index, &block);
+        self.store_block(&block);
+        self.notify_block_event(&block);
+        self.send_block_event(&block).await;
+
+        for trans in block.transactions {
+            self.notify_payload_event(&trans.payload()).await;
+        }
+    }
+
+    // Persist `block` to the block store, or queue it to the writer, a failure does not undo
+    // the commit of the block
+    fn store_block(&mut self, block: &Block) {
+        let stored = match (&self.writer, self.store.as_mut()) {
+            (Some(writer), _) => writer.put(block.clone()),
+            (None, Some(store)) => store.put(block),
+            (None, None) => Ok(()),
+        };
+        if let Err(e) = stored {
+            warn!(
+                "Blockchain: failed to store block {}: {}",
+                block.header.ordinal, e
+            );
+        }
+    }
+
+    // The ordinal of the last block `chain` shares with ours. Our pruned blocks count as shared
+    // when `chain` holds the pruning checkpoint block. None when the chains only share
+    // genesis before the checkpoint, the state to check `chain` from being pruned.
+    fn fork_point(&self, chain: &Chain) -> Option<Ordinal> {
+        let (ordinal, _) = self.chain.common_ancestor(chain)?;
+        match self.chain.checkpoint() {
+            Some(checkpoint) if ordinal < checkpoint.ordinal => chain
+                .get_block_by_ordinal(checkpoint.ordinal)
+                .filter(|block| block.header.hash() == checkpoint.hash)
+                .map(|_| checkpoint.ordinal),
+            _ => Some(ordinal),
+        }
+    }
+
+    // Check `chain` starts from our genesis and keeps our finalized block
+    fn check_chain(&self, chain: &Chain) -> Result<(), ChainError> {
+        let genesis_hash = self.genesis_hash();
+        let their_genesis = chain.get_block_by_ordinal(0).ok_or(ChainError::Empty)?;
+        if their_genesis.header.hash() != genesis_hash {
+            return Err(ChainError::GenesisMismatch {
+                expected: genesis_hash,
+                actual: their_genesis.header.hash(),
+            });
+        }
+        if let Some(finalized) = self.finalized {
+            // the linkage of their chain is validated below, so sharing the finalized block
+            // means sharing every block before it as well
+            let shares_finalized = chain
+                .get_block_by_ordinal(finalized.ordinal)
+                .map(|block| block.header.hash() == finalized.hash)
+                .unwrap_or(false);
+            if !shares_finalized {
+                return Err(ChainError::FinalityViolation(finalized.ordinal));
+            }
+        }
+        Ok(())
+    }
+}
+
+// The state the blocks following `parent` are checked against before they are accepted into a
+// chain, shared by `Blockchain::apply_blocks` and `Blockchain::consider_chain` so that a block
+// passes the same checks whichever way it arrives. Each accepted block advances the state, as
+// it may change the authority set and whose turn the next block is.
+struct BlockAcceptance<'a> {
+    // the blocks of the chain up to `parent`, whose transactions are settled
+    preceding: Vec<&'a Block>,
+    parent: Header,
+    authorities: HashSet<Address>,
+    revocations: Revocations,
+    // the transactions of the blocks accepted so far, settled for the blocks after them
+    settled: HashSet<HashDigest>,
+    // the timestamps of the last blocks, the median of which the next block may not precede
+    recent_timestamps: VecDeque<u64>,
+}
+
+impl<'a> BlockAcceptance<'a> {
+    // The state as of `parent`, a block of `chain` with `authorities` and `revocations`
+    fn new(
+        chain: &'a Chain,
+        parent: Header,
+        authorities: HashSet<Address>,
+        revocations: Revocations,
+    ) -> Self {
+        let preceding = chain.blocks_in_range(0, parent.ordinal);
+        let recent_timestamps = preceding
+            .iter()
+            .skip(preceding.len().saturating_sub(MEDIAN_TIME_PAST_BLOCKS))
+            .map(|block| block.header.timestamp)
+            .collect();
+        Self {
+            preceding,
+            parent,
+            authorities,
+            revocations,
+            settled: HashSet::new(),
+            recent_timestamps,
+        }
+    }
+
+    // Check `block` may follow the blocks accepted so far under `config` and, when it may,
+    // accept it
+    fn accept(&mut self, config: &BlockchainConfig, block: &Block) -> Result<(), BlockchainError> {
+        let median_time_past = median_time(self.recent_timestamps.iter().copied());
+        if let Err(e) = check_authority_count(config.min_authorities, &self.authorities)
+            .and_then(|_| check_successor(&self.parent, block))
+            .and_then(|_| check_timestamp(median_time_past, config.timestamp_skew, block))
+            .and_then(|_| {
+                check_committer(
+                    &self.authorities,
+                    &self.parent,
+                    block,
+                    config.committer_timeout,
+                )
+            })
+            .and_then(|_| check_revocations(&self.revocations, block))
+            .and_then(|_| check_network(&config.network_salt(), block))
+            .and_then(|_| check_weight(config.max_block_weight, block))
+            .and_then(|_| self.check_replays(block))
+            .and_then(|_| self.check_dependencies(block))
+        {
+            log_rejected_block(block, &e);
+            return Err(e);
+        }
+        apply_authority_transactions(&mut self.authorities, block);
+        apply_revocations(&mut self.revocations, block);
+        self.settled
+            .extend(block.transactions.iter().map(|trans| trans.hash()));
+        self.recent_timestamps.push_back(block.header.timestamp);
+        if self.recent_timestamps.len() > MEDIAN_TIME_PAST_BLOCKS {
+            self.recent_timestamps.pop_front();
+        }
+        self.parent = block.header;
+        Ok(())
+    }
+
+    // Check none of the transactions of `block` is settled already, so a committed transaction
+    // can not be replayed
+    fn check_replays(&self, block: &Block) -> Result<(), BlockchainError> {
+        match block
+            .transactions
+            .iter()
+            .find(|trans| self.is_settled(&trans.hash()))
+        {
+            Some(trans) => Err(BlockchainError::CommittedTransaction {
+                ordinal: block.header.ordinal,
+                transaction: trans.hash(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // Check every dependency of the transactions of `block` is settled, either in a block
+    // accepted before it or in the chain up to the first of them
+    fn check_dependencies(&self, block: &Block) -> Result<(), BlockchainError> {
+        for trans in block.transactions.iter() {
+            if let Some(dependency) = trans
+                .depends_on()
+                .iter()
+                .find(|dependency| !self.is_settled(dependency))
+            {
+                return Err(BlockchainError::UnsettledDependency {
+                    ordinal: block.header.ordinal,
+                    transaction: trans.hash(),
+                    dependency: *dependency,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn is_settled(&self, hash: &HashDigest) -> bool {
+        self.settled.contains(hash)
+            || self
+                .preceding
+                .iter()
+                .flat_map(|block| block.transactions.iter())
+                .any(|trans| trans.hash() == *hash)
+    }
+}
+
+// The ordinal of the block carrying each transaction and its hash, by submitter
+type SubmitterIndex = HashMap<Address, Vec<(Ordinal, HashDigest)>>;
+
+// Add the transactions of `block`, which follows the blocks already indexed, to `index`
+fn index_submitters(index: &mut SubmitterIndex, block: &Block) {
+    for trans in block.transactions.iter() {
+        index
+            .entry(trans.submitter())
+            .or_default()
+            .push((block.header.ordinal, trans.hash()));
+    }
+}
+
+// Index the transactions of `blocks`, given in chain order, by submitter
+fn build_submitter_index<'a>(blocks: impl IntoIterator<Item = &'a Block>) -> SubmitterIndex {
+    let mut index = SubmitterIndex::new();
+    for block in blocks {
+        index_submitters(&mut index, block);
+    }
+    index
+}
+
+// Why `trans` may not be committed, when its submitter may not submit a transaction of its type
+fn unauthorized_reason(trans: &Transaction) -> String {
+    format!(
+        "{:?} is not an authority and may not change the authority set",
+        trans.submitter()
+    )
+}
+
+// Why `trans` may not be submitted, when it is already committed to the chain
+fn committed_reason(trans: &Transaction) -> String {
+    format!("transaction {} is already committed", trans.hash())
+}
+
+// The current Unix time in seconds, the unit of block timestamps
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+// Record why `block` was rejected, for operators debugging a sync which does not progress
+fn log_rejected_block(block: &Block, reason: &dyn fmt::Display) {
+    tracing::warn!(
+        hash = %block.header.hash(),
+        ordinal = %block.header.ordinal,
+        committer = ?block.header.committer,
+        reason = %reason,
+        "Blockchain: rejected block"
+    );
+}
+
+// Check `block` is well-formed and can be appended right after `parent`
+fn check_successor(parent: &Header, block: &Block) -> Result<(), BlockchainError> {
+    block.verify()?;
+    if let Some((submitter, nonce)) = block.find_duplicate_nonce() {
+        return Err(BlockchainError::DuplicateNonce {
+            ordinal: block.header.ordinal,
+            submitter,
+            nonce,
+        });
+    }
+    if block.header.ordinal != parent.ordinal + 1 {
+        return Err(BlockchainError::UnexpectedOrdinal {
+            expected: parent.ordinal + 1,
+            actual: block.header.ordinal,
+        });
+    }
+    if block.header.parent_hash != parent.hash() {
+        return Err(BlockchainError::ParentHashMismatch(block.header.ordinal));
+    }
+    Ok(())
+}
+
+// Check neither `block` nor its transactions were signed with a key once it was revoked
+fn check_revocations(revocations: &Revocations, block: &Block) -> Result<(), BlockchainError> {
+    let committer = block.header.committer;
+    if let Some(revoked_at) = revoked_at(revocations, &committer, block.header.timestamp) {
+        return Err(BlockchainError::RevokedCommitter {
+            ordinal: block.header.ordinal,
+            committer,
+            revoked_at,
+        });
+    }
+    for trans in block.transactions.iter() {
+        trans.verify_not_revoked(revocations)?;
+    }
+    Ok(())
+}
+
+// Check the transactions of `block` were built for the network scoped by `network_salt`
+fn check_network(network_salt: &[u8], block: &Block) -> Result<(), BlockchainError> {
+    for trans in block.transactions.iter() {
+        trans.verify_network(network_salt)?;
+    }
+    Ok(())
+}
+
+// Check `authorities`, the authority set a block is committed under, holds at least
+// `min_authorities` authorities
+fn check_authority_count(
+    min_authorities: usize,
+    authorities: &HashSet<Address>,
+) -> Result<(), BlockchainError> {
+    if authorities.len() < min_authorities {
+        return Err(BlockchainError::InsufficientAuthorities {
+            count: authorities.len(),
+            min: min_authorities,
+        });
+    }
+    Ok(())
+}
+
+// Check `block` is timestamped after `median_time_past`, the median timestamp of the
+// blocks preceding it, nor more than `timestamp_skew` seconds ahead of the local clock
+fn check_timestamp(
+    median_time_past: Option<u64>,
+    timestamp_skew: u64,
+    block: &Block,
+) -> Result<(), BlockchainError> {
+    let timestamp = block.header.timestamp;
+    if let Some(median) = median_time_past.filter(|median| timestamp <= *median) {
+        return Err(BlockchainError::TimestampBeforeMedian {
+            ordinal: block.header.ordinal,
+            timestamp,
+            median,
+        });
+    }
+    if timestamp > unix_timestamp().saturating_add(timestamp_skew) {
+        return Err(BlockchainError::TimestampInFuture {
+            ordinal: block.header.ordinal,
+            timestamp,
+            skew: timestamp_skew,
+        });
+    }
+    Ok(())
+}
+
+// Check `block` weighs no more than `max_block_weight`, when there is a maximum
+fn check_weight(max_block_weight: Option<u64>, block: &Block) -> Result<(), BlockchainError> {
+    match max_block_weight {
+        Some(max) if block.weight() > max => Err(BlockchainError::OverweightBlock {
+            ordinal: block.header.ordinal,
+            weight: block.weight(),
+            max,
+        }),
+        _ => Ok(()),
+    }
+}
+
+// The hashes of the blocks stored under `dir` making up the chain of the highest total
+// difficulty from a genesis block, ignoring the blocks which do not link to a stored parent
+fn find_stored_chain(dir: &Path) -> Result<HashSet<HashDigest>, BlockchainError> {
+    // the ordinal, parent hash and total difficulty of the blocks linking back to a genesis
+    // block, the files being read in ordinal order
+    let mut linked: HashMap<HashDigest, (Ordinal, HashDigest, u128)> = HashMap::new();
+    let mut tip: Option<(u128, HashDigest)> = None;
+    for block in Blockchain::iter_blocks_from_disk(dir) {
+        let header = block?.header;
+        let parent_difficulty = match linked.get(&header.parent_hash) {
+            _ if header.ordinal == 0 => Some(0),
+            Some((ordinal, _, difficulty)) if *ordinal + 1 == header.ordinal => Some(*difficulty),
+            _ => None,
+        };
+        if let Some(parent_difficulty) = parent_difficulty {
+            let difficulty = parent_difficulty.saturating_add(header.difficulty());
+            let hash = header.hash();
+            linked.insert(hash, (header.ordinal, header.parent_hash, difficulty));
+            if tip.map_or(true, |(best, _)| difficulty > best) {
+                tip = Some((difficulty, hash));
+            }
+        }
+    }
+
+    let mut chain = HashSet::new();
+    let mut next = tip.map(|(_, hash)| hash);
+    while let Some(hash) = next {
+        next = match linked.get(&hash) {
+            Some((ordinal, parent_hash, _)) if *ordinal > 0 => Some(*parent_hash),
+            _ => None,
+        };
+        chain.insert(hash);
+    }
+    Ok(chain)
+}
+
+// Check `genesis` can start a chain
+fn check_genesis(genesis: &Block) -> Result<(), BlockchainError> {
+    if genesis.header.ordinal != 0 {
+        return Err(BlockchainError::InvalidGenesis(genesis.header.ordinal));
+    }
+    if !genesis.is_valid_genesis() {
+        return Err(BlockchainError::MalformedGenesis(genesis.header.hash()));
+    }
+    Ok(())
+}
+
+// Check `block` is committed by the authority whose turn it is or, once `committer_timeout`
+// elapsed since `parent` according to their timestamps, by a fallback authority
+fn check_committer(
+    authorities: &HashSet<Address>,
+    parent: &Header,
+    block: &Block,
+    committer_timeout: Option<u64>,
+) -> Result<(), BlockchainError> {
+    let ordinal = block.header.ordinal;
+    let elapsed = block.header.timestamp.saturating_sub(parent.timestamp);
+    if !permitted_committers(authorities, ordinal, elapsed, committer_timeout)
+        .contains(&block.header.committer)
+    {
+        return Err(BlockchainError::UnexpectedCommitter {
+            ordinal,
+            committer: block.header.committer,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::args::parser::{
+        BlockchainNodeArgs, DEFAULT_BLOCK_KEYPAIR_FILENAME, DEFAULT_BLOCK_STORAGE_DIR,
+    };
+    use crate::crypto::merkle::MerkleStep;
+    use crate::structures::genesis::read_authorities;
+    use crate::structures::transaction::SIGNATURE_WEIGHT;
+    use futures::FutureExt;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_build_blockchain() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blockchain = Blockchain::new(&keypair);
+
+        let mut transactions = vec![];
+        let data = "Hello First Transaction";
+        let transaction = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            data.as_bytes().to_vec(),
+            &keypair,
+        )
+        .unwrap();
+        transactions.push(transaction);
+        assert_eq!(1, blockchain.chain.len());
+        blockchain
+            .commit_block(
+                Block::new(
+                    blockchain.chain.blocks()[0].header.hash(),
+                    blockchain.chain.blocks()[0].header.ordinal + 1,
+                    transactions,
+                    &keypair,
+                )
+                .unwrap(),
+            )
+            .await;
+        assert_eq!(Ok(()), blockchain.chain.blocks().last().unwrap().verify());
+        assert_eq!(2, blockchain.chain.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_blockchain_builder() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let storage_dir = std::env::temp_dir().join(format!(
+            "pyrsia_blockchain_builder_{}",
+            rand::random::<u64>()
+        ));
+        let genesis = Block::new(HashDigest::zero(), 0, vec![], &keypair).unwrap();
+        assert!(genesis.is_valid_genesis());
+
+        let blockchain = BlockchainBuilder::new(&keypair)
+            .storage_dir(&storage_dir)
+            .consensus_mode(ConsensusMode::SingleNode)
+            .genesis(genesis.clone())
+            .max_transactions_per_block(10)
+            .max_pending(20)
+            .timestamp_skew(5)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        assert!(storage_dir.is_dir());
+        assert!(storage::build_path_for_block(&storage_dir, &genesis).is_file());
+        assert_eq!(Some(&genesis), blockchain.get_block_by_ordinal(0));
+        assert_eq!(
+            &BlockchainConfig {
+                storage_dir: Some(storage_dir.clone()),
+                consensus_mode: ConsensusMode::SingleNode,
+                max_transactions_per_block: 10,
+                max_pending: 20,
+                max_payload_bytes: DEFAULT_MAX_PAYLOAD_BYTES,
+                timestamp_skew: 5,
+                block_event_capacity: DEFAULT_BLOCK_EVENT_CAPACITY,
+                async_writes: false,
+                committer_timeout: None,
+                network_id: None,
+                max_block_weight: None,
+                min_authorities: DEFAULT_MIN_AUTHORITIES,
+            },
+            blockchain.config()
+        );
+        std::fs::remove_dir_all(storage_dir).map_err(|e| e.to_string())?;
+
+        assert_eq!(
+            &BlockchainConfig::default(),
+            Blockchain::new(&keypair).config()
+        );
+        // the blocks are committed by the local node only, the Aleph BFT mode is not supported
+        assert!(matches!(
+            BlockchainBuilder::new(&keypair)
+                .consensus_mode(ConsensusMode::AlephBft)
+                .build(),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_initial_authorities() -> Result<(), String> {
+        let key_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypairs: Vec<identity::ed25519::Keypair> = (0..3)
+            .map(|_| identity::ed25519::Keypair::generate())
+            .collect();
+        let initial_authorities = keypairs
+            .iter()
+            .enumerate()
+            .map(|(index, keypair)| {
+                let path = key_dir.path().join(format!("authority_{}.pub", index));
+                let public_key = identity::PublicKey::Ed25519(keypair.public());
+                fs::write(&path, public_key.to_protobuf_encoding()).map(|_| path)
+            })
+            .collect::<Result<Vec<PathBuf>, _>>()
+            .map_err(|e| e.to_string())?;
+        let args = BlockchainNodeArgs {
+            key_filename: DEFAULT_BLOCK_KEYPAIR_FILENAME.to_string(),
+            peer_index: 0,
+            initial_authorities,
+            storage_dir: DEFAULT_BLOCK_STORAGE_DIR.into(),
+            command: None,
+        };
+        let addresses = read_authorities(&args.initial_authorities).map_err(|e| e.to_string())?;
+
+        let blockchain = BlockchainBuilder::new(&keypairs[0])
+            .block_store(InMemoryBlockStore::new())
+            .initial_authorities(addresses.clone())
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let genesis = blockchain.get_block_by_ordinal(0).unwrap();
+        assert!(genesis.is_valid_genesis());
+        assert_eq!(3, genesis.transactions.len());
+        assert_eq!(
+            &addresses.into_iter().collect::<HashSet<Address>>(),
+            blockchain.authorities()
+        );
+
+        assert!(matches!(
+            BlockchainBuilder::new(&keypairs[0])
+                .initial_authorities(vec![])
+                .build(),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            BlockchainBuilder::new(&keypairs[0])
+                .genesis(genesis.clone())
+                .initial_authorities(vec![Address::from(identity::PublicKey::Ed25519(
+                    keypairs[1].public()
+                ))])
+                .build(),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_blockchain_builder_rejects_invalid_options() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let not_genesis = Block::new(HashDigest::new(b""), 3, vec![], &keypair).unwrap();
+
+        assert_eq!(
+            Some(BlockchainError::InvalidGenesis(3)),
+            BlockchainBuilder::new(&keypair)
+                .genesis(not_genesis)
+                .build()
+                .err()
+        );
+        let not_zero_parent = Block::new(HashDigest::new(b""), 0, vec![], &keypair).unwrap();
+        assert_eq!(
+            Some(BlockchainError::MalformedGenesis(not_zero_parent.id())),
+            BlockchainBuilder::new(&keypair)
+                .genesis(not_zero_parent)
+                .build()
+                .err()
+        );
+        assert!(matches!(
+            BlockchainBuilder::new(&keypair)
+                .max_transactions_per_block(0)
+                .build(),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            BlockchainBuilder::new(&keypair).max_pending(0).build(),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_add_trans_listener() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut chain = Blockchain::new(&keypair);
+
+        let transaction = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            "some transaction".as_bytes().to_vec(),
+            &keypair,
+        )
+        .unwrap();
+        let called = Arc::new(AtomicBool::new(false));
+        chain
+            .submit_transaction(transaction.clone(), {
+                let called = called.clone();
+                let transaction = transaction.clone();
+                move |t: Transaction, outcome: TransactionOutcome| {
+                    assert_eq!(transaction, t);
+                    assert_eq!(TransactionOutcome::Settled, outcome);
+                    called.store(true, Ordering::SeqCst)
+                }
+            })
+            .map_err(|e| e.to_string())?
+            .notify_transaction_settled(transaction);
+        assert!(called.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[test]
+    fn test_mempool_digest() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let transactions = ["first", "second", "third"]
+            .iter()
+            .map(|payload| {
+                Transaction::new(
+                    TransactionType::Create,
+                    local_id,
+                    payload.as_bytes().to_vec(),
+                    &keypair,
+                )
+                .map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<Transaction>, String>>()?;
+
+        let mut chain = Blockchain::new(&keypair);
+        let mut other_chain = Blockchain::new(&keypair);
+        assert_eq!(chain.mempool_digest(), other_chain.mempool_digest());
+        for trans in transactions.iter() {
+            chain
+                .submit_transaction(trans.clone(), |_, _| {})
+                .map_err(|e| e.to_string())?;
+        }
+        for trans in transactions.iter().rev().skip(1) {
+            other_chain
+                .submit_transaction(trans.clone(), |_, _| {})
+                .map_err(|e| e.to_string())?;
+        }
+        assert_ne!(chain.mempool_digest(), other_chain.mempool_digest());
+
+        other_chain
+            .submit_transaction(transactions[0].clone(), |_, _| {})
+            .map_err(|e| e.to_string())?;
+        assert_eq!(chain.mempool_digest(), other_chain.mempool_digest());
+        Ok(())
+    }
+
+    #[test]
+    fn test_submit_transactions() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut chain = Blockchain::new(&keypair);
+
+        let payloads = vec!["first", "second", "third"];
+        let called = Arc::new(AtomicUsize::new(0));
+        let transactions = chain
+            .submit_transactions(payloads.clone(), &keypair, {
+                let called = called.clone();
+                move |_: Transaction, _: TransactionOutcome| {
+                    called.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .map_err(|e| e.to_string())?;
+
+        assert_eq!(
+            payloads,
+            transactions
+                .iter()
+                .map(|t| serde_json::from_slice(&t.payload()).unwrap())
+                .collect::<Vec<&str>>()
+        );
+        assert!(transactions
+            .iter()
+            .all(|t| t.type_id() == TransactionType::Create));
+        assert_eq!(3, chain.trans_observers.len());
+
+        for trans in transactions {
+            chain.notify_transaction_settled(trans);
+        }
+        assert_eq!(3, called.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[test]
+    fn test_submit_transaction_idempotent() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut chain = Blockchain::new(&keypair);
+
+        let first = chain
+            .submit_transaction_idempotent("order-42", "payload", &keypair, |_, _| {})
+            .map_err(|e| e.to_string())?;
+        let second = chain
+            .submit_transaction_idempotent("order-42", "payload", &keypair, |_, _| {})
+            .map_err(|e| e.to_string())?;
+
+        assert_eq!(first, second);
+        assert_eq!(1, chain.pending_count());
+        assert_eq!(1, chain.trans_observers.len());
+
+        let other = chain
+            .submit_transaction_idempotent("order-43", "payload", &keypair, |_, _| {})
+            .map_err(|e| e.to_string())?;
+        assert_ne!(first.hash(), other.hash());
+        assert_eq!(2, chain.pending_count());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_next_block_admission() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blockchain = BlockchainBuilder::new(&keypair)
+            .max_transactions_per_block(2)
+            .max_payload_bytes(16)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let transaction = |payload: &[u8], priority: u64| {
+            Transaction::new_with_priority(
+                TransactionType::Create,
+                local_id,
+                payload.to_vec(),
+                priority,
+                &keypair,
+            )
+            .map_err(|e| e.to_string())
+        };
+
+        let low = transaction(b"low", 1)?;
+        assert_eq!(
+            Admission::WillInclude,
+            blockchain.next_block_admission(&low)
+        );
+
+        for (payload, priority) in [(&b"high"[..], 5), (b"urgent", 9)] {
+            blockchain
+                .submit_transaction(transaction(payload, priority)?, |_, _| {})
+                .map_err(|e| e.to_string())?;
+        }
+        assert_eq!(
+            Admission::Queued { behind: 2 },
+            blockchain.next_block_admission(&low)
+        );
+        let higher = transaction(b"higher", 6)?;
+        assert_eq!(
+            Admission::WillInclude,
+            blockchain.next_block_admission(&higher)
+        );
+        let equal = transaction(b"equal", 5)?;
+        assert_eq!(
+            Admission::Queued { behind: 2 },
+            blockchain.next_block_admission(&equal)
+        );
+
+        let too_large = transaction(&[0; 17][..], 9)?;
+        assert_eq!(
+            Admission::Rejected {
+                reason: BlockchainError::PayloadTooLarge { size: 17, max: 16 }.to_string()
+            },
+            blockchain.next_block_admission(&too_large)
+        );
+
+        blockchain
+            .produce_block(&keypair)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(
+            Admission::WillInclude,
+            blockchain.next_block_admission(&low)
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_produce_block_by_priority() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blockchain = BlockchainBuilder::new(&keypair)
+            .max_transactions_per_block(4)
+            .build()
+            .map_err(|e| e.to_string())?;
+        assert_eq!(Ok(None), blockchain.produce_block(&keypair).await);
+
+        let transactions = [1, 5, 0, 5, 3]
+            .into_iter()
+            .enumerate()
+            .map(|(i, priority)| {
+                Transaction::new_with_priority(
+                    TransactionType::Create,
+                    local_id,
+                    format!("transaction {}", i).into_bytes(),
+                    priority,
+                    &keypair,
+                )
+            })
+            .collect::<Result<Vec<Transaction>, _>>()
+            .map_err(|e| e.to_string())?;
+        let settled = Arc::new(AtomicUsize::new(0));
+        for trans in transactions.iter() {
+            blockchain
+                .submit_transaction(trans.clone(), {
+                    let settled = settled.clone();
+                    move |_: Transaction, outcome: TransactionOutcome| {
+                        assert_eq!(TransactionOutcome::Settled, outcome);
+                        settled.fetch_add(1, Ordering::SeqCst);
+                    }
+                })
+                .map_err(|e| e.to_string())?;
+        }
+
+        let block = blockchain
+            .produce_block(&keypair)
+            .await
+            .map_err(|e| e.to_string())?
+            .unwrap();
+        assert_eq!(
+            vec![
+                &transactions[1],
+                &transactions[3],
+                &transactions[4],
+                &transactions[0]
+            ],
+            block.transactions.iter().collect::<Vec<&Transaction>>()
+        );
+        assert_eq!(Some(&block), blockchain.get_block_by_ordinal(1));
+        assert_eq!(4, settled.load(Ordering::SeqCst));
+        assert_eq!(1, blockchain.pending_count());
+
+        let block = blockchain
+            .produce_block(&keypair)
+            .await
+            .map_err(|e| e.to_string())?
+            .unwrap();
+        assert_eq!(vec![transactions[2].clone()], block.transactions);
+        assert_eq!(0, blockchain.pending_count());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_rejected_transactions() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let outsider = identity::ed25519::Keypair::generate();
+        let outsider_id = Address::from(identity::PublicKey::Ed25519(outsider.public()));
+        let mut blockchain = Blockchain::new(&keypair);
+
+        let outcomes = Arc::new(std::sync::Mutex::new(vec![]));
+        let on_done = {
+            let outcomes = outcomes.clone();
+            move |trans: Transaction, outcome: TransactionOutcome| {
+                outcomes.lock().unwrap().push((trans, outcome))
+            }
+        };
+        let transaction = |payload: &[u8], nonce: u128| {
+            Transaction::new_with(
+                TransactionType::Create,
+                local_id,
+                payload.to_vec(),
+                1_660_000_000,
+                nonce,
+                &keypair,
+            )
+            .map_err(|e| e.to_string())
+        };
+        let first = transaction(b"first", 1)?;
+        let same_nonce = transaction(b"same nonce", 1)?;
+        let unauthorized = Transaction::new(
+            TransactionType::AddAuthority,
+            outsider_id,
+            outsider_id.to_bytes(),
+            &outsider,
+        )
+        .map_err(|e| e.to_string())?;
+        for trans in [&first, &same_nonce, &unauthorized] {
+            blockchain
+                .submit_transaction(trans.clone(), on_done.clone())
+                .map_err(|e| e.to_string())?;
+        }
+        assert_eq!(2, blockchain.pending_count());
+
+        let block = blockchain
+            .produce_block(&keypair)
+            .await
+            .map_err(|e| e.to_string())?
+            .unwrap();
+        assert_eq!(vec![first.clone()], block.transactions);
+        assert_eq!(0, blockchain.pending_count());
+
+        // a committed transaction can not be replayed, neither submitted nor in a block
+        blockchain
+            .submit_transaction(first.clone(), on_done.clone())
+            .map_err(|e| e.to_string())?;
+        assert_eq!(0, blockchain.pending_count());
+        let replay = Block::new_with(
+            block.header.hash(),
+            2,
+            vec![first.clone()],
+            &keypair,
+            blockchain.next_block_timestamp(),
+            0,
+        )
+        .map_err(|e| e.to_string())?;
+        assert_eq!(
+            Err(BlockchainError::CommittedTransaction {
+                ordinal: 2,
+                transaction: first.hash()
+            }),
+            blockchain.apply_blocks(vec![replay]).await
+        );
+        assert_eq!(1, blockchain.height());
+
+        let outcomes = outcomes.lock().unwrap();
+        assert_eq!(4, outcomes.len());
+        assert!(matches!(
+            &outcomes[0],
+            (trans, TransactionOutcome::Rejected { .. }) if *trans == same_nonce
+        ));
+        assert!(matches!(
+            &outcomes[1],
+            (trans, TransactionOutcome::Rejected { .. }) if *trans == unauthorized
+        ));
+        assert_eq!((first.clone(), TransactionOutcome::Settled), outcomes[2]);
+        assert!(matches!(
+            &outcomes[3],
+            (trans, TransactionOutcome::Rejected { .. }) if *trans == first
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fold_transactions() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blockchain = Blockchain::new(&keypair);
+        let peers: Vec<Address> = (0..2)
+            .map(|_| Address::from(identity::Keypair::generate_ed25519().public()))
+            .collect();
+        let submit = |blockchain: &mut Blockchain, type_id: TransactionType, payload: Vec<u8>| {
+            let trans = Transaction::new(type_id, local_id, payload, &keypair)
+                .map_err(|e| e.to_string())?;
+            blockchain
+                .submit_transaction(trans, |_, _: TransactionOutcome| {})
+                .map_err(|e| e.to_string())?;
+            Ok::<(), String>(())
+        };
+        submit(&mut blockchain, TransactionType::Create, b"first".to_vec())?;
+        blockchain
+            .produce_block(&keypair)
+            .await
+            .map_err(|e| e.to_string())?;
+        // authorities are only added in the last block, so the local node stays in turn
+        for peer in peers.iter() {
+            submit(
+                &mut blockchain,
+                TransactionType::AddAuthority,
+                peer.to_bytes(),
+            )?;
+        }
+        submit(&mut blockchain, TransactionType::Create, b"second".to_vec())?;
+        blockchain
+            .produce_block(&keypair)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let count_of = |type_id: TransactionType| {
+            blockchain.fold_transactions(0, |count, trans| {
+                if trans.type_id() == type_id {
+                    count + 1
+                } else {
+                    count
+                }
+            })
+        };
+        // the genesis block carries a Create transaction too
+        assert_eq!(3, count_of(TransactionType::Create));
+        assert_eq!(2, count_of(TransactionType::AddAuthority));
+        assert_eq!(0, count_of(TransactionType::RemoveAuthority));
+        assert_eq!(
+            vec![b"first".to_vec(), b"second".to_vec()],
+            blockchain.fold_transactions(vec![], |mut payloads, trans| {
+                if trans.type_id() == TransactionType::Create {
+                    payloads.push(trans.payload());
+                }
+                payloads
+            })[1..]
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_transactions_between() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blockchain = BlockchainBuilder::new(&keypair)
+            .genesis(Block::new_with(HashDigest::zero(), 0, vec![], &keypair, 0, 0).unwrap())
+            .build()
+            .map_err(|e| e.to_string())?;
+        blockchain.set_transaction_ttl(10);
+
+        // the transactions of every block are 5 seconds older than the block
+        let mut transactions = vec![];
+        for (ordinal, timestamp) in [(1, 100), (2, 200), (3, 300), (4, 400)] {
+            let block_transactions = (0..2)
+                .map(|nonce| {
+                    Transaction::new_with(
+                        TransactionType::Create,
+                        local_id,
+                        vec![ordinal as u8, nonce as u8],
+                        timestamp - 5 + nonce as u64,
+                        nonce,
+                        &keypair,
+                    )
+                })
+                .collect::<Result<Vec<Transaction>, _>>()
+                .map_err(|e| e.to_string())?;
+            transactions.extend(block_transactions.clone());
+            let parent = blockchain.chain.last_block().unwrap();
+            let block = Block::new_with(
+                parent.header.hash(),
+                ordinal,
+                block_transactions,
+                &keypair,
+                timestamp,
+                ordinal,
+            )
+            .unwrap();
+            blockchain.commit_block(block).await;
+        }
+        // a transaction committed long after its TTL elapsed, e.g. by a peer
+        let late = Transaction::new_with(
+            TransactionType::Create,
+            local_id,
+            b"late".to_vec(),
+            250,
+            2,
+            &keypair,
+        )
+        .map_err(|e| e.to_string())?;
+        let parent = blockchain.chain.last_block().unwrap();
+        let block = Block::new_with(
+            parent.header.hash(),
+            5,
+            vec![late.clone()],
+            &keypair,
+            1_000,
+            5,
+        )
+        .unwrap();
+        blockchain.commit_block(block).await;
+
+        assert_eq!(
+            vec![&transactions[2], &transactions[3], &transactions[4], &late],
+            blockchain.transactions_between(195, 295)
+        );
+        assert_eq!(
+            vec![&transactions[3]],
+            blockchain.transactions_between(196, 196)
+        );
+        assert!(blockchain.transactions_between(500, 600).is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_blocks_with_transaction_type() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blockchain = Blockchain::new(&keypair);
+        let blocks = [
+            vec![(TransactionType::Create, b"first".to_vec())],
+            // the local node adds itself, so it stays the only authority
+            vec![
+                (TransactionType::Create, b"second".to_vec()),
+                (TransactionType::AddAuthority, local_id.to_bytes()),
+            ],
+            vec![(TransactionType::Create, b"third".to_vec())],
+        ];
+        for transactions in blocks {
+            for (type_id, payload) in transactions {
+                let trans = Transaction::new(type_id, local_id, payload, &keypair)
+                    .map_err(|e| e.to_string())?;
+                blockchain
+                    .submit_transaction(trans, |_, _: TransactionOutcome| {})
+                    .map_err(|e| e.to_string())?;
+            }
+            blockchain
+                .produce_block(&keypair)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        let ordinals_of = |type_id: TransactionType| {
+            blockchain
+                .blocks_with_transaction_type(type_id)
+                .map(|block| block.header.ordinal)
+                .collect::<Vec<Ordinal>>()
+        };
+        assert_eq!(vec![0, 1, 2, 3], ordinals_of(TransactionType::Create));
+        assert_eq!(vec![2], ordinals_of(TransactionType::AddAuthority));
+        assert!(ordinals_of(TransactionType::RemoveAuthority).is_empty());
+        assert_eq!(
+            Some(2),
+            blockchain
+                .blocks_with_transaction_type(TransactionType::AddAuthority)
+                .last()
+                .map(|block| block.header.ordinal)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mempool_full() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut blockchain = BlockchainBuilder::new(&keypair)
+            .max_pending(3)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let payloads = |count: usize| (0..count).collect::<Vec<usize>>();
+        let on_done = |_: Transaction, _: TransactionOutcome| {};
+
+        assert_eq!(
+            Err(BlockchainError::MempoolFull(3)),
+            blockchain.submit_transactions(payloads(4), &keypair, on_done)
+        );
+        assert_eq!(0, blockchain.pending_count());
+
+        let transactions = blockchain
+            .submit_transactions(payloads(3), &keypair, on_done)
+            .map_err(|e| e.to_string())?;
+        assert_eq!(3, blockchain.pending_count());
+
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let next = Transaction::new(TransactionType::Create, local_id, vec![3], &keypair)
+            .map_err(|e| e.to_string())?;
+        assert!(matches!(
+            blockchain.submit_transaction(next.clone(), on_done),
+            Err(BlockchainError::MempoolFull(3))
+        ));
+        assert_eq!(3, blockchain.pending_count());
+
+        blockchain.notify_transaction_settled(transactions[0].clone());
+        blockchain
+            .submit_transaction(next, on_done)
+            .map_err(|e| e.to_string())?;
+        assert_eq!(3, blockchain.pending_count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_payload_bytes() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blockchain = BlockchainBuilder::new(&keypair)
+            .max_payload_bytes(16)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let on_done = |_: Transaction, _: TransactionOutcome| {};
+
+        let oversized = Transaction::new(TransactionType::Create, local_id, vec![0; 17], &keypair)
+            .map_err(|e| e.to_string())?;
+        assert_eq!(
+            Some(BlockchainError::PayloadTooLarge { size: 17, max: 16 }),
+            blockchain.submit_transaction(oversized, on_done).err()
+        );
+        assert_eq!(
+            Err(BlockchainError::PayloadTooLarge { size: 17, max: 16 }),
+            // serialized with their quotes, the strings are 16 and 17 bytes long
+            blockchain.submit_transactions(vec!["x".repeat(14), "x".repeat(15)], &keypair, on_done)
+        );
+        assert_eq!(0, blockchain.pending_count());
+
+        let boundary = Transaction::new(TransactionType::Create, local_id, vec![0; 16], &keypair)
+            .map_err(|e| e.to_string())?;
+        blockchain
+            .submit_transaction(boundary, on_done)
+            .map_err(|e| e.to_string())?;
+        assert_eq!(1, blockchain.pending_count());
+
+        assert!(matches!(
+            BlockchainBuilder::new(&keypair)
+                .max_payload_bytes(0)
+                .build(),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_pending() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut chain = Blockchain::new(&keypair);
+        chain.set_transaction_ttl(10);
+
+        let transaction = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            "some old transaction".as_bytes().to_vec(),
+            &keypair,
+        )
+        .unwrap();
+        let outcome = Arc::new(std::sync::Mutex::new(None));
+        chain
+            .submit_transaction(transaction.clone(), {
+                let outcome = outcome.clone();
+                move |_: Transaction, o: TransactionOutcome| *outcome.lock().unwrap() = Some(o)
+            })
+            .map_err(|e| e.to_string())?;
+
+        assert!(chain
+            .expire_pending(transaction.timestamp() + 10)
+            .is_empty());
+        assert_eq!(None, *outcome.lock().unwrap());
+
+        // a transaction pending without an observer, e.g. requeued after a reorg, expires too
+        let unobserved = Transaction::new_with(
+            TransactionType::Create,
+            local_id,
+            "some unobserved transaction".as_bytes().to_vec(),
+            transaction.timestamp(),
+            1,
+            &keypair,
+        )
+        .map_err(|e| e.to_string())?;
+        chain.pending.push(unobserved.clone());
+
+        let expired = chain.expire_pending(transaction.timestamp() + 11);
+        assert_eq!(vec![transaction, unobserved], expired);
+        assert_eq!(Some(TransactionOutcome::Expired), *outcome.lock().unwrap());
+        assert!(chain.trans_observers.is_empty());
+        assert!(chain.pending.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_add_payload_listener() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let block = Block::new(
+            HashDigest::new(b"Hello World!"),
+            1u128,
+            Vec::new(),
+            &keypair,
+        )
+        .unwrap();
+        let mut blockchain = Blockchain::new(&keypair);
+        let called = move |b: &Vec<u8>| println!("data is {:?}", b);
+
+        blockchain
+            .add_payload_listener(called)
+            .commit_block(block)
+            .await;
+
+        assert_eq!(1, blockchain.payload_observers.len());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_add_block_listener() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = Blockchain::new(&ed25519_key);
+
+        let received = Arc::new(std::sync::Mutex::new(vec![]));
+        blockchain.add_block_listener({
+            let received = received.clone();
+            move |block: &Block| received.lock().unwrap().push(block.clone())
+        });
+        blockchain
+            .add_block(b"Hello Block Listener".to_vec(), keypair)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        assert_eq!(
+            vec![blockchain.chain.last_block().unwrap()],
+            *received.lock().unwrap()
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_add_block_listener_until() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = Blockchain::new(&ed25519_key);
+        let found = Arc::new(AtomicUsize::new(0));
+        blockchain.add_block_listener_until({
+            let found = found.clone();
+            move |block: &Block| {
+                if block.fetch_payload().contains(&b"wanted".to_vec()) {
+                    found.fetch_add(1, Ordering::SeqCst);
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            }
+        });
+
+        for payload in ["unwanted", "wanted", "wanted"] {
+            blockchain
+                .add_block(payload.as_bytes().to_vec(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+            if payload == "unwanted" {
+                assert_eq!(1, blockchain.block_observers.len());
+            }
+        }
+
+        assert_eq!(1, found.load(Ordering::SeqCst));
+        assert!(blockchain.block_observers.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_events() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+            .block_event_capacity(2)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let mut events = blockchain.block_events();
+        let dropped = blockchain.block_events();
+        drop(dropped);
+
+        // the receiver lags behind without holding up blocks, up to the capacity
+        for i in 1..=2 {
+            tokio::time::timeout(
+                Duration::from_secs(5),
+                blockchain.add_block(format!("payload {}", i).into_bytes(), keypair.clone()),
+            )
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+        }
+        assert_eq!(1, blockchain.block_event_senders.len());
+
+        let third = blockchain.add_block(b"payload 3".to_vec(), keypair.clone());
+        tokio::pin!(third);
+        assert!(tokio::time::timeout(Duration::from_millis(100), &mut third)
+            .await
+            .is_err());
+        assert_eq!(
+            Some(1),
+            events.recv().await.map(|block| block.header.ordinal)
+        );
+        third.await.map_err(|e| e.to_string())?;
+
+        assert_eq!(
+            Some(2),
+            events.recv().await.map(|block| block.header.ordinal)
+        );
+        assert_eq!(
+            Some(3),
+            events.recv().await.map(|block| block.header.ordinal)
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_subscribe_transaction_type() -> Result<(), String> {
+        let keypairs: Vec<identity::ed25519::Keypair> = (0..3)
+            .map(|_| identity::ed25519::Keypair::generate())
+            .collect();
+        let addresses: Vec<Address> = keypairs
+            .iter()
+            .map(|keypair| Address::from(identity::PublicKey::Ed25519(keypair.public())))
+            .collect();
+        let mut blockchain = Blockchain::in_memory(&keypairs[0]);
+        let mut additions = blockchain.subscribe_transaction_type(TransactionType::AddAuthority);
+
+        let transaction = |type_id: TransactionType, payload: Vec<u8>| {
+            Transaction::new(type_id, addresses[0], payload, &keypairs[0])
+                .map_err(|e| e.to_string())
+        };
+        let first = transaction(TransactionType::AddAuthority, addresses[1].to_bytes())?;
+        let second = transaction(TransactionType::AddAuthority, addresses[2].to_bytes())?;
+        let block = Block::new_with(
+            blockchain.genesis_hash(),
+            1,
+            vec![
+                transaction(TransactionType::Create, b"Hello First".to_vec())?,
+                first.clone(),
+                transaction(TransactionType::Create, b"Hello Second".to_vec())?,
+                second.clone(),
+            ],
+            &keypairs[0],
+            blockchain.next_block_timestamp(),
+            0,
+        )
+        .unwrap();
+        blockchain
+            .append_block(block)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        assert_eq!(Some(first), additions.recv().await);
+        assert_eq!(Some(second), additions.recv().await);
+        assert!(additions.try_recv().is_err());
+
+        // the subscription is cleaned up with the next block once the receiver is dropped
+        drop(additions);
+        assert_eq!(1, blockchain.block_observers.len());
+        let committer = blockchain.expected_committer(2).unwrap();
+        let index = addresses.iter().position(|a| *a == committer).unwrap();
+        let parent_hash = blockchain.chain.last_block().unwrap().header.hash();
+        let timestamp = blockchain.next_block_timestamp();
+        blockchain
+            .append_block(
+                Block::new_with(parent_hash, 2, vec![], &keypairs[index], timestamp, 0).unwrap(),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        assert!(blockchain.block_observers.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_block_by_ordinal() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = Blockchain::new(&ed25519_key);
+        for payload in ["first", "second"] {
+            blockchain
+                .add_block(payload.as_bytes().to_vec(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        // the checks themselves are tested with Chain::verify_integrity
+        assert_eq!(Ok(()), blockchain.validate_chain());
+
+        let genesis = blockchain.get_block_by_ordinal(0).unwrap();
+        assert_eq!(0, genesis.header.ordinal);
+        let tip = blockchain
+            .get_block_by_ordinal(blockchain.height())
+            .unwrap();
+        assert_eq!(2, tip.header.ordinal);
+        assert_eq!(b"second".to_vec(), tip.transactions[0].payload());
+        assert_eq!(None, blockchain.get_block_by_ordinal(3));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_restore_from_snapshot() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blockchain = Blockchain::new(&keypair);
+        let peers: Vec<Address> = (0..3)
+            .map(|_| Address::from(identity::Keypair::generate_ed25519().public()))
+            .collect();
+
+        let next_block = |type_id: TransactionType, peer: &Address, chain: &Chain| {
+            let last_block = chain.last_block().unwrap();
+            Block::new(
+                last_block.header.hash(),
+                last_block.header.ordinal + 1,
+                vec![Transaction::new(type_id, local_id, peer.to_bytes(), &keypair).unwrap()],
+                &keypair,
+            )
+            .unwrap()
+        };
+        for peer in peers.iter() {
+            let block = next_block(TransactionType::AddAuthority, peer, &blockchain.chain);
+            blockchain.commit_block(block).await;
+        }
+        let snapshot = blockchain.state_snapshot();
+        let block = next_block(
+            TransactionType::RemoveAuthority,
+            &peers[0],
+            &blockchain.chain,
+        );
+        blockchain.commit_block(block).await;
+
+        let blocks = blockchain.chain.blocks();
+        let from_genesis = replay_authorities(blocks.iter());
+        assert_eq!(&from_genesis, blockchain.authorities());
+        assert_eq!(3, from_genesis.len());
+
+        let mut restored = Blockchain::new(&keypair);
+        restored.chain = blockchain.chain.clone();
+        restored
+            .restore_from_snapshot(snapshot.clone())
+            .map_err(|e| e.to_string())?;
+        assert_eq!(&from_genesis, restored.authorities());
+
+        let mut unrelated = Blockchain::new(&keypair);
+        assert_eq!(
+            Err(BlockchainError::UnknownSnapshotTip(snapshot.tip_hash)),
+            unrelated.restore_from_snapshot(snapshot.clone())
+        );
+        let mut misplaced = snapshot.clone();
+        misplaced.tip_ordinal += 1;
+        assert_eq!(
+            Err(BlockchainError::UnknownSnapshotTip(snapshot.tip_hash)),
+            restored.restore_from_snapshot(misplaced)
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_min_authorities() -> Result<(), String> {
+        let keypairs: Vec<identity::ed25519::Keypair> = (0..3)
+            .map(|_| identity::ed25519::Keypair::generate())
+            .collect();
+        let addresses: Vec<Address> = keypairs
+            .iter()
+            .map(|keypair| Address::from(identity::PublicKey::Ed25519(keypair.public())))
+            .collect();
+        let mut blockchain = BlockchainBuilder::new(&keypairs[0])
+            .initial_authorities(addresses.clone())
+            .min_authorities(2)
+            .build()
+            .map_err(|e| e.to_string())?;
+        // the next block, committed by the authority whose turn it is and removing another
+        // authority when `remove` is set
+        let next_block = |blockchain: &Blockchain, remove: bool| {
+            let tip = blockchain.chain.last_block().unwrap();
+            let ordinal = tip.header.ordinal + 1;
+            let committer = blockchain.expected_committer(ordinal).unwrap();
+            let keypair = &keypairs[addresses.iter().position(|a| *a == committer).unwrap()];
+            let transactions = blockchain
+                .authorities()
+                .iter()
+                .find(|authority| remove && **authority != committer)
+                .map(|removed| {
+                    Transaction::new(
+                        TransactionType::RemoveAuthority,
+                        committer,
+                        removed.to_bytes(),
+                        keypair,
+                    )
+                    .unwrap()
+                })
+                .into_iter()
+                .collect();
+            Block::new_with(
+                tip.header.hash(),
+                ordinal,
+                transactions,
+                keypair,
+                blockchain.next_block_timestamp(),
+                0,
+            )
+            .unwrap()
+        };
+
+        for remaining in [2, 1] {
+            let block = next_block(&blockchain, true);
+            blockchain
+                .append_block(block)
+                .await
+                .map_err(|e| e.to_string())?;
+            assert_eq!(remaining, blockchain.authorities().len());
+        }
+
+        let block = next_block(&blockchain, false);
+        assert_eq!(
+            Err(BlockchainError::InsufficientAuthorities { count: 1, min: 2 }),
+            blockchain.append_block(block).await
+        );
+        assert_eq!(2, blockchain.height());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_committer_timeout() -> Result<(), String> {
+        let keypairs: Vec<identity::ed25519::Keypair> = (0..3)
+            .map(|_| identity::ed25519::Keypair::generate())
+            .collect();
+        let addresses: Vec<Address> = keypairs
+            .iter()
+            .map(|keypair| Address::from(identity::PublicKey::Ed25519(keypair.public())))
+            .collect();
+        let mut blockchain = BlockchainBuilder::new(&keypairs[0])
+            .initial_authorities(addresses.clone())
+            .committer_timeout(30)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let genesis = blockchain.get_block_by_ordinal(0).unwrap().clone();
+        let keypair_of = |ordinal: Ordinal| {
+            let committer = blockchain.expected_committer(ordinal).unwrap();
+            let index = addresses.iter().position(|a| *a == committer).unwrap();
+            &keypairs[index]
+        };
+        // the committer of block 1 is offline, the next two authorities stand in for it
+        let block_by = |keypair, elapsed| {
+            Block::new_with(
+                genesis.header.hash(),
+                1,
+                vec![],
+                keypair,
+                genesis.header.timestamp + elapsed,
+                42,
+            )
+            .unwrap()
+        };
+        let fallback = keypair_of(2);
+        let second_fallback = keypair_of(3);
+
+        let early = block_by(fallback, 29);
+        let late_second = block_by(second_fallback, 30);
+        let late = block_by(fallback, 30);
+        for rejected in [early, late_second] {
+            assert_eq!(
+                Err(BlockchainError::UnexpectedCommitter {
+                    ordinal: 1,
+                    committer: rejected.header.committer
+                }),
+                blockchain.append_block(rejected).await
+            );
+        }
+        blockchain
+            .append_block(late.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(Some(&late), blockchain.get_block_by_ordinal(1));
+
+        assert!(matches!(
+            BlockchainBuilder::new(&keypairs[0])
+                .committer_timeout(0)
+                .build(),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_expected_committer() -> Result<(), String> {
+        let keypairs: Vec<identity::ed25519::Keypair> = (0..3)
+            .map(|_| identity::ed25519::Keypair::generate())
+            .collect();
+        let addresses: Vec<Address> = keypairs
+            .iter()
+            .map(|keypair| Address::from(identity::PublicKey::Ed25519(keypair.public())))
+            .collect();
+        let mut blockchain = Blockchain::new(&keypairs[0]);
+        assert_eq!(Some(addresses[0]), blockchain.expected_committer(1));
+
+        let additions = addresses[1..]
+            .iter()
+            .map(|peer| {
+                Transaction::new(
+                    TransactionType::AddAuthority,
+                    addresses[0],
+                    peer.to_bytes(),
+                    &keypairs[0],
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        let block = Block::new_with(
+            blockchain.genesis_hash(),
+            1,
+            additions,
+            &keypairs[0],
+            blockchain.next_block_timestamp(),
+            0,
+        )
+        .unwrap();
+        blockchain
+            .apply_blocks(vec![block])
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(3, blockchain.authorities().len());
+
+        let turns: Vec<Address> = (2..8)
+            .map(|ordinal| blockchain.expected_committer(ordinal).unwrap())
+            .collect();
+        assert_eq!(turns[..3], turns[3..]);
+        assert_eq!(3, turns[..3].iter().collect::<HashSet<&Address>>().len());
+
+        let next_block = |blockchain: &Blockchain, committer: &Address| {
+            let parent = blockchain.chain.last_block().unwrap();
+            let index = addresses.iter().position(|a| a == committer).unwrap();
+            Block::new_with(
+                parent.header.hash(),
+                parent.header.ordinal + 1,
+                vec![],
+                &keypairs[index],
+                blockchain.next_block_timestamp(),
+                0,
+            )
+            .unwrap()
+        };
+        let out_of_turn = turns[1];
+        assert_eq!(
+            Err(BlockchainError::UnexpectedCommitter {
+                ordinal: 2,
+                committer: out_of_turn
+            }),
+            blockchain
+                .apply_blocks(vec![next_block(&blockchain, &out_of_turn)])
+                .await
+        );
+        assert_eq!(1, blockchain.height());
+
+        for committer in turns[..3].iter() {
+            let block = next_block(&blockchain, committer);
+            blockchain
+                .apply_blocks(vec![block])
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        assert_eq!(4, blockchain.height());
+
+        let index = addresses.iter().position(|a| *a == out_of_turn).unwrap();
+        assert!(blockchain
+            .add_block(
+                b"Hello Out Of Turn".to_vec(),
+                Ed25519(keypairs[index].clone())
+            )
+            .await
+            .is_err());
+        assert_eq!(4, blockchain.height());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_recent_committers() -> Result<(), String> {
+        let keypairs: Vec<identity::ed25519::Keypair> = (0..3)
+            .map(|_| identity::ed25519::Keypair::generate())
+            .collect();
+        let addresses: Vec<Address> = keypairs
+            .iter()
+            .map(|keypair| Address::from(identity::PublicKey::Ed25519(keypair.public())))
+            .collect();
+        let mut blockchain = Blockchain::new(&keypairs[0]);
+        let additions = addresses[1..]
+            .iter()
+            .map(|peer| {
+                Transaction::new(
+                    TransactionType::AddAuthority,
+                    addresses[0],
+                    peer.to_bytes(),
+                    &keypairs[0],
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        let block = Block::new_with(
+            blockchain.genesis_hash(),
+            1,
+            additions,
+            &keypairs[0],
+            blockchain.next_block_timestamp(),
+            0,
+        )
+        .unwrap();
+        blockchain
+            .apply_blocks(vec![block])
+            .await
+            .map_err(|e| e.to_string())?;
+
+        for ordinal in 2..8 {
+            let committer = blockchain.expected_committer(ordinal).unwrap();
+            let index = addresses.iter().position(|a| *a == committer).unwrap();
+            let parent = blockchain.chain.last_block().unwrap();
+            let block = Block::new_with(
+                parent.header.hash(),
+                ordinal,
+                vec![],
+                &keypairs[index],
+                blockchain.next_block_timestamp(),
+                0,
+            )
+            .unwrap();
+            blockchain
+                .apply_blocks(vec![block])
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        // blocks 2 to 7, two full rotations of the three authorities
+        let counts = blockchain.recent_committers(6);
+        assert_eq!(3, counts.len());
+        assert!(counts.values().all(|count| *count == 2));
+
+        // blocks 6 and 7, the authority of the next turn went silent
+        let counts = blockchain.recent_committers(2);
+        let silent = blockchain.expected_committer(8).unwrap();
+        assert_eq!(Some(&0), counts.get(&silent));
+        assert_eq!(
+            Some(&1),
+            counts.get(&blockchain.expected_committer(7).unwrap())
+        );
+        assert_eq!(
+            Some(&1),
+            counts.get(&blockchain.expected_committer(6).unwrap())
+        );
+
+        // the whole chain, the genesis block and the first block included
+        let counts = blockchain.recent_committers(100);
+        assert_eq!(8, counts.values().sum::<usize>());
+        assert_eq!(Some(&4), counts.get(&addresses[0]));
+        assert!(blockchain
+            .recent_committers(0)
+            .values()
+            .all(|count| *count == 0));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_intervals() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let genesis = Block::new_with(HashDigest::zero(), 0, vec![], &keypair, 1_000, 0).unwrap();
+        let mut blockchain = BlockchainBuilder::new(&keypair)
+            .block_store(InMemoryBlockStore::new())
+            .genesis(genesis)
+            .build()
+            .map_err(|e| e.to_string())?;
+        assert!(blockchain.block_intervals().is_empty());
+        assert_eq!(None, blockchain.mean_block_interval());
+        assert_eq!(None, blockchain.max_block_interval());
+
+        for (ordinal, timestamp) in [(1, 1_010), (2, 1_015), (3, 1_045), (4, 1_045)] {
+            let parent_hash = blockchain.chain.last_block().unwrap().header.hash();
+            let block =
+                Block::new_with(parent_hash, ordinal, vec![], &keypair, timestamp, 0).unwrap();
+            blockchain
+                .append_block(block)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        assert_eq!(vec![10, 5, 30, 0], blockchain.block_intervals());
+        assert_eq!(Some(11.25), blockchain.mean_block_interval());
+        assert_eq!(Some(30), blockchain.max_block_interval());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_median_time_past() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let genesis = Block::new_with(HashDigest::zero(), 0, vec![], &keypair, 1_000, 0).unwrap();
+        let mut blockchain = BlockchainBuilder::new(&keypair)
+            .genesis(genesis)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let block_at = |blockchain: &Blockchain, timestamp| {
+            let tip = blockchain.chain.last_block().unwrap();
+            Block::new_with(
+                tip.header.hash(),
+                tip.header.ordinal + 1,
+                vec![],
+                &keypair,
+                timestamp,
+                0,
+            )
+            .unwrap()
+        };
+
+        for timestamp in [1_010, 1_020, 1_030, 1_040] {
+            let block = block_at(&blockchain, timestamp);
+            blockchain
+                .append_block(block)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        assert_eq!(
+            Some(1_020),
+            blockchain.chain.median_time_past(MEDIAN_TIME_PAST_BLOCKS)
+        );
+
+        for timestamp in [1_015, 1_020] {
+            let not_after_median = block_at(&blockchain, timestamp);
+            assert_eq!(
+                Err(BlockchainError::TimestampBeforeMedian {
+                    ordinal: 5,
+                    timestamp,
+                    median: 1_020
+                }),
+                blockchain.append_block(not_after_median).await
+            );
+        }
+        let too_late = unix_timestamp() + DEFAULT_TIMESTAMP_SKEW + 60;
+        assert_eq!(
+            Err(BlockchainError::TimestampInFuture {
+                ordinal: 5,
+                timestamp: too_late,
+                skew: DEFAULT_TIMESTAMP_SKEW
+            }),
+            blockchain
+                .append_block(block_at(&blockchain, too_late))
+                .await
+        );
+
+        // a block may precede its parent, as long as it follows the median
+        let block = block_at(&blockchain, 1_021);
+        blockchain
+            .append_block(block)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(5, blockchain.height());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_snapshot() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = Blockchain::in_memory(&ed25519_key);
+        for i in 0..3 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        let snapshot = blockchain.snapshot();
+        let tip = blockchain.chain.last_block().unwrap();
+
+        for i in 3..6 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        blockchain.prune(2);
+
+        assert_eq!(6, blockchain.height());
+        let reader = std::thread::spawn(move || {
+            (
+                snapshot.height(),
+                snapshot.len(),
+                snapshot.last_block(),
+                snapshot.get_block_by_ordinal(1).is_some(),
+            )
+        });
+        let (height, len, last_block, has_block_1) = reader.join().unwrap();
+        assert_eq!(3, height);
+        assert_eq!(4, len);
+        assert_eq!(Some(tip), last_block);
+        assert!(has_block_1);
+        assert!(blockchain.get_block_by_ordinal(1).is_none());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_append_block() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let peer_key = identity::ed25519::Keypair::generate();
+        let peer_id = Address::from(identity::PublicKey::Ed25519(peer_key.public()));
+        let local_id = Address::from(identity::PublicKey::Ed25519(ed25519_key.public()));
+        let mut blockchain = Blockchain::new(&ed25519_key);
+        blockchain
+            .submit_transaction(
+                Transaction::new(
+                    TransactionType::AddAuthority,
+                    local_id,
+                    peer_id.to_bytes(),
+                    &ed25519_key,
+                )
+                .map_err(|e| e.to_string())?,
+                |_, _: TransactionOutcome| {},
+            )
+            .map_err(|e| e.to_string())?;
+        blockchain
+            .produce_block(&ed25519_key)
+            .await
+            .map_err(|e| e.to_string())?;
+        while blockchain.expected_committer(blockchain.height() + 1) != Some(peer_id) {
+            blockchain
+                .add_block(b"Hello Local Block".to_vec(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        let tip = blockchain.chain.last_block().unwrap();
+        let block = Block::new_with(
+            tip.header.hash(),
+            tip.header.ordinal + 1,
+            vec![Transaction::new(
+                TransactionType::Create,
+                peer_id,
+                b"Hello Peer Block".to_vec(),
+                &peer_key,
+            )
+            .map_err(|e| e.to_string())?],
+            &peer_key,
+            blockchain.next_block_timestamp(),
+            0,
+        )
+        .unwrap();
+        blockchain
+            .append_block(block.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(Some(block), blockchain.chain.last_block());
+
+        let tip = blockchain.chain.last_block().unwrap();
+        let outsider = identity::ed25519::Keypair::generate();
+        let block = Block::new_with(
+            tip.header.hash(),
+            tip.header.ordinal + 1,
+            vec![],
+            &outsider,
+            blockchain.next_block_timestamp(),
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            Err(BlockchainError::UnexpectedCommitter {
+                ordinal: tip.header.ordinal + 1,
+                committer: block.header.committer
+            }),
+            blockchain.append_block(block).await
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_append_raw_block() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut blockchain = Blockchain::new(&keypair);
+        let genesis_hash = blockchain.genesis_hash();
+        let block = Block::new_with(
+            genesis_hash,
+            1,
+            vec![],
+            &keypair,
+            blockchain.next_block_timestamp(),
+            0,
+        )
+        .unwrap();
+
+        // a JSON value sorts the fields of the block by name, the nonce of the block fits in
+        // the u64 of a JSON value
+        let json_value: serde_json::Value =
+            serde_json::from_slice(&serde_json::to_vec(&block).unwrap()).unwrap();
+        let reordered = serde_json::to_vec(&json_value).unwrap();
+        assert_eq!(
+            Err(BlockchainError::NonCanonicalBlock(1)),
+            blockchain.append_raw_block(&reordered).await
+        );
+        assert_eq!(0, blockchain.height());
+        assert!(matches!(
+            blockchain.append_raw_block(b"{}").await,
+            Err(BlockchainError::Serialization(_))
+        ));
+
+        // a digest too short to hash is rejected rather than crashing the Merkle root
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let transactions = [b"first", b"other"]
+            .iter()
+            .map(|payload| {
+                Transaction::new(
+                    TransactionType::Create,
+                    local_id,
+                    payload.to_vec(),
+                    &keypair,
+                )
+            })
+            .collect::<Result<Vec<Transaction>, _>>()
+            .map_err(|e| e.to_string())?;
+        let tx_hash = transactions[0].hash().to_string();
+        let short_digest =
+            serde_json::to_string(&Block::new(genesis_hash, 1, transactions, &keypair).unwrap())
+                .map_err(|e| e.to_string())?
+                .replace(&tx_hash, "1b04deadbeef");
+        assert!(matches!(
+            blockchain.append_raw_block(short_digest.as_bytes()).await,
+            Err(BlockchainError::Serialization(_))
+        ));
+
+        let raw = serde_json::to_vec(&block).map_err(|e| e.to_string())?;
+        blockchain
+            .append_raw_block(&raw)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(Some(&block), blockchain.get_block_by_ordinal(1));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_rollback_to() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let peer_id = Address::from(identity::Keypair::generate_ed25519().public());
+        let mut blockchain = Blockchain::new(&keypair);
+        blockchain
+            .add_block(
+                b"first".to_vec(),
+                identity::Keypair::Ed25519(keypair.clone()),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        blockchain.finalize(1).map_err(|e| e.to_string())?;
+        let authorities = blockchain.authorities().clone();
+        let indexed = blockchain.transactions_by_submitter(&local_id).len();
+
+        let add_authority = Transaction::new(
+            TransactionType::AddAuthority,
+            local_id,
+            peer_id.to_bytes(),
+            &keypair,
+        )
+        .map_err(|e| e.to_string())?;
+        blockchain
+            .submit_transaction(add_authority.clone(), |_, _| {})
+            .map_err(|e| e.to_string())?;
+        blockchain
+            .produce_block(&keypair)
+            .await
+            .map_err(|e| e.to_string())?;
+        blockchain
+            .add_block(
+                b"third".to_vec(),
+                identity::Keypair::Ed25519(keypair.clone()),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        let still_pending = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"pending".to_vec(),
+            &keypair,
+        )
+        .map_err(|e| e.to_string())?;
+        blockchain
+            .submit_transaction(still_pending.clone(), |_, _| {})
+            .map_err(|e| e.to_string())?;
+        assert_eq!(3, blockchain.height());
+        assert!(blockchain.authorities().contains(&peer_id));
+        assert_eq!(1, blockchain.pending_count());
+
+        assert_eq!(
+            Err(BlockchainError::BelowFinality {
+                ordinal: 0,
+                finalized: 1
+            }),
+            blockchain.rollback_to(0)
+        );
+        assert_eq!(
+            Err(BlockchainError::UnknownBlock(4)),
+            blockchain.rollback_to(4)
+        );
+
+        let third = blockchain.get_block_by_ordinal(3).unwrap().clone();
+        let removed = blockchain.rollback_to(1).map_err(|e| e.to_string())?;
+        assert_eq!(
+            vec![2, 3],
+            removed
+                .iter()
+                .map(|block| block.header.ordinal)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, blockchain.height());
+        assert_eq!(&authorities, blockchain.authorities());
+        assert_eq!(
+            vec![add_authority, third.transactions[0].clone(), still_pending],
+            blockchain.pending
+        );
+        assert_eq!(
+            indexed,
+            blockchain.transactions_by_submitter(&local_id).len()
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_max_block_weight() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let max = 2 * SIGNATURE_WEIGHT + 20;
+        let mut blockchain = BlockchainBuilder::new(&keypair)
+            .max_block_weight(max)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let transactions = blockchain
+            .submit_transactions(
+                // serialized with their quotes, the first three are 10 bytes long
+                vec![
+                    "a".repeat(8),
+                    "b".repeat(8),
+                    "c".repeat(8),
+                    "d".repeat(max as usize),
+                ],
+                &keypair,
+                |_, _| {},
+            )
+            .map_err(|e| e.to_string())?;
+
+        let block = blockchain
+            .produce_block(&keypair)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("no block produced")?;
+        assert_eq!(transactions[..2], block.transactions);
+        assert_eq!(
+            transactions[0].weight() + transactions[1].weight(),
+            block.weight()
+        );
+        // the transaction heavier than a block is rejected, the last light one waits
+        assert_eq!(vec![transactions[2].clone()], blockchain.pending);
+
+        let overweight = Block::new_with(
+            block.header.hash(),
+            2,
+            transactions[..3].to_vec(),
+            &keypair,
+            blockchain.next_block_timestamp(),
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            Err(BlockchainError::OverweightBlock {
+                ordinal: 2,
+                weight: 3 * SIGNATURE_WEIGHT + 30,
+                max
+            }),
+            blockchain.append_block(overweight).await
+        );
+        assert_eq!(1, blockchain.height());
+
+        // a heavier fork is held to the same maximum
+        let genesis = blockchain.get_block_by_ordinal(0).unwrap().clone();
+        let mut fork = Chain::genesis_only(genesis.clone());
+        fork.add_block(
+            Block::new_with_difficulty(
+                genesis.header.hash(),
+                1,
+                transactions[..3].to_vec(),
+                &keypair,
+                genesis.header.timestamp + 1,
+                2,
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            Err(BlockchainError::OverweightBlock {
+                ordinal: 1,
+                weight: 3 * SIGNATURE_WEIGHT + 30,
+                max
+            }),
+            blockchain.consider_chain(fork).await
+        );
+        assert_eq!(Some(&block), blockchain.get_block_by_ordinal(1));
+
+        assert!(matches!(
+            BlockchainBuilder::new(&keypair).max_block_weight(0).build(),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_append_block_duplicate_ordinal() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = Blockchain::new(&ed25519_key);
+        for i in 0..2 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        let parent_hash = blockchain.chain.last_block().unwrap().header.hash();
+        let timestamp = blockchain.next_block_timestamp();
+        let first = Block::new_with(parent_hash, 3, vec![], &ed25519_key, timestamp, 1).unwrap();
+        blockchain
+            .append_block(first.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let second = Block::new_with(parent_hash, 3, vec![], &ed25519_key, timestamp, 2).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(
+            Err(BlockchainError::DuplicateOrdinal { ordinal: 3 }),
+            blockchain.append_block(second).await
+        );
+        assert_eq!(3, blockchain.height());
+        assert_eq!(Some(&first), blockchain.get_block_by_ordinal(3));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_transaction_dependencies() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blockchain = Blockchain::in_memory(&keypair);
+        let version_1 = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"publish version 1".to_vec(),
+            &keypair,
+        )
+        .map_err(|e| e.to_string())?;
+        let version_2 = Transaction::new_with_dependencies(
+            TransactionType::Create,
+            local_id,
+            b"publish version 2".to_vec(),
+            vec![version_1.hash()],
+            &keypair,
+        )
+        .map_err(|e| e.to_string())?;
+        let next_block = |blockchain: &Blockchain, transactions: Vec<Transaction>| {
+            let parent = blockchain.chain.last_block().unwrap();
+            Block::new_with(
+                parent.header.hash(),
+                parent.header.ordinal + 1,
+                transactions,
+                &keypair,
+                blockchain.next_block_timestamp(),
+                0,
+            )
+            .unwrap()
+        };
+
+        // unsettled, whether missing from the chain or in the same block as its dependency
+        let block = next_block(&blockchain, vec![version_2.clone()]);
+        assert_eq!(
+            Err(BlockchainError::UnsettledDependency {
+                ordinal: 1,
+                transaction: version_2.hash(),
+                dependency: version_1.hash(),
+            }),
+            blockchain.append_block(block).await
+        );
+        let block = next_block(&blockchain, vec![version_1.clone(), version_2.clone()]);
+        assert!(matches!(
+            blockchain.append_block(block).await,
+            Err(BlockchainError::UnsettledDependency { .. })
+        ));
+        assert_eq!(0, blockchain.height());
+
+        // satisfied, by the chain or by a preceding block of the same batch
+        let first = next_block(&blockchain, vec![version_1]);
+        let second = Block::new_with(
+            first.header.hash(),
+            2,
+            vec![version_2],
+            &keypair,
+            first.header.timestamp + 1,
+            0,
+        )
+        .unwrap();
+        blockchain
+            .apply_blocks(vec![first, second])
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(2, blockchain.height());
+
+        let version_3 = Transaction::new_with_dependencies(
+            TransactionType::Create,
+            local_id,
+            b"publish version 3".to_vec(),
+            vec![blockchain.get_block_by_ordinal(2).unwrap().transactions[0].hash()],
+            &keypair,
+        )
+        .map_err(|e| e.to_string())?;
+        let block = next_block(&blockchain, vec![version_3]);
+        blockchain
+            .append_block(block)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(3, blockchain.height());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_apply_blocks() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut source = Blockchain::new(&ed25519_key);
+        for i in 0..10 {
+            source
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        let range = |from: Ordinal, to: Ordinal| -> Vec<Block> {
+            source
+                .blocks_in_range(from, to)
+                .into_iter()
+                .cloned()
+                .collect()
+        };
+
+        let mut peer = BlockchainBuilder::new(&ed25519_key)
+            .genesis(source.get_block_by_ordinal(0).unwrap().clone())
+            .build()
+            .map_err(|e| e.to_string())?;
+        peer.apply_blocks(range(1, 5))
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(5, peer.height());
+
+        assert_eq!(
+            Err(BlockchainError::UnexpectedOrdinal {
+                expected: 6,
+                actual: 7
+            }),
+            peer.apply_blocks(range(7, 10)).await
+        );
+        let mut out_of_order = range(6, 10);
+        out_of_order.swap(1, 2);
+        assert_eq!(
+            Err(BlockchainError::UnexpectedOrdinal {
+                expected: 7,
+                actual: 8
+            }),
+            peer.apply_blocks(out_of_order).await
+        );
+        assert_eq!(5, peer.height());
+
+        peer.apply_blocks(range(6, 10))
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(10, peer.height());
+        assert_eq!(source.blocks_in_range(0, 10), peer.blocks_in_range(0, 10));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_apply_blocks_settles_pending() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blockchain = Blockchain::new(&keypair);
+        let trans = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"peer".to_vec(),
+            &keypair,
+        )
+        .map_err(|e| e.to_string())?;
+        let settled = Arc::new(AtomicBool::new(false));
+        blockchain
+            .submit_transaction(trans.clone(), {
+                let settled = settled.clone();
+                move |_, outcome| {
+                    settled.store(outcome == TransactionOutcome::Settled, Ordering::SeqCst)
+                }
+            })
+            .map_err(|e| e.to_string())?;
+
+        // the same transaction reaches a peer, which commits it first
+        let block = Block::new_with(
+            blockchain.genesis_hash(),
+            1,
+            vec![trans],
+            &keypair,
+            blockchain.next_block_timestamp(),
+            0,
+        )
+        .map_err(|e| e.to_string())?;
+        blockchain
+            .apply_blocks(vec![block])
+            .await
+            .map_err(|e| e.to_string())?;
+
+        assert!(settled.load(Ordering::SeqCst));
+        assert_eq!(0, blockchain.pending_count());
+        assert_eq!(
+            Ok(None),
+            blockchain
+                .produce_block(&keypair)
+                .await
+                .map_err(|e| e.to_string())
+        );
+        assert_eq!(1, blockchain.height());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_iter_blocks_from_disk() -> Result<(), String> {
+        let storage_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+            .storage_dir(storage_dir.path())
+            .build()
+            .map_err(|e| e.to_string())?;
+        for i in 0..3 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        let mut blocks = Blockchain::iter_blocks_from_disk(storage_dir.path());
+        for ordinal in 0..3 {
+            assert_eq!(
+                blockchain.get_block_by_ordinal(ordinal),
+                blocks.next().unwrap().ok().as_ref()
+            );
+        }
+        // the last block is only read once it is reached
+        let tip = blockchain.get_block_by_ordinal(3).unwrap();
+        std::fs::remove_file(storage::build_path_for_block(storage_dir.path(), tip))
+            .map_err(|e| e.to_string())?;
+        assert!(matches!(
+            blocks.next(),
+            Some(Err(BlockchainError::StorageFailure(_)))
+        ));
+        assert!(blocks.next().is_none());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_self_check() -> Result<(), String> {
+        let storage_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+            .storage_dir(storage_dir.path())
+            .build()
+            .map_err(|e| e.to_string())?;
+        for i in 1..5 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        assert!(blockchain.self_check().is_healthy());
+
+        let corrupted = blockchain.get_block_by_ordinal(2).unwrap();
+        fs::write(
+            storage::build_path_for_block(storage_dir.path(), corrupted),
+            b"not a block",
+        )
+        .map_err(|e| e.to_string())?;
+
+        let report = blockchain.self_check();
+        assert!(!report.is_healthy());
+        assert!(report.linkage.is_pass());
+        assert!(report.tip_hash.is_pass());
+        assert!(report.authorities.is_pass());
+        assert!(report.gaps.is_pass());
+        match report.block_files {
+            CheckOutcome::Fail(failures) => {
+                assert_eq!(1, failures.len());
+                assert!(failures[0].starts_with("block 2 can not be read"));
+            }
+            CheckOutcome::Pass => return Err("the corrupted block was not flagged".to_string()),
+        }
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_on_height() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = Blockchain::new(&ed25519_key);
+        let mut reached = Box::pin(blockchain.on_height(5));
+        let observers = blockchain.block_observers.len();
+
+        for i in 1..=5 {
+            // still waiting with only i - 1 blocks appended
+            assert_eq!(None, reached.as_mut().now_or_never());
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        assert_eq!(Some(()), reached.now_or_never());
+        assert_eq!(observers - 1, blockchain.block_observers.len());
+
+        assert_eq!(Some(()), blockchain.on_height(3).now_or_never());
+        assert_eq!(observers - 1, blockchain.block_observers.len());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_gc_orphans() -> Result<(), String> {
+        let storage_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        // replaced blocks are only left behind when blocks are written asynchronously
+        let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+            .storage_dir(storage_dir.path())
+            .async_writes(true)
+            .build()
+            .map_err(|e| e.to_string())?;
+        for i in 0..2 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        blockchain.flush_writes().await.map_err(|e| e.to_string())?;
+        let reorged: Vec<PathBuf> = blockchain.chain.blocks()[1..]
+            .iter()
+            .map(|block| storage::build_path_for_block(storage_dir.path(), block))
+            .collect();
+        assert_eq!(
+            Vec::<PathBuf>::new(),
+            blockchain
+                .list_orphan_files(storage_dir.path())
+                .map_err(|e| e.to_string())?
+        );
+
+        let mut heavier = Chain::genesis_only(blockchain.get_block_by_ordinal(0).unwrap().clone());
+        for ordinal in 1..=2 {
+            let parent_hash = heavier.last_block().unwrap().header.hash();
+            heavier.add_block(
+                Block::new_with_difficulty(
+                    parent_hash,
+                    ordinal,
+                    vec![],
+                    &ed25519_key,
+                    blockchain.next_block_timestamp() + ordinal as u64,
+                    2,
+                )
+                .unwrap(),
+            );
+        }
+        assert_eq!(Ok(true), blockchain.consider_chain(heavier).await);
+        blockchain.flush_writes().await.map_err(|e| e.to_string())?;
+
+        assert_eq!(
+            reorged,
+            blockchain
+                .list_orphan_files(storage_dir.path())
+                .map_err(|e| e.to_string())?
+        );
+        assert_eq!(reorged, blockchain.gc_orphans().map_err(|e| e.to_string())?);
+        assert!(reorged.iter().all(|path| !path.exists()));
+        for block in blockchain.chain.iter() {
+            assert!(storage::build_path_for_block(storage_dir.path(), block).is_file());
+        }
+
+        // the files of pruned blocks are left behind too
+        let pruned = storage::build_path_for_block(
+            storage_dir.path(),
+            blockchain.get_block_by_ordinal(1).unwrap(),
+        );
+        blockchain.prune(1);
+        assert_eq!(
+            vec![pruned],
+            blockchain
+                .list_orphan_files(storage_dir.path())
+                .map_err(|e| e.to_string())?
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_gc_orphans_in_segments() -> Result<(), String> {
+        let storage_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut blockchain = BlockchainBuilder::new(&keypair)
+            .storage_dir(storage_dir.path())
+            .build()
+            .map_err(|e| e.to_string())?;
+        blockchain
+            .submit_transactions(vec!["first", "second"], &keypair, |_, _| {})
+            .map_err(|e| e.to_string())?;
+        blockchain
+            .produce_block(&keypair)
+            .await
+            .map_err(|e| e.to_string())?;
+        blockchain.compact(5).map_err(|e| e.to_string())?;
+
+        // a block of a replaced fork merged into the segment along with the chain
+        let segment = storage::build_path_for_segment(storage_dir.path(), 0, 4);
+        let orphan = Block::new(blockchain.genesis_hash(), 1, vec![], &keypair).unwrap();
+        let mut blocks = blockchain.chain.blocks();
+        blocks.push(orphan);
+        storage::write_segment(storage_dir.path(), 0, 4, &blocks).map_err(|e| e.to_string())?;
+        assert_eq!(
+            vec![segment.clone()],
+            blockchain
+                .list_orphan_files(storage_dir.path())
+                .map_err(|e| e.to_string())?
+        );
+        assert!(!blockchain.self_check().is_healthy());
+
+        assert_eq!(
+            vec![segment.clone()],
+            blockchain.gc_orphans().map_err(|e| e.to_string())?
+        );
+        assert_eq!(
+            blockchain.chain.blocks(),
+            storage::read_segment(&segment).map_err(|e| e.to_string())?
+        );
+        assert!(blockchain.self_check().is_healthy());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_network_id() -> Result<(), String> {
+        let storage_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut networks = vec![];
+        for network_id in ["alpha", "beta"] {
+            let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+                .storage_dir(storage_dir.path())
+                .network_id(network_id)
+                .build()
+                .map_err(|e| e.to_string())?;
+            blockchain
+                .add_block(network_id.as_bytes().to_vec(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+            networks.push((storage_dir.path().join(network_id), blockchain));
+        }
+
+        for (block_dir, blockchain) in networks.iter() {
+            assert_eq!(Some(block_dir.clone()), blockchain.config().block_dir());
+            let stored = storage::list_block_files(block_dir).map_err(|e| e.to_string())?;
+            assert_eq!(
+                blockchain
+                    .chain
+                    .iter()
+                    .map(|block| storage::build_path_for_block(block_dir, block))
+                    .collect::<Vec<PathBuf>>(),
+                stored.into_iter().map(|(_, path)| path).collect::<Vec<_>>()
+            );
+            let opened =
+                Blockchain::open(blockchain.config().clone()).map_err(|e| e.to_string())?;
+            assert_eq!(blockchain.chain.blocks(), opened.chain.blocks());
+            assert_eq!(blockchain.config(), opened.config());
+        }
+        assert!(storage::list_block_files(storage_dir.path())
+            .map_err(|e| e.to_string())?
+            .is_empty());
+
+        assert!(matches!(
+            BlockchainBuilder::new(&ed25519_key)
+                .storage_dir(storage_dir.path())
+                .network_id("../gamma")
+                .build(),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_network_salt() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut alpha = BlockchainBuilder::new(&ed25519_key)
+            .network_id("alpha")
+            .build()
+            .map_err(|e| e.to_string())?;
+        let mut beta = BlockchainBuilder::new(&ed25519_key)
+            .genesis(alpha.get_block_by_ordinal(0).unwrap().clone())
+            .network_id("beta")
+            .build()
+            .map_err(|e| e.to_string())?;
+        alpha
+            .add_block(b"Hello Alpha".to_vec(), keypair.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        let replayed = alpha.get_block_by_ordinal(1).unwrap().clone();
+        let trans = replayed.transactions[0].clone();
+        assert_eq!(network_salt("alpha"), trans.network_salt());
+
+        assert_eq!(
+            Err(BlockchainError::Transaction(
+                TransactionError::NetworkMismatch(trans.hash())
+            )),
+            beta.apply_blocks(vec![replayed]).await
+        );
+        beta.submit_transaction(trans, |_, _| {})
+            .map_err(|e| e.to_string())?;
+        assert_eq!(0, beta.pending_count());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_receipt_for() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut blockchain = Blockchain::new(&keypair);
+        let genesis_hash = blockchain.genesis_hash();
+        let transactions = blockchain
+            .submit_transactions(vec!["first", "second", "third"], &keypair, |_, _| {})
+            .map_err(|e| e.to_string())?;
+        blockchain
+            .produce_block(&keypair)
+            .await
+            .map_err(|e| e.to_string())?;
+        blockchain
+            .add_block(
+                b"later".to_vec(),
+                identity::Keypair::Ed25519(keypair.clone()),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        // only the finalized blocks are covered by receipts
+        assert_eq!(None, blockchain.receipt_for(&transactions[1].hash()));
+        let checkpoint = blockchain.finalize(2).map_err(|e| e.to_string())?;
+
+        let receipt = blockchain
+            .receipt_for(&transactions[1].hash())
+            .ok_or("no receipt")?;
+        assert_eq!(1, receipt.header.ordinal);
+        assert_eq!(1, receipt.descendants.len());
+        assert!(receipt.verify(&checkpoint));
+        assert!(!receipt.verify(&Checkpoint {
+            ordinal: 0,
+            hash: genesis_hash
+        }));
+        assert!(!receipt.verify(&Checkpoint {
+            ordinal: 2,
+            hash: HashDigest::new(b"another block")
+        }));
+
+        let later = blockchain.get_block_by_ordinal(2).unwrap().transactions[0].hash();
+        let later_receipt = blockchain.receipt_for(&later).ok_or("no receipt")?;
+        assert!(later_receipt.descendants.is_empty());
+        assert!(later_receipt.verify(&checkpoint));
+
+        let mut tampered = receipt.clone();
+        tampered
+            .proof
+            .path
+            .push(MerkleStep::Left(HashDigest::new(b"forged")));
+        assert!(!tampered.verify(&checkpoint));
+        let mut tampered = receipt.clone();
+        tampered.tx_hash = later;
+        assert!(!tampered.verify(&checkpoint));
+        let mut tampered = receipt.clone();
+        tampered.signature = later_receipt.signature.clone();
+        assert!(!tampered.verify(&checkpoint));
+
+        // a block linked to genesis but not to the checkpoint
+        let forger = identity::ed25519::Keypair::generate();
+        let forger_id = Address::from(identity::PublicKey::Ed25519(forger.public()));
+        let forged_transaction = Transaction::new(
+            TransactionType::Create,
+            forger_id,
+            b"forged".to_vec(),
+            &forger,
+        )
+        .map_err(|e| e.to_string())?;
+        let forged_block =
+            Block::new(genesis_hash, 1, vec![forged_transaction.clone()], &forger).unwrap();
+        let forged = Receipt {
+            tx_hash: forged_transaction.hash(),
+            header: forged_block.header,
+            signature: forged_block.signature(),
+            proof: forged_block
+                .inclusion_proof(&forged_transaction.hash())
+                .ok_or("no proof")?,
+            descendants: vec![receipt.descendants[0]],
+        };
+        assert!(!forged.verify(&checkpoint));
+
+        assert_eq!(None, blockchain.receipt_for(&HashDigest::new(b"unknown")));
+
+        // once pruned, the receipts of the blocks left are still anchored on the checkpoint
+        blockchain.prune(1);
+        assert_eq!(None, blockchain.receipt_for(&transactions[1].hash()));
+        let later_receipt = blockchain.receipt_for(&later).ok_or("no receipt")?;
+        assert!(later_receipt.verify(&checkpoint));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_open() -> Result<(), String> {
+        let storage_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+            .storage_dir(storage_dir.path())
+            .build()
+            .map_err(|e| e.to_string())?;
+        for i in 0..3 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        let opened = Blockchain::open(blockchain.config().clone()).map_err(|e| e.to_string())?;
+        assert_eq!(blockchain.chain.blocks(), opened.chain.blocks());
+        assert_eq!(blockchain.authorities(), opened.authorities());
+        assert!(opened.self_check().is_healthy());
+
+        let empty_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let empty = BlockchainConfig {
+            storage_dir: Some(empty_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert_eq!(
+            Some(BlockchainError::MissingGenesis),
+            Blockchain::open(empty).err()
+        );
+        assert!(matches!(
+            Blockchain::open(BlockchainConfig::default()),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+
+        // a block left over from a replaced fork takes the ordinal of a block of the chain
+        let tip = blockchain.chain.last_block().unwrap();
+        let parent_hash = blockchain.get_block_by_ordinal(1).unwrap().header.hash();
+        let orphan = Block::new(parent_hash, 2, vec![], &ed25519_key).unwrap();
+        let orphan_path =
+            storage::write_block(storage_dir.path(), &orphan).map_err(|e| e.to_string())?;
+        assert!(Blockchain::open(blockchain.config().clone()).is_err());
+        let opened = Blockchain::open_skipping_orphans(blockchain.config().clone())
+            .map_err(|e| e.to_string())?;
+        assert_eq!(Some(tip), opened.chain.last_block());
+        assert_eq!(blockchain.chain.blocks(), opened.chain.blocks());
+        let report = opened.self_check();
+        assert!(report.linkage.is_pass());
+        assert_eq!(
+            CheckOutcome::Fail(vec![format!(
+                "{} is not part of the chain",
+                orphan_path.display()
+            )]),
+            report.orphans
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_writes() -> Result<(), String> {
+        let storage_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+            .storage_dir(storage_dir.path())
+            .async_writes(true)
+            .build()
+            .map_err(|e| e.to_string())?;
+        for i in 1..=5 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+            // the block is in memory as soon as it is appended, on disk or not
+            assert_eq!(i, blockchain.height());
+        }
+
+        blockchain.flush_writes().await.map_err(|e| e.to_string())?;
+        for block in blockchain.chain.iter() {
+            assert!(storage::build_path_for_block(storage_dir.path(), block).is_file());
+        }
+        assert!(blockchain.self_check().is_healthy());
+
+        assert!(matches!(
+            BlockchainBuilder::new(&ed25519_key)
+                .async_writes(true)
+                .build(),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            BlockchainBuilder::new(&ed25519_key)
+                .storage_dir(storage_dir.path())
+                .block_store(InMemoryBlockStore::new())
+                .async_writes(true)
+                .build(),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_compact() -> Result<(), String> {
+        let storage_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+            .storage_dir(storage_dir.path())
+            .build()
+            .map_err(|e| e.to_string())?;
+        for i in 1..50 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        assert_eq!(
+            50,
+            storage::list_block_files(storage_dir.path())
+                .map_err(|e| e.to_string())?
+                .len()
+        );
+
+        blockchain.compact(10).map_err(|e| e.to_string())?;
+
+        assert!(storage::list_block_files(storage_dir.path())
+            .map_err(|e| e.to_string())?
+            .is_empty());
+        assert_eq!(
+            5,
+            storage::list_segment_files(storage_dir.path())
+                .map_err(|e| e.to_string())?
+                .len()
+        );
+        let stored = Blockchain::iter_blocks_from_disk(storage_dir.path())
+            .collect::<Result<Vec<Block>, BlockchainError>>()
+            .map_err(|e| e.to_string())?;
+        assert_eq!(blockchain.chain.blocks(), stored);
+        let store = blockchain.block_store().unwrap();
+        for block in blockchain.chain.iter() {
+            assert_eq!(
+                Some(block),
+                store
+                    .get(&block.header.hash())
+                    .map_err(|e| e.to_string())?
+                    .as_ref()
+            );
+        }
+        assert!(matches!(
+            blockchain.compact(0),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_flush() -> Result<(), String> {
+        let storage_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut blockchain = BlockchainBuilder::new(&keypair)
+            .storage_dir(storage_dir.path())
+            .max_transactions_per_block(2)
+            .build()
+            .map_err(|e| e.to_string())?;
+        blockchain
+            .submit_transactions(vec![0, 1, 2], &keypair, |_, _| {})
+            .map_err(|e| e.to_string())?;
+
+        let blocks = blockchain
+            .flush(&keypair)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        assert_eq!(2, blocks.len());
+        assert_eq!(0, blockchain.pending_count());
+        for block in blocks.iter() {
+            assert!(storage::build_path_for_block(storage_dir.path(), block).is_file());
+        }
+        assert!(blockchain
+            .flush(&keypair)
+            .await
+            .map_err(|e| e.to_string())?
+            .is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_store() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+            .block_store(InMemoryBlockStore::new())
+            .build()
+            .map_err(|e| e.to_string())?;
+        for i in 0..3 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        let store = blockchain.block_store().unwrap();
+        let stored = store
+            .iter()
+            .collect::<std::io::Result<Vec<Block>>>()
+            .map_err(|e| e.to_string())?;
+        assert_eq!(4, stored.len());
+        for block in stored.iter() {
+            assert_eq!(Ok(()), block.verify());
+            assert_eq!(
+                blockchain.get_block_by_hash(&block.header.hash()),
+                Some(block)
+            );
+        }
+        let tip_hash = blockchain.get_block_by_ordinal(3).unwrap().header.hash();
+        assert_eq!(
+            blockchain.get_block_by_ordinal(3).cloned(),
+            store.get(&tip_hash).map_err(|e| e.to_string())?
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_in_memory() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = Blockchain::in_memory(&ed25519_key);
+        for i in 0..5 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        let stored = blockchain
+            .block_store()
+            .unwrap()
+            .iter()
+            .collect::<std::io::Result<Vec<Block>>>()
+            .map_err(|e| e.to_string())?;
+        assert_eq!(blockchain.chain.blocks(), stored);
+        assert_eq!(None, blockchain.config().storage_dir);
+        assert_eq!(Ok(0), blockchain.total_bytes_on_disk());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_export_import() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = Blockchain::new(&ed25519_key);
+        for i in 0..3 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        let tip_hash = blockchain.chain.last_block().unwrap().header.hash();
+
+        for format in [ExportFormat::JsonLines, ExportFormat::Bincode] {
+            let mut exported = vec![];
+            blockchain
+                .export(&mut exported, format)
+                .map_err(|e| e.to_string())?;
+
+            let imported =
+                Blockchain::import(exported.as_slice(), format).map_err(|e| e.to_string())?;
+            assert_eq!(blockchain.height(), imported.height());
+            assert_eq!(tip_hash, imported.chain.last_block().unwrap().header.hash());
+            assert_eq!(blockchain.authorities(), imported.authorities());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_rejects_broken_linkage() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let blockchain = Blockchain::new(&keypair);
+        let mut exported = vec![];
+        blockchain
+            .export(&mut exported, ExportFormat::JsonLines)
+            .map_err(|e| e.to_string())?;
+        let unlinked = Block::new(HashDigest::new(b"unrelated"), 1, vec![], &keypair).unwrap();
+        storage::write_exported_block(&mut exported, &unlinked, ExportFormat::JsonLines)
+            .map_err(|e| e.to_string())?;
+
+        assert!(matches!(
+            Blockchain::import(exported.as_slice(), ExportFormat::JsonLines),
+            Err(BlockchainError::ParentHashMismatch(1))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_consider_chain() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut peer = Blockchain::new(&ed25519_key);
+        let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+            .genesis(peer.get_block_by_ordinal(0).unwrap().clone())
+            .build()
+            .map_err(|e| e.to_string())?;
+        for i in 0..2 {
+            peer.add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        let received = Arc::new(AtomicUsize::new(0));
+        blockchain.add_block_listener({
+            let received = received.clone();
+            move |_: &Block| {
+                received.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        assert_eq!(
+            Ok(true),
+            blockchain.consider_chain((*peer.chain).clone()).await
+        );
+        assert_eq!(2, blockchain.height());
+        assert_eq!(2, received.load(Ordering::SeqCst));
+        assert_eq!(
+            Ok(false),
+            blockchain.consider_chain((*peer.chain).clone()).await
+        );
+
+        let other_network = Blockchain::new(&identity::ed25519::Keypair::generate());
+        assert_eq!(
+            Err(BlockchainError::Chain(ChainError::GenesisMismatch {
+                expected: blockchain.genesis_hash(),
+                actual: other_network.genesis_hash()
+            })),
+            blockchain
+                .consider_chain((*other_network.chain).clone())
+                .await
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_consider_chain_reconciles_mempool() -> Result<(), String> {
+        let storage_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blockchain = BlockchainBuilder::new(&keypair)
+            .storage_dir(storage_dir.path())
+            .build()
+            .map_err(|e| e.to_string())?;
+        let transaction = |payload: &[u8]| {
+            Transaction::new(
+                TransactionType::Create,
+                local_id,
+                payload.to_vec(),
+                &keypair,
+            )
+            .map_err(|e| e.to_string())
+        };
+        let replaced = transaction(b"replaced")?;
+        blockchain
+            .submit_transaction(replaced.clone(), |_, _| {})
+            .map_err(|e| e.to_string())?;
+        let ours = blockchain
+            .produce_block(&keypair)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("no block produced")?;
+        let adopted = transaction(b"adopted")?;
+        let settled = Arc::new(AtomicBool::new(false));
+        blockchain
+            .submit_transaction(adopted.clone(), {
+                let settled = settled.clone();
+                move |_, outcome| {
+                    settled.store(outcome == TransactionOutcome::Settled, Ordering::SeqCst)
+                }
+            })
+            .map_err(|e| e.to_string())?;
+
+        let genesis = blockchain.get_block_by_ordinal(0).unwrap().clone();
+        let mut heavier = Chain::genesis_only(genesis.clone());
+        heavier.add_block(
+            Block::new_with_difficulty(
+                genesis.header.hash(),
+                1,
+                vec![adopted],
+                &keypair,
+                genesis.header.timestamp + 1,
+                2,
+            )
+            .unwrap(),
         );
-        // Make the "genesis" blocks
-        let block = Block::new(HashDigest::new(b""), 0, Vec::from([transaction]), keypair);
-        let mut chain: Chain = Default::default();
-        chain.add_block(block);
-        Self {
-            trans_observers: Default::default(),
-            payload_observers: vec![],
-            chain,
-        }
-    }
+        assert_eq!(Ok(true), blockchain.consider_chain(heavier).await);
 
-    pub fn submit_transaction<CallBack: 'static + FnOnce(Transaction)>(
-        &mut self,
-        trans: Transaction,
-        on_done: CallBack,
-    ) -> &mut Self {
-        self.trans_observers.insert(trans, Box::new(on_done));
-        self
+        assert_eq!(vec![replaced], blockchain.pending);
+        assert!(settled.load(Ordering::SeqCst));
+        let store = blockchain.block_store().ok_or("no block store")?;
+        assert_eq!(
+            Ok(None),
+            store.get(&ours.header.hash()).map_err(|e| e.to_string())
+        );
+        assert!(!storage::build_path_for_block(storage_dir.path(), &ours).exists());
+        Ok(())
     }
 
-    pub fn notify_transaction_settled(&mut self, trans: Transaction) {
-        // if there were no observers, we don't care
-        if let Some(on_settled) = self.trans_observers.remove(&trans) {
-            on_settled(trans)
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_consider_chain_when_pruned() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = Blockchain::new(&ed25519_key);
+        for i in 0..5 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
         }
-    }
+        let unpruned = (*blockchain.chain).clone();
+        blockchain.prune(2);
+        let checkpoint = blockchain.chain.checkpoint();
+        let received = Arc::new(AtomicUsize::new(0));
+        blockchain.add_block_listener({
+            let received = received.clone();
+            move |_: &Block| {
+                received.fetch_add(1, Ordering::SeqCst);
+            }
+        });
 
-    pub fn add_payload_listener<CallBack: 'static + FnMut(&Vec<u8>)>(
-        &mut self,
-        on_payload: CallBack,
-    ) -> &mut Self {
-        self.payload_observers.push(Box::new(on_payload));
-        self
-    }
+        // an unpruned copy of our own chain is not heavier than ours
+        assert_eq!(Ok(false), blockchain.consider_chain(unpruned.clone()).await);
+        assert_eq!(5, blockchain.height());
+        assert_eq!(0, received.load(Ordering::SeqCst));
 
-    pub async fn notify_payload_event(&mut self, payload: &Vec<u8>) -> &mut Self {
-        self.payload_observers
-            .iter_mut()
-            .for_each(|notify| notify(payload));
-        self
+        // a fork right after the checkpoint is compared from the checkpoint
+        let mut fork = unpruned;
+        fork.truncate(3);
+        let timestamp = blockchain.next_block_timestamp();
+        for ordinal in 4..=6 {
+            let parent_hash = fork.last_block().unwrap().header.hash();
+            fork.add_block(
+                Block::new_with(
+                    parent_hash,
+                    ordinal,
+                    vec![],
+                    &ed25519_key,
+                    timestamp + ordinal as u64,
+                    0,
+                )
+                .map_err(|e| e.to_string())?,
+            );
+        }
+        assert_eq!(Ok(true), blockchain.consider_chain(fork.clone()).await);
+        assert_eq!(6, blockchain.height());
+        assert_eq!(3, received.load(Ordering::SeqCst));
+        assert_eq!(checkpoint, blockchain.chain.checkpoint());
+        assert_eq!(None, blockchain.get_block_by_ordinal(3));
+        assert_eq!(
+            fork.get_block_by_ordinal(4),
+            blockchain.get_block_by_ordinal(4)
+        );
+        assert!(blockchain.chain.verify_linkage());
+        Ok(())
     }
 
-    /// Add block after receiving payload and keypair
-    pub async fn add_block(
-        &mut self,
-        payload: Vec<u8>,
-        local_key: identity::Keypair,
-    ) -> anyhow::Result<()> {
-        let submitter = Address::from(local_key.public());
-        let ed25519_key = match local_key {
-            Ed25519(some) => some,
-            _ => {
-                anyhow::bail!(
-                    "Blockchain: Key {:?} is not valid Ed25519 format",
-                    local_key
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_consider_chain_prefers_total_difficulty() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut blockchain = Blockchain::new(&keypair);
+        let genesis = blockchain.get_block_by_ordinal(0).unwrap().clone();
+        let fork_of = |difficulty: u128| {
+            let mut chain = Chain::genesis_only(genesis.clone());
+            for ordinal in 1..=2 {
+                let parent_hash = chain.last_block().unwrap().header.hash();
+                chain.add_block(
+                    Block::new_with_difficulty(
+                        parent_hash,
+                        ordinal,
+                        vec![],
+                        &keypair,
+                        genesis.header.timestamp + ordinal as u64,
+                        difficulty,
+                    )
+                    .unwrap(),
                 );
             }
+            chain
         };
-        let trans_vec = vec![Transaction::new(
-            TransactionType::Create,
-            submitter,
-            payload,
-            &ed25519_key,
-        )];
-
-        let last_block = match self.chain.last_block() {
-            Some(block) => block,
-            None => {
-                anyhow::bail!("Blockchain: Local blockchain does non exist!!");
-            }
-        };
+        let light = fork_of(1);
+        let heavy = fork_of(2);
+        assert_eq!(light.len(), heavy.len());
+        assert_eq!(3, light.total_difficulty());
+        assert_eq!(5, heavy.total_difficulty());
 
-        let block = Block::new(
-            last_block.header.hash(),
-            last_block.header.ordinal + 1,
-            trans_vec,
-            &ed25519_key,
-        );
+        assert_eq!(Ok(true), blockchain.consider_chain(light.clone()).await);
+        assert_eq!(Ok(true), blockchain.consider_chain(heavy.clone()).await);
+        assert_eq!(heavy, *blockchain.chain);
 
-        // TODO: Consensus algorithm will be refactored
-        self.commit_block(block).await;
+        assert_eq!(Ok(false), blockchain.consider_chain(light).await);
+        assert_eq!(heavy, *blockchain.chain);
         Ok(())
     }
 
-    /// Commit block and notify block listeners
-    async fn commit_block(&mut self, block: Block) {
-        self.chain.add_block(block.clone());
-
-        for trans in block.transactions {
-            self.notify_payload_event(&trans.payload()).await;
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_finalize() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = Blockchain::new(&ed25519_key);
+        for i in 0..8 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        let mut fork = BlockchainBuilder::new(&ed25519_key)
+            .genesis(blockchain.get_block_by_ordinal(0).unwrap().clone())
+            .build()
+            .map_err(|e| e.to_string())?;
+        fork.apply_blocks(
+            blockchain
+                .blocks_in_range(1, 2)
+                .into_iter()
+                .cloned()
+                .collect(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        for i in 3..=10 {
+            fork.add_block(format!("fork {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
         }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::cell::Cell;
-    use std::rc::Rc;
+        let checkpoint = blockchain.finalize(5).map_err(|e| e.to_string())?;
+        assert_eq!(Some(checkpoint), blockchain.finalized());
+        assert_eq!(
+            blockchain.get_block_by_ordinal(5).unwrap().header.hash(),
+            checkpoint.hash
+        );
+        assert_eq!(
+            Err(BlockchainError::BelowFinality {
+                ordinal: 4,
+                finalized: 5
+            }),
+            blockchain.finalize(4)
+        );
+        assert_eq!(
+            Err(BlockchainError::UnknownBlock(9)),
+            blockchain.finalize(9)
+        );
 
-    use super::*;
+        assert_eq!(
+            Err(BlockchainError::Chain(ChainError::FinalityViolation(5))),
+            blockchain.consider_chain((*fork.chain).clone()).await
+        );
+        assert_eq!(8, blockchain.height());
+
+        let mut extended = (*blockchain.chain).clone();
+        let tip = extended.last_block().unwrap();
+        extended.add_block(
+            Block::new_with(
+                tip.header.hash(),
+                9,
+                vec![],
+                &ed25519_key,
+                blockchain.next_block_timestamp(),
+                0,
+            )
+            .unwrap(),
+        );
+        assert_eq!(Ok(true), blockchain.consider_chain(extended).await);
+        assert_eq!(9, blockchain.height());
+        Ok(())
+    }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_build_blockchain() -> Result<(), String> {
+    async fn test_apply_blocks_rejects_duplicate_nonce() -> Result<(), String> {
         let keypair = identity::ed25519::Keypair::generate();
         let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
         let mut blockchain = Blockchain::new(&keypair);
-
-        let mut transactions = vec![];
-        let data = "Hello First Transaction";
         let transaction = Transaction::new(
             TransactionType::Create,
             local_id,
-            data.as_bytes().to_vec(),
+            b"Hello Replay".to_vec(),
             &keypair,
+        )
+        .map_err(|e| e.to_string())?;
+        let block = Block::new_with(
+            blockchain.genesis_hash(),
+            1,
+            vec![transaction.clone(), transaction.clone()],
+            &keypair,
+            blockchain.next_block_timestamp(),
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Err(BlockchainError::DuplicateNonce {
+                ordinal: 1,
+                submitter: local_id,
+                nonce: transaction.nonce()
+            }),
+            blockchain.apply_blocks(vec![block]).await
         );
-        transactions.push(transaction);
-        assert_eq!(1, blockchain.chain.len());
-        blockchain
-            .commit_block(Block::new(
-                blockchain.chain.blocks()[0].header.hash(),
-                blockchain.chain.blocks()[0].header.ordinal + 1,
-                transactions,
-                &keypair,
-            ))
-            .await;
-        assert_eq!(true, blockchain.chain.blocks().last().unwrap().verify());
-        assert_eq!(2, blockchain.chain.len());
+        assert_eq!(0, blockchain.height());
         Ok(())
     }
 
-    #[test]
-    fn test_add_trans_listener() -> Result<(), String> {
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_revoked_key() -> Result<(), String> {
         let keypair = identity::ed25519::Keypair::generate();
-        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
-        let mut chain = Blockchain::new(&keypair);
+        let revoked = identity::ed25519::Keypair::generate();
+        let revoked_id = Address::from(identity::PublicKey::Ed25519(revoked.public()));
+        let mut blockchain = Blockchain::new(&keypair);
+        let signed_at = |timestamp, nonce| {
+            Transaction::new_with(
+                TransactionType::Create,
+                revoked_id,
+                b"Hello Revocation".to_vec(),
+                timestamp,
+                nonce,
+                &revoked,
+            )
+            .map_err(|e| e.to_string())
+        };
 
-        let transaction = Transaction::new(
-            TransactionType::Create,
-            local_id,
-            "some transaction".as_bytes().to_vec(),
+        let revocation = Transaction::new_with(
+            TransactionType::RevokeKey,
+            revoked_id,
+            revoked_id.to_bytes(),
+            1656633600,
+            0,
+            &revoked,
+        )
+        .map_err(|e| e.to_string())?;
+        let timestamp = blockchain.next_block_timestamp();
+        let revoke = Block::new_with(
+            blockchain.genesis_hash(),
+            1,
+            vec![revocation],
+            &keypair,
+            timestamp,
+            0,
+        )
+        .unwrap();
+        let before = Block::new_with(
+            revoke.header.hash(),
+            2,
+            vec![signed_at(1656633599, 1)?],
             &keypair,
+            timestamp + 1,
+            0,
+        )
+        .unwrap();
+        blockchain
+            .apply_blocks(vec![revoke, before.clone()])
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(Some(&1656633600), blockchain.revocations().get(&revoked_id));
+
+        let after = signed_at(1656633601, 2)?;
+        let timestamp = blockchain.next_block_timestamp();
+        assert_eq!(
+            Err(BlockchainError::Transaction(TransactionError::RevokedKey {
+                submitter: revoked_id,
+                revoked_at: 1656633600
+            })),
+            blockchain
+                .apply_blocks(vec![Block::new_with(
+                    before.header.hash(),
+                    3,
+                    vec![after.clone()],
+                    &keypair,
+                    timestamp,
+                    0
+                )
+                .unwrap()])
+                .await
         );
-        let called = Rc::new(Cell::new(false));
-        chain
-            .submit_transaction(transaction.clone(), {
-                let called = called.clone();
-                let transaction = transaction.clone();
-                move |t: Transaction| {
-                    assert_eq!(transaction, t);
-                    called.set(true)
+        assert_eq!(2, blockchain.height());
+
+        let rejected = Arc::new(AtomicBool::new(false));
+        blockchain
+            .submit_transaction(after, {
+                let rejected = rejected.clone();
+                move |_: Transaction, outcome: TransactionOutcome| {
+                    rejected.store(
+                        matches!(outcome, TransactionOutcome::Rejected { .. }),
+                        Ordering::SeqCst,
+                    );
                 }
             })
-            .notify_transaction_settled(transaction);
-        assert!(called.get());
+            .map_err(|e| e.to_string())?;
+        assert!(rejected.load(Ordering::SeqCst));
+        assert_eq!(0, blockchain.pending_count());
         Ok(())
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_add_payload_listener() -> Result<(), String> {
+    async fn test_capacity_figures() -> Result<(), String> {
+        let storage_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+            .storage_dir(storage_dir.path())
+            .build()
+            .map_err(|e| e.to_string())?;
+        for i in 0..3 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        // the genesis transaction plus one transaction per added block
+        assert_eq!(4, blockchain.transaction_count());
+        let expected_bytes: usize = blockchain
+            .blocks_in_range(0, blockchain.height())
+            .iter()
+            .map(|block| block.byte_size())
+            .sum();
+        assert_eq!(Ok(expected_bytes as u64), blockchain.total_bytes_on_disk());
+        assert_eq!(Ok(0), Blockchain::new(&ed25519_key).total_bytes_on_disk());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_apply_blocks_rejects_swapped_transactions() -> Result<(), String> {
         let keypair = identity::ed25519::Keypair::generate();
-        let block = Block::new(
-            HashDigest::new(b"Hello World!"),
-            1u128,
-            Vec::new(),
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blockchain = Blockchain::new(&keypair);
+        let mut block = Block::new_with(
+            blockchain.genesis_hash(),
+            1,
+            vec![],
+            &keypair,
+            blockchain.next_block_timestamp(),
+            0,
+        )
+        .unwrap();
+        block.transactions = vec![Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"Hello Smuggled".to_vec(),
             &keypair,
+        )
+        .map_err(|e| e.to_string())?];
+
+        assert_eq!(
+            Err(BlockchainError::Block(BlockError::TransactionRootMismatch)),
+            blockchain.apply_blocks(vec![block]).await
         );
+        assert_eq!(0, blockchain.height());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_transactions_by_submitter() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let other = identity::ed25519::Keypair::generate();
+        let other_id = Address::from(identity::PublicKey::Ed25519(other.public()));
         let mut blockchain = Blockchain::new(&keypair);
-        let called = move |b: &Vec<u8>| println!("data is {:?}", b);
 
+        let mut blocks: Vec<Block> = vec![];
+        let mut expected: HashMap<Address, Vec<Transaction>> = HashMap::new();
+        for ordinal in 1..=4 {
+            let transactions = [(local_id, &keypair), (other_id, &other)]
+                .iter()
+                .map(|(submitter, signer)| {
+                    Transaction::new(
+                        TransactionType::Create,
+                        *submitter,
+                        format!("{:?} {}", submitter, ordinal).into_bytes(),
+                        signer,
+                    )
+                    .map_err(|e| e.to_string())
+                })
+                .collect::<Result<Vec<Transaction>, String>>()?;
+            for trans in transactions.iter() {
+                expected
+                    .entry(trans.submitter())
+                    .or_default()
+                    .push(trans.clone());
+            }
+            let parent_hash = blocks
+                .last()
+                .map(|block| block.header.hash())
+                .unwrap_or_else(|| blockchain.genesis_hash());
+            blocks.push(
+                Block::new_with(
+                    parent_hash,
+                    ordinal,
+                    transactions,
+                    &keypair,
+                    blockchain.next_block_timestamp() + ordinal as u64,
+                    0,
+                )
+                .unwrap(),
+            );
+        }
         blockchain
-            .add_payload_listener(called)
-            .commit_block(block)
-            .await;
+            .apply_blocks(blocks)
+            .await
+            .map_err(|e| e.to_string())?;
 
-        assert_eq!(1, blockchain.payload_observers.len());
+        for submitter in [local_id, other_id] {
+            assert_eq!(
+                expected[&submitter].iter().collect::<Vec<&Transaction>>(),
+                blockchain.transactions_by_submitter(&submitter)
+            );
+        }
+        let stranger = Address::from(identity::Keypair::generate_ed25519().public());
+        assert!(blockchain.transactions_by_submitter(&stranger).is_empty());
+
+        // the transactions of blocks 1 and 2 go with them
+        blockchain.prune(2);
+        assert_eq!(
+            expected[&other_id][2..]
+                .iter()
+                .collect::<Vec<&Transaction>>(),
+            blockchain.transactions_by_submitter(&other_id)
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_prune() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let storage_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+            .storage_dir(storage_dir.path())
+            .build()
+            .map_err(|e| e.to_string())?;
+        for i in 0..5 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        let blocks = blockchain.chain.blocks();
+
+        blockchain.prune(3);
+
+        assert_eq!(4, blockchain.chain.len());
+        assert_eq!(5, blockchain.height());
+        assert!(blockchain.get_block_by_ordinal(0).is_some());
+        assert_eq!(None, blockchain.get_block_by_ordinal(2));
+        for ordinal in 3..=5 {
+            assert!(blockchain.get_block_by_ordinal(ordinal).is_some());
+        }
+        assert!(blockchain.chain.verify_linkage());
+        for block in blocks.iter() {
+            let pruned = (1..=2).contains(&block.header.ordinal);
+            assert_eq!(
+                !pruned,
+                storage::build_path_for_block(storage_dir.path(), block).exists()
+            );
+        }
+
+        // the tip is never pruned, the next block still follows it
+        blockchain.prune(0);
+        assert_eq!(5, blockchain.height());
+        assert_eq!(2, blockchain.chain.len());
+        blockchain
+            .add_block(b"after pruning".to_vec(), keypair.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(6, blockchain.height());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_prune_keeps_state() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let other = identity::ed25519::Keypair::generate();
+        let other_id = Address::from(identity::PublicKey::Ed25519(other.public()));
+        let mut blockchain = Blockchain::new(&keypair);
+        let add_authority = Transaction::new(
+            TransactionType::AddAuthority,
+            local_id,
+            other_id.to_bytes(),
+            &keypair,
+        )
+        .map_err(|e| e.to_string())?;
+        blockchain
+            .submit_transaction(add_authority, |_, _| {})
+            .map_err(|e| e.to_string())?;
+        blockchain
+            .produce_block(&keypair)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("no block produced")?;
+        for i in 0..4 {
+            let committer = match blockchain.expected_committer(blockchain.height() + 1) {
+                Some(committer) if committer == other_id => other.clone(),
+                _ => keypair.clone(),
+            };
+            blockchain
+                .add_block(
+                    format!("payload {}", i).into_bytes(),
+                    identity::Keypair::Ed25519(committer),
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        // the block adding the authority is pruned, its effect is kept
+        blockchain.prune(2);
+        assert_eq!(None, blockchain.get_block_by_ordinal(1));
+        blockchain.rollback_to(4).map_err(|e| e.to_string())?;
+        assert_eq!(
+            &HashSet::from([local_id, other_id]),
+            blockchain.authorities()
+        );
+        assert_eq!(
+            Err(BlockchainError::UnknownBlock(0)),
+            blockchain.rollback_to(0)
+        );
+        Ok(())
+    }
+
+    #[derive(Clone, Default)]
+    struct SpanRecorder(Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanRecorder {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _id: &tracing::span::Id,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(attrs.metadata().name().to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tracing_spans() -> Result<(), String> {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let recorder = SpanRecorder::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = Blockchain::new(&ed25519_key);
+        blockchain
+            .add_block(b"Hello Tracing".to_vec(), keypair)
+            .await
+            .map_err(|e| e.to_string())?;
+        let block = blockchain.chain.last_block().unwrap();
+        assert_eq!(Ok(()), block.verify());
+        assert!(blockchain.chain.verify_linkage());
+        blockchain.notify_transaction_settled(block.transactions[0].clone());
+
+        assert_eq!(
+            vec![
+                "add_block",
+                "verify_block",
+                "verify_linkage",
+                "settle_transaction"
+            ],
+            *recorder.0.lock().unwrap()
+        );
+        Ok(())
+    }
+
+    #[derive(Clone, Default)]
+    struct EventRecorder(Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl tracing::field::Visit for EventRecorder {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn Debug) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for EventRecorder {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            event.record(&mut self.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_block_is_logged() -> Result<(), String> {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let recorder = EventRecorder::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut blockchain = Blockchain::new(&keypair);
+        let unlinked = Block::new(HashDigest::new(b"unrelated"), 1, vec![], &keypair).unwrap();
+        assert_eq!(
+            Err(BlockchainError::ParentHashMismatch(1)),
+            blockchain.append_block(unlinked.clone()).await
+        );
+
+        let fields = recorder.0.lock().unwrap();
+        assert!(fields.contains(&"message=Blockchain: rejected block".to_string()));
+        assert!(fields.contains(&format!("hash={}", unlinked.header.hash())));
+        assert!(fields.contains(&"ordinal=1".to_string()));
+        assert!(fields.contains(&format!(
+            "reason={}",
+            BlockchainError::ParentHashMismatch(1)
+        )));
         Ok(())
     }
 
@@ -253,16 +6121,20 @@ mod tests {
             local_id,
             data.as_bytes().to_vec(),
             &keypair,
-        );
+        )
+        .unwrap();
         transactions.push(transaction);
         assert_eq!(1, blockchain.chain.len());
         blockchain
-            .commit_block(Block::new(
-                blockchain.chain.blocks()[0].header.hash(),
-                blockchain.chain.blocks()[0].header.ordinal + 1,
-                transactions,
-                &keypair,
-            ))
+            .commit_block(
+                Block::new(
+                    blockchain.chain.blocks()[0].header.hash(),
+                    blockchain.chain.blocks()[0].header.ordinal + 1,
+                    transactions,
+                    &keypair,
+                )
+                .unwrap(),
+            )
             .await;
         assert_ne!(None, blockchain.chain.last_block());
         Ok(())
@@ -15,3 +15,4 @@
 */
 
 pub mod hash_algorithm;
+pub mod merkle;
@@ -15,31 +15,282 @@
 */
 
 use codec::{Decode, Encode};
-use multihash::{Code, Multihash, MultihashDigest};
-use serde::{Deserialize, Serialize};
+use multihash::{Code, Multihash, MultihashDigest, MultihashGeneric};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
 
-#[derive(
-    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Decode, Encode, PartialOrd,
-)]
+/// Define the supported hash algorithms, a digest carries its algorithm as its multihash code
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DigestAlgorithm {
+    #[default]
+    Keccak256,
+    Sha2_256,
+    Sha3_256,
+    Blake2b256,
+}
+
+impl DigestAlgorithm {
+    fn code(&self) -> Code {
+        match self {
+            DigestAlgorithm::Keccak256 => Code::Keccak256,
+            DigestAlgorithm::Sha2_256 => Code::Sha2_256,
+            DigestAlgorithm::Sha3_256 => Code::Sha3_256,
+            DigestAlgorithm::Blake2b256 => Code::Blake2b256,
+        }
+    }
+}
+
+impl TryFrom<u64> for DigestAlgorithm {
+    type Error = multihash::Error;
+
+    fn try_from(code: u64) -> Result<Self, Self::Error> {
+        match Code::try_from(code)? {
+            Code::Keccak256 => Ok(DigestAlgorithm::Keccak256),
+            Code::Sha2_256 => Ok(DigestAlgorithm::Sha2_256),
+            Code::Sha3_256 => Ok(DigestAlgorithm::Sha3_256),
+            Code::Blake2b256 => Ok(DigestAlgorithm::Blake2b256),
+            _ => Err(multihash::Error::UnsupportedCode(code)),
+        }
+    }
+}
+
+// the multihash of a 256-bit digest, the digests of every supported algorithm fit in it
+type Multihash256 = MultihashGeneric<32>;
+
+/// A digest serializes as the hex string of its multihash in human readable formats like JSON,
+/// and keeps its structured multihash form in binary formats, the bincode bytes of which are
+/// hashed. The structured form is still read from legacy JSON. Only 256-bit digests of a
+/// supported algorithm are ever deserialized or decoded.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Encode)]
 pub struct HashDigest {
-    multihash: Multihash,
+    multihash: Multihash256,
 }
 
 impl HashDigest {
+    /// Hash `msg` with the default algorithm
     pub fn new(msg: &[u8]) -> Self {
+        Self::with_algorithm(DigestAlgorithm::default(), msg)
+    }
+
+    pub fn with_algorithm(algorithm: DigestAlgorithm, msg: &[u8]) -> Self {
+        let multihash = algorithm.code().digest(msg);
         Self {
-            multihash: Code::Keccak256.digest(msg),
+            multihash: Multihash256::wrap(multihash.code(), multihash.digest())
+                .expect("a 256-bit algorithm to produce a 32 bytes digest"),
         }
     }
 
+    /// The all-zero digest of the default algorithm, the parent hash of a genesis block
+    pub fn zero() -> Self {
+        Self {
+            multihash: Multihash256::wrap(u64::from(DigestAlgorithm::default().code()), &[0; 32])
+                .expect("a 256-bit digest to fit in a multihash"),
+        }
+    }
+
+    /// The algorithm the digest was computed with, read from its multihash code
+    pub fn algorithm(&self) -> Result<DigestAlgorithm, multihash::Error> {
+        DigestAlgorithm::try_from(self.multihash.code())
+    }
+
+    /// Check the digest was computed from `data`, hashing it again with the algorithm of the
+    /// digest. A digest of an unsupported algorithm never matches.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        match self.algorithm() {
+            Ok(algorithm) => Self::with_algorithm(algorithm, data) == *self,
+            Err(_) => false,
+        }
+    }
+
+    // Wrap `multihash` once checked it is a 256-bit digest of a supported algorithm, which
+    // is what hashing, e.g. of the Merkle root of transactions, expects of every digest
+    fn from_multihash(multihash: Multihash256) -> Result<Self, multihash::Error> {
+        DigestAlgorithm::try_from(multihash.code())?;
+        if multihash.size() != 32 {
+            return Err(multihash::Error::InvalidSize(u64::from(multihash.size())));
+        }
+        Ok(Self { multihash })
+    }
+
+    /// The hex encoding of the multihash, prefixed by its algorithm code and digest size
+    pub fn as_string(&self) -> String {
+        hex::encode(self.multihash.to_bytes())
+    }
+
+    /// The 32 bytes of the digest
     pub fn to_slice(&self) -> [u8; 32] {
-        self.multihash
-            .digest()
-            .try_into()
-            .expect("a valid Keccak256 to be 32 bytes")
+        let mut bytes = [0; 32];
+        let digest = self.multihash.digest();
+        bytes[..digest.len()].copy_from_slice(digest);
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum ParseHashDigestError {
+    #[error("The digest is not hex encoded: {0}")]
+    Hex(#[from] hex::FromHexError),
+    #[error("The digest is not a 256-bit multihash: {0}")]
+    Multihash(String),
+}
+
+/// Parse the hex string of a multihash, as printed by `Display`
+impl FromStr for HashDigest {
+    type Err = ParseHashDigestError;
+
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(hex)?;
+        Multihash256::from_bytes(&bytes)
+            .and_then(HashDigest::from_multihash)
+            .map_err(|e| ParseHashDigestError::Multihash(e.to_string()))
+    }
+}
+
+impl Decode for HashDigest {
+    fn decode<I: codec::Input>(input: &mut I) -> Result<Self, codec::Error> {
+        HashDigest::from_multihash(Multihash256::decode(input)?)
+            .map_err(|_| "not a 256-bit digest of a supported algorithm".into())
+    }
+}
+
+// the structured form digests were serialized with before the hex string form, its digest
+// padded with zeros to the 64 bytes of the default multihash. Binary formats keep it, the
+// hashes of headers and transactions being computed over their bincode bytes, zeros included.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "HashDigest")]
+struct LegacyHashDigest {
+    multihash: Multihash,
+}
+
+impl From<&HashDigest> for LegacyHashDigest {
+    fn from(hash: &HashDigest) -> Self {
+        Self {
+            multihash: Multihash::wrap(hash.multihash.code(), hash.multihash.digest())
+                .expect("a 256-bit digest to fit in a 512-bit multihash"),
+        }
+    }
+}
+
+impl TryFrom<LegacyHashDigest> for HashDigest {
+    type Error = multihash::Error;
+
+    fn try_from(legacy: LegacyHashDigest) -> Result<Self, Self::Error> {
+        HashDigest::from_multihash(Multihash256::wrap(
+            legacy.multihash.code(),
+            legacy.multihash.digest(),
+        )?)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HumanReadableHashDigest {
+    Hex(String),
+    Legacy(LegacyHashDigest),
+}
+
+impl Serialize for HashDigest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.as_string())
+        } else {
+            LegacyHashDigest::from(self).serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for HashDigest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            let legacy = LegacyHashDigest::deserialize(deserializer)?;
+            return HashDigest::try_from(legacy).map_err(de::Error::custom);
+        }
+        match HumanReadableHashDigest::deserialize(deserializer)? {
+            HumanReadableHashDigest::Hex(hex) => hex.parse().map_err(de::Error::custom),
+            HumanReadableHashDigest::Legacy(legacy) => {
+                HashDigest::try_from(legacy).map_err(de::Error::custom)
+            }
+        }
+    }
+}
+
+/// Digests are ordered by algorithm code, then by digest bytes
+impl Ord for HashDigest {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.multihash.code(), self.multihash.digest())
+            .cmp(&(other.multihash.code(), other.multihash.digest()))
+    }
+}
+
+impl PartialOrd for HashDigest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for HashDigest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_string())
+    }
+}
+
+impl Debug for HashDigest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "HashDigest({})", self.as_string())
+    }
+}
+
+/// Serialize `value` as compact JSON with the keys of every object sorted, so logically equal
+/// values produce the same bytes whatever the order and spacing of the JSON they were parsed
+/// from. The keys are sorted here rather than relying on the order of `serde_json::Map`, which
+/// keeps the insertion order once the `preserve_order` feature is enabled anywhere in the build.
+pub fn canonical_json(value: &Value) -> Vec<u8> {
+    let mut bytes = vec![];
+    write_canonical_json(value, &mut bytes);
+    bytes
+}
+
+fn write_canonical_json(value: &Value, bytes: &mut Vec<u8>) {
+    match value {
+        Value::Array(items) => {
+            bytes.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    bytes.push(b',');
+                }
+                write_canonical_json(item, bytes);
+            }
+            bytes.push(b']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|(left, _), (right, _)| left.cmp(right));
+            bytes.push(b'{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    bytes.push(b',');
+                }
+                bytes.extend(serde_json::to_vec(key).expect("a string to serialize as JSON"));
+                bytes.push(b':');
+                write_canonical_json(item, bytes);
+            }
+            bytes.push(b'}');
+        }
+        scalar => bytes.extend(serde_json::to_vec(scalar).expect("a scalar to serialize as JSON")),
     }
 }
 
+/// Hash a JSON value over its canonical form, so every node computes the same hash for the
+/// same logical value. Transactions hash the bytes of their payload instead, see
+/// `TransactionBuilder::json_payload` for JSON payloads.
+pub fn hash_value(value: &Value) -> HashDigest {
+    HashDigest::new(&canonical_json(value))
+}
+
 impl aleph_bft::Hasher for HashDigest {
     type Hash = [u8; 32];
 
@@ -52,6 +303,7 @@ impl aleph_bft::Hasher for HashDigest {
 mod tests {
     use super::*;
     use aleph_bft::Hasher;
+    use serde_json::json;
 
     #[test]
     fn test_hash_digest() {
@@ -67,4 +319,160 @@ mod tests {
         assert_eq!(hash.to_slice(), expected_digest);
         assert_eq!(HashDigest::hash(message), expected_digest);
     }
+
+    #[test]
+    fn test_hash_value_ignores_key_order() {
+        let first: Value = serde_json::from_str(
+            r#"{"package": {"version": "1.0", "name": "alpine"}, "tags": ["latest", "3.16"]}"#,
+        )
+        .unwrap();
+        let second: Value = serde_json::from_str(
+            r#"{ "tags": ["latest", "3.16"], "package": { "name": "alpine", "version": "1.0" } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            br#"{"package":{"name":"alpine","version":"1.0"},"tags":["latest","3.16"]}"#.to_vec(),
+            canonical_json(&first)
+        );
+        assert_eq!(canonical_json(&first), canonical_json(&second));
+        assert_eq!(hash_value(&first), hash_value(&second));
+        assert_ne!(hash_value(&first), hash_value(&json!({"tags": []})));
+    }
+
+    #[test]
+    fn test_hash_digest_with_algorithm() {
+        let message = b"hello world";
+
+        let sha2 = HashDigest::with_algorithm(DigestAlgorithm::Sha2_256, message);
+        let sha3 = HashDigest::with_algorithm(DigestAlgorithm::Sha3_256, message);
+
+        assert_ne!(sha2, sha3);
+        assert_ne!(sha2.to_slice(), sha3.to_slice());
+        assert_eq!(DigestAlgorithm::Sha2_256, sha2.algorithm().unwrap());
+        assert_eq!(DigestAlgorithm::Sha3_256, sha3.algorithm().unwrap());
+        assert_eq!(0x12, sha2.multihash.code());
+        assert_eq!(0x16, sha3.multihash.code());
+        assert_eq!(
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9",
+            hex::encode(sha2.to_slice())
+        );
+        assert_eq!(
+            DigestAlgorithm::Keccak256,
+            HashDigest::new(message).algorithm().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_hash_digest_matches() {
+        let keccak = HashDigest::new(b"hello world");
+        let sha2 = HashDigest::with_algorithm(DigestAlgorithm::Sha2_256, b"hello world");
+
+        assert!(keccak.matches(b"hello world"));
+        assert!(sha2.matches(b"hello world"));
+        assert!(!keccak.matches(b"hello world!"));
+        assert!(!sha2.matches(b""));
+    }
+
+    #[test]
+    fn test_hash_digest_zero() {
+        assert_eq!([0; 32], HashDigest::zero().to_slice());
+        assert_eq!(
+            DigestAlgorithm::Keccak256,
+            HashDigest::zero().algorithm().unwrap()
+        );
+        assert_ne!(HashDigest::new(b""), HashDigest::zero());
+    }
+
+    #[test]
+    fn test_hash_digest_display() {
+        let hash = HashDigest::new(b"hello world");
+        let expected = "1b2047173285a8d7341e5e972fc677286384f802f8ef42a5ec5f03bbfa254cb01fad";
+
+        assert_eq!(expected, hash.to_string());
+        assert_eq!(expected, hash.as_string());
+        assert_eq!(Ok(hash), expected.parse::<HashDigest>());
+        assert!(matches!(
+            "not hex".parse::<HashDigest>(),
+            Err(ParseHashDigestError::Hex(_))
+        ));
+        assert!(matches!(
+            "1b20".parse::<HashDigest>(),
+            Err(ParseHashDigestError::Multihash(_))
+        ));
+        // a digest too short to hash, or of an unsupported algorithm, is never parsed
+        assert!(matches!(
+            "1b04deadbeef".parse::<HashDigest>(),
+            Err(ParseHashDigestError::Multihash(_))
+        ));
+        assert!(matches!(
+            format!("0020{}", "00".repeat(32)).parse::<HashDigest>(),
+            Err(ParseHashDigestError::Multihash(_))
+        ));
+        assert_eq!(format!("HashDigest({})", expected), format!("{:?}", hash));
+        assert_ne!(
+            format!("HashDigest {{ multihash: {:?} }}", hash.multihash),
+            format!("{:?}", hash)
+        );
+    }
+
+    #[test]
+    fn test_hash_digest_serde() {
+        let hash = HashDigest::new(b"hello world");
+
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(format!("\"{}\"", hash.as_string()), json);
+        assert_eq!(hash, serde_json::from_str::<HashDigest>(&json).unwrap());
+        assert!(serde_json::from_str::<HashDigest>("\"not hex\"").is_err());
+
+        let bytes = bincode::serialize(&hash).unwrap();
+        assert_eq!(
+            bincode::serialize(&LegacyHashDigest::from(&hash)).unwrap(),
+            bytes
+        );
+        assert_eq!(hash, bincode::deserialize::<HashDigest>(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_hash_digest_trimmed() {
+        let hash = HashDigest::new(b"hello world");
+        assert_eq!(32, hash.multihash.digest().len());
+        assert!(std::mem::size_of::<HashDigest>() < std::mem::size_of::<Multihash>());
+
+        // the algorithm code and digest size prefix the 32 bytes of the digest, no padding
+        let json = serde_json::to_value(&hash).unwrap();
+        let bytes = hex::decode(json.as_str().unwrap()).unwrap();
+        assert_eq!(34, bytes.len());
+        assert_eq!(hash.to_slice(), bytes[2..]);
+
+        let encoded = hash.encode();
+        assert!(encoded.len() <= 8 + 1 + 32);
+        assert_eq!(hash, HashDigest::decode(&mut &encoded[..]).unwrap());
+        let short = Multihash256::wrap(0x1b, &[1; 4]).unwrap().encode();
+        assert!(HashDigest::decode(&mut &short[..]).is_err());
+    }
+
+    #[test]
+    fn test_hash_digest_legacy_json() {
+        let hash = HashDigest::new(b"hello world");
+        let legacy = serde_json::to_string(&LegacyHashDigest::from(&hash)).unwrap();
+        assert!(legacy.contains("\"digest\":["));
+
+        let read = serde_json::from_str::<HashDigest>(&legacy).unwrap();
+        assert_eq!(hash, read);
+        assert!(serde_json::to_string(&read).unwrap().len() < legacy.len());
+
+        let too_long = Multihash::wrap(0x1b, &[1; 64]).unwrap();
+        let too_long = serde_json::to_string(&LegacyHashDigest {
+            multihash: too_long,
+        })
+        .unwrap();
+        assert!(serde_json::from_str::<HashDigest>(&too_long).is_err());
+
+        let short = Multihash::wrap(0x1b, &[1; 4]).unwrap();
+        let short = LegacyHashDigest { multihash: short };
+        assert!(
+            serde_json::from_str::<HashDigest>(&serde_json::to_string(&short).unwrap()).is_err()
+        );
+        assert!(bincode::deserialize::<HashDigest>(&bincode::serialize(&short).unwrap()).is_err());
+    }
 }
@@ -0,0 +1,134 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use serde::{Deserialize, Serialize};
+
+use super::hash_algorithm::HashDigest;
+
+// Prefix of the inner nodes, so a pair of hashes can never be passed off as a leaf
+const NODE_PREFIX: u8 = 1;
+
+/// The sibling of a node on the path from a leaf to the Merkle root
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MerkleStep {
+    Left(HashDigest),
+    Right(HashDigest),
+}
+
+/// The sibling-hash path proving a leaf is part of a Merkle tree, ordered from the leaf up
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct MerkleProof {
+    pub path: Vec<MerkleStep>,
+}
+
+fn hash_node(left: &HashDigest, right: &HashDigest) -> HashDigest {
+    let mut bytes = vec![NODE_PREFIX];
+    bytes.extend(left.to_slice());
+    bytes.extend(right.to_slice());
+    HashDigest::new(&bytes)
+}
+
+// Hash the nodes pairwise, a node without a sibling is promoted to the next level as is
+fn next_level(level: &[HashDigest]) -> Vec<HashDigest> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_node(left, right),
+            [single] => *single,
+            _ => unreachable!("chunks of at most two nodes"),
+        })
+        .collect()
+}
+
+/// The Merkle root of `leaves`, the root of no leaves being the hash of the empty input
+pub fn merkle_root(leaves: &[HashDigest]) -> HashDigest {
+    if leaves.is_empty() {
+        return HashDigest::new(b"");
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+/// Build the proof that the leaf at `index` is part of the Merkle tree of `leaves`
+pub fn merkle_proof(leaves: &[HashDigest], index: usize) -> Option<MerkleProof> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let mut proof = MerkleProof::default();
+    let mut level = leaves.to_vec();
+    let mut index = index;
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        if sibling < level.len() {
+            proof.path.push(if sibling < index {
+                MerkleStep::Left(level[sibling])
+            } else {
+                MerkleStep::Right(level[sibling])
+            });
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    Some(proof)
+}
+
+/// Check that `proof` leads from `leaf` to `root`
+pub fn verify_inclusion(leaf: &HashDigest, proof: &MerkleProof, root: &HashDigest) -> bool {
+    let computed = proof.path.iter().fold(*leaf, |node, step| match step {
+        MerkleStep::Left(sibling) => hash_node(sibling, &node),
+        MerkleStep::Right(sibling) => hash_node(&node, sibling),
+    });
+    computed == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: usize) -> Vec<HashDigest> {
+        (0..count)
+            .map(|i| HashDigest::new(format!("leaf {}", i).as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_merkle_root() {
+        let leaves = leaves(3);
+
+        assert_eq!(HashDigest::new(b""), merkle_root(&[]));
+        assert_eq!(leaves[0], merkle_root(&leaves[..1]));
+        assert_eq!(
+            hash_node(&hash_node(&leaves[0], &leaves[1]), &leaves[2]),
+            merkle_root(&leaves)
+        );
+    }
+
+    #[test]
+    fn test_merkle_proof_for_every_leaf() {
+        for count in 1..10 {
+            let leaves = leaves(count);
+            let root = merkle_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = merkle_proof(&leaves, index).unwrap();
+                assert!(verify_inclusion(leaf, &proof, &root));
+            }
+            assert_eq!(None, merkle_proof(&leaves, count));
+        }
+    }
+}
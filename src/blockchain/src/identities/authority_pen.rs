@@ -36,7 +36,8 @@ impl AuthorityPen {
         self.keypair.public()
     }
     pub fn sign(&self, msg: &[u8]) -> Signature {
-        Signature::new(msg, &self.keypair)
+        // signing with a keypair at hand never fails
+        Signature::new(msg, &self.keypair).expect("a keypair to always sign")
     }
 }
 
@@ -18,9 +18,12 @@ pub mod args;
 pub mod blockchain;
 pub mod crypto;
 pub mod identities;
+pub mod metrics;
 pub mod network;
 pub mod providers;
+pub mod rpc;
 pub mod signature;
+pub mod storage;
 pub mod structures;
 
 use crate::network::NetworkData;
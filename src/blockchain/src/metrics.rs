@@ -0,0 +1,205 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use log::warn;
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, IntGauge, Registry, TextEncoder};
+use std::sync::{Arc, Mutex};
+use warp::Filter;
+
+use crate::blockchain::SharedBlockchain;
+use crate::structures::block::Block;
+
+/// Buckets, in seconds, of the time between two consecutive blocks
+const BLOCK_INTERVAL_BUCKETS: [f64; 8] = [1.0, 5.0, 15.0, 30.0, 60.0, 300.0, 900.0, 3600.0];
+
+/// The Prometheus metrics of a blockchain, exposed in their own registry
+pub struct BlockchainMetrics {
+    registry: Registry,
+    pub chain_height: IntGauge,
+    pub mempool_pending: IntGauge,
+    pub blocks_appended: IntCounter,
+    pub block_interval: Histogram,
+    // timestamp of the last observed block, to measure the time between blocks
+    last_timestamp: Mutex<Option<u64>>,
+}
+
+impl BlockchainMetrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+        let chain_height = IntGauge::new("pyrsia_chain_height", "Ordinal of the last block")?;
+        let mempool_pending = IntGauge::new(
+            "pyrsia_mempool_pending",
+            "Number of submitted transactions not settled yet",
+        )?;
+        let blocks_appended = IntCounter::new(
+            "pyrsia_blocks_appended_total",
+            "Number of blocks appended to the chain",
+        )?;
+        let block_interval = Histogram::with_opts(
+            HistogramOpts::new(
+                "pyrsia_block_interval_seconds",
+                "Time between the timestamps of consecutive blocks",
+            )
+            .buckets(BLOCK_INTERVAL_BUCKETS.to_vec()),
+        )?;
+
+        registry.register(Box::new(chain_height.clone()))?;
+        registry.register(Box::new(mempool_pending.clone()))?;
+        registry.register(Box::new(blocks_appended.clone()))?;
+        registry.register(Box::new(block_interval.clone()))?;
+
+        Ok(Self {
+            registry,
+            chain_height,
+            mempool_pending,
+            blocks_appended,
+            block_interval,
+            last_timestamp: Mutex::new(None),
+        })
+    }
+
+    /// Update the metrics for a block appended to the chain
+    pub fn observe_block(&self, block: &Block) {
+        self.chain_height
+            .set(i64::try_from(block.header.ordinal).unwrap_or(i64::MAX));
+        self.blocks_appended.inc();
+
+        let mut last_timestamp = self
+            .last_timestamp
+            .lock()
+            .expect("metrics lock not to be poisoned");
+        if let Some(last_timestamp) = *last_timestamp {
+            self.block_interval
+                .observe(block.header.timestamp.saturating_sub(last_timestamp) as f64);
+        }
+        *last_timestamp = Some(block.header.timestamp);
+    }
+
+    /// Render the metrics in the Prometheus text format
+    pub fn gather(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        String::from_utf8(buffer).map_err(|e| prometheus::Error::Msg(e.to_string()))
+    }
+}
+
+/// Create the metrics of `blockchain` and keep them up to date through a block listener
+pub async fn make_blockchain_metrics(
+    blockchain: &SharedBlockchain,
+) -> Result<Arc<BlockchainMetrics>, prometheus::Error> {
+    let metrics = Arc::new(BlockchainMetrics::new()?);
+    let mut blockchain = blockchain.lock().await;
+    if let Some(tip) = blockchain.get_block_by_ordinal(blockchain.height()) {
+        metrics
+            .chain_height
+            .set(i64::try_from(tip.header.ordinal).unwrap_or(i64::MAX));
+        *metrics
+            .last_timestamp
+            .lock()
+            .expect("metrics lock not to be poisoned") = Some(tip.header.timestamp);
+    }
+    let listener_metrics = metrics.clone();
+    blockchain.add_block_listener(move |block: &Block| listener_metrics.observe_block(block));
+    Ok(metrics)
+}
+
+/// Serve the metrics on GET `/metrics`, refreshing the mempool depth on every scrape
+pub fn make_metrics_routes(
+    blockchain: SharedBlockchain,
+    metrics: Arc<BlockchainMetrics>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("metrics")
+        .and(warp::get())
+        .and(warp::any().map(move || (blockchain.clone(), metrics.clone())))
+        .and_then(
+            |(blockchain, metrics): (SharedBlockchain, Arc<BlockchainMetrics>)| async move {
+                let pending = blockchain.lock().await.pending_count();
+                metrics
+                    .mempool_pending
+                    .set(i64::try_from(pending).unwrap_or(i64::MAX));
+                match metrics.gather() {
+                    Ok(text) => Ok::<_, warp::Rejection>(warp::reply::with_status(
+                        text,
+                        warp::http::StatusCode::OK,
+                    )),
+                    Err(e) => {
+                        warn!("Failed to gather the blockchain metrics: {}", e);
+                        Ok(warp::reply::with_status(
+                            e.to_string(),
+                            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                        ))
+                    }
+                }
+            },
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::{Blockchain, TransactionOutcome};
+    use libp2p::identity;
+    use tokio::sync::Mutex as AsyncMutex;
+
+    #[tokio::test]
+    async fn test_blockchain_metrics() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_keypair = match keypair.clone() {
+            identity::Keypair::Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let blockchain: SharedBlockchain =
+            Arc::new(AsyncMutex::new(Blockchain::new(&ed25519_keypair)));
+        let metrics = make_blockchain_metrics(&blockchain)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(0, metrics.chain_height.get());
+
+        for i in 0..2 {
+            blockchain
+                .lock()
+                .await
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        assert_eq!(2, metrics.chain_height.get());
+        assert_eq!(2, metrics.blocks_appended.get());
+        assert_eq!(2, metrics.block_interval.get_sample_count());
+
+        blockchain
+            .lock()
+            .await
+            .submit_transactions(
+                vec!["pending"],
+                &ed25519_keypair,
+                |_, _: TransactionOutcome| {},
+            )
+            .map_err(|e| e.to_string())?;
+        let response = warp::test::request()
+            .method("GET")
+            .path("/metrics")
+            .reply(&make_metrics_routes(blockchain, metrics))
+            .await;
+        assert_eq!(200, response.status());
+        let body = String::from_utf8(response.body().to_vec()).unwrap();
+        assert!(body.contains("pyrsia_chain_height 2"));
+        assert!(body.contains("pyrsia_blocks_appended_total 2"));
+        assert!(body.contains("pyrsia_mempool_pending 1"));
+        Ok(())
+    }
+}
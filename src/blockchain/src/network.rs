@@ -14,6 +14,8 @@
    limitations under the License.
 */
 
+pub mod sync;
+
 use super::crypto::hash_algorithm::HashDigest;
 use super::identities::verify_key::VerifyKey;
 use super::signature::{MultiSignature, Signature};
@@ -32,7 +34,7 @@ use futures::{
 use libp2p::core::identity::ed25519::PublicKey;
 use libp2p::tcp::{self, GenTcpConfig};
 use libp2p::{
-    core::upgrade,
+    core::{muxing::StreamMuxerBox, transport::Boxed, upgrade},
     identity,
     mdns::{Mdns, MdnsEvent},
     mplex,
@@ -339,6 +341,21 @@ impl aleph_bft::Network<HashDigest, Block, Signature, MultiSignature> for Networ
     }
 }
 
+// Create a tokio-based TCP transport use noise for authenticated
+// encryption and Mplex for multiplexing of substreams on a TCP stream.
+fn build_transport(local_key: &identity::Keypair) -> Boxed<(PeerId, StreamMuxerBox)> {
+    // Create a keypair for authenticated encryption of the transport.
+    let noise_keys = noise::Keypair::<noise::X25519Spec>::new()
+        .into_authentic(local_key)
+        .expect("Signing libp2p-noise static DH keypair failed.");
+
+    tcp::TokioTcpTransport::new(GenTcpConfig::default().nodelay(true))
+        .upgrade(upgrade::Version::V1)
+        .authenticate(noise::NoiseConfig::xx(noise_keys).into_authenticated())
+        .multiplex(mplex::MplexConfig::new())
+        .boxed()
+}
+
 pub struct NetworkManager {
     swarm: Swarm<Behaviour>,
     consensus_rx: UnboundedReceiver<(NetworkData, Recipient)>,
@@ -367,18 +384,7 @@ impl Network {
         let local_peer_id = PeerId::from(local_key.public());
         info!("Local peer id: {:?}", local_peer_id);
 
-        // Create a keypair for authenticated encryption of the transport.
-        let noise_keys = noise::Keypair::<noise::X25519Spec>::new()
-            .into_authentic(&local_key)
-            .expect("Signing libp2p-noise static DH keypair failed.");
-
-        // Create a tokio-based TCP transport use noise for authenticated
-        // encryption and Mplex for multiplexing of substreams on a TCP stream.
-        let transport = tcp::TokioTcpTransport::new(GenTcpConfig::default().nodelay(true))
-            .upgrade(upgrade::Version::V1)
-            .authenticate(noise::NoiseConfig::xx(noise_keys).into_authenticated())
-            .multiplex(mplex::MplexConfig::new())
-            .boxed();
+        let transport = build_transport(&local_key);
 
         let (msg_to_manager_tx, msg_to_manager_rx) = mpsc::unbounded();
         let (msg_for_store, msg_from_manager) = mpsc::unbounded();
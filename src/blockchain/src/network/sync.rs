@@ -0,0 +1,462 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use codec::{Decode, Encode};
+use futures::prelude::*;
+use libp2p::core::upgrade::{read_length_prefixed, write_length_prefixed, ProtocolName};
+use libp2p::request_response::{
+    ProtocolSupport, RequestId, RequestResponse, RequestResponseCodec, RequestResponseConfig,
+    RequestResponseEvent, RequestResponseMessage,
+};
+use libp2p::swarm::{SwarmBuilder, SwarmEvent};
+use libp2p::{identity, Multiaddr, PeerId, Swarm};
+use log::{debug, warn};
+use std::{io, iter, time::Duration};
+use thiserror::Error;
+
+use super::build_transport;
+use crate::blockchain::{Blockchain, BlockchainError, SharedBlockchain};
+use crate::crypto::hash_algorithm::HashDigest;
+use crate::structures::block::Block;
+use crate::structures::header::Ordinal;
+
+/// The maximum number of blocks sent in answer to a single range request
+pub const MAX_BLOCKS_PER_RESPONSE: usize = 100;
+
+// the maximum size of an encoded request or response
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum SyncError {
+    #[error("Failed to listen for peers: {0}")]
+    Transport(String),
+    #[error("Request to the peer failed: {0}")]
+    Outbound(String),
+    #[error("The peer answered with a response of another kind")]
+    UnexpectedResponse,
+    #[error("The peer did not send block {0}, below its tip")]
+    MissingBlocks(Ordinal),
+    #[error("Block {0} does not match the tip of the peer")]
+    TipMismatch(Ordinal),
+    #[error(transparent)]
+    Blockchain(#[from] BlockchainError),
+}
+
+/// The requests of the block sync protocol, sent by a node to pull the blocks it misses
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub enum SyncRequest {
+    GetBlockByHash(HashDigest),
+    /// The blocks with an ordinal within `from..=to`, at most [`MAX_BLOCKS_PER_RESPONSE`]
+    GetBlocksInRange {
+        from: Ordinal,
+        to: Ordinal,
+    },
+    GetTip,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub enum SyncResponse {
+    Block(Option<Block>),
+    Blocks(Vec<Block>),
+    Tip { ordinal: Ordinal, hash: HashDigest },
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockSyncProtocol();
+
+impl ProtocolName for BlockSyncProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        "/pyrsia/blockchain/sync/1".as_bytes()
+    }
+}
+
+/// Implements the libp2p [`RequestResponseCodec`] trait for the block sync protocol, every
+/// request and response is SCALE encoded and prefixed by its length
+#[derive(Debug, Clone)]
+pub struct BlockSyncCodec();
+
+async fn read_message<T, M>(io: &mut T) -> io::Result<M>
+where
+    T: AsyncRead + Unpin + Send,
+    M: Decode,
+{
+    let bytes = read_length_prefixed(io, MAX_MESSAGE_SIZE).await?;
+    M::decode(&mut &bytes[..])
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+async fn write_message<T, M>(io: &mut T, message: M) -> io::Result<()>
+where
+    T: AsyncWrite + Unpin + Send,
+    M: Encode,
+{
+    write_length_prefixed(io, message.encode()).await?;
+    io.close().await
+}
+
+#[async_trait::async_trait]
+impl RequestResponseCodec for BlockSyncCodec {
+    type Protocol = BlockSyncProtocol;
+    type Request = SyncRequest;
+    type Response = SyncResponse;
+
+    async fn read_request<T>(
+        &mut self,
+        _: &BlockSyncProtocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_message(io).await
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _: &BlockSyncProtocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_message(io).await
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &BlockSyncProtocol,
+        io: &mut T,
+        request: SyncRequest,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_message(io, request).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &BlockSyncProtocol,
+        io: &mut T,
+        response: SyncResponse,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_message(io, response).await
+    }
+}
+
+/// Answer `request` from the blocks of `blockchain`
+pub fn respond(blockchain: &Blockchain, request: SyncRequest) -> SyncResponse {
+    match request {
+        SyncRequest::GetBlockByHash(hash) => {
+            SyncResponse::Block(blockchain.get_block_by_hash(&hash).cloned())
+        }
+        SyncRequest::GetBlocksInRange { from, to } => {
+            let to = to.min(from.saturating_add(MAX_BLOCKS_PER_RESPONSE as Ordinal - 1));
+            SyncResponse::Blocks(
+                blockchain
+                    .blocks_in_range(from, to)
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+            )
+        }
+        SyncRequest::GetTip => {
+            let ordinal = blockchain.height();
+            let hash = blockchain
+                .get_block_by_ordinal(ordinal)
+                .map(|block| block.header.hash())
+                .expect("a blockchain to always have a genesis block");
+            SyncResponse::Tip { ordinal, hash }
+        }
+    }
+}
+
+/// A node of the block sync protocol. It answers the requests of its peers from a
+/// [`Blockchain`], and lets a node which joined late pull the blocks it misses from a peer.
+pub struct BlockSync {
+    swarm: Swarm<RequestResponse<BlockSyncCodec>>,
+}
+
+impl BlockSync {
+    pub fn new(keypair: identity::ed25519::Keypair) -> Self {
+        let local_key = identity::Keypair::Ed25519(keypair);
+        let local_peer_id = PeerId::from(local_key.public());
+
+        let mut config = RequestResponseConfig::default();
+        config.set_connection_keep_alive(Duration::from_secs(10));
+        config.set_request_timeout(Duration::from_secs(10));
+        let behaviour = RequestResponse::new(
+            BlockSyncCodec(),
+            iter::once((BlockSyncProtocol(), ProtocolSupport::Full)),
+            config,
+        );
+        let swarm = SwarmBuilder::new(build_transport(&local_key), behaviour, local_peer_id)
+            .executor(Box::new(|fut| {
+                tokio::spawn(fut);
+            }))
+            .build();
+        Self { swarm }
+    }
+
+    pub fn local_peer_id(&self) -> PeerId {
+        *self.swarm.local_peer_id()
+    }
+
+    /// Start listening on `address`, returning the address listened on once the transport
+    /// picked it, e.g. with the port assigned to `/tcp/0`
+    pub async fn listen_on(&mut self, address: Multiaddr) -> Result<Multiaddr, SyncError> {
+        self.swarm
+            .listen_on(address)
+            .map_err(|e| SyncError::Transport(e.to_string()))?;
+        loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = self.swarm.select_next_some().await {
+                return Ok(address);
+            }
+        }
+    }
+
+    /// Make `peer` reachable at `address`
+    pub fn add_peer_address(&mut self, peer: &PeerId, address: Multiaddr) {
+        self.swarm.behaviour_mut().add_address(peer, address);
+    }
+
+    /// Answer the requests of peers from `blockchain`, for as long as the future is polled.
+    /// The blockchain is only locked while a request is answered, so it can be extended
+    /// meanwhile.
+    pub async fn serve(&mut self, blockchain: SharedBlockchain) {
+        loop {
+            if let SwarmEvent::Behaviour(event) = self.swarm.select_next_some().await {
+                let blockchain = blockchain.lock().await;
+                self.handle_event(event, &blockchain);
+            }
+        }
+    }
+
+    /// Send `request` to `peer` and wait for its response. Requests from peers received
+    /// meanwhile are answered from `blockchain`.
+    pub async fn request(
+        &mut self,
+        peer: &PeerId,
+        request: SyncRequest,
+        blockchain: &Blockchain,
+    ) -> Result<SyncResponse, SyncError> {
+        let request_id = self.swarm.behaviour_mut().send_request(peer, request);
+        loop {
+            if let SwarmEvent::Behaviour(event) = self.swarm.select_next_some().await {
+                match self.handle_event(event, blockchain) {
+                    Some((id, result)) if id == request_id => return result,
+                    _ => continue,
+                }
+            }
+        }
+    }
+
+    /// Pull the blocks `blockchain` misses from `peer`, up to the tip of the peer, and
+    /// return the new height. Every block received is checked by `append_block`, the sync
+    /// stops at the first block which does not follow the chain.
+    pub async fn catch_up(
+        &mut self,
+        peer: &PeerId,
+        blockchain: &mut Blockchain,
+    ) -> Result<Ordinal, SyncError> {
+        let (tip, tip_hash) = match self.request(peer, SyncRequest::GetTip, blockchain).await? {
+            SyncResponse::Tip { ordinal, hash } => (ordinal, hash),
+            _ => return Err(SyncError::UnexpectedResponse),
+        };
+        while blockchain.height() < tip {
+            let from = blockchain.height() + 1;
+            let request = SyncRequest::GetBlocksInRange { from, to: tip };
+            let blocks = match self.request(peer, request, blockchain).await? {
+                SyncResponse::Blocks(blocks) if blocks.is_empty() => {
+                    return Err(SyncError::MissingBlocks(from))
+                }
+                SyncResponse::Blocks(blocks) => blocks,
+                _ => return Err(SyncError::UnexpectedResponse),
+            };
+            debug!("Received {} blocks from {:?}", blocks.len(), peer);
+            for block in blocks {
+                blockchain.append_block(block).await?;
+            }
+        }
+
+        match blockchain.get_block_by_ordinal(tip) {
+            Some(block) if block.header.hash() == tip_hash => Ok(blockchain.height()),
+            _ => Err(SyncError::TipMismatch(tip)),
+        }
+    }
+
+    // answer an inbound request, or hand over the result of one of our requests
+    fn handle_event(
+        &mut self,
+        event: RequestResponseEvent<SyncRequest, SyncResponse>,
+        blockchain: &Blockchain,
+    ) -> Option<(RequestId, Result<SyncResponse, SyncError>)> {
+        match event {
+            RequestResponseEvent::Message {
+                peer,
+                message:
+                    RequestResponseMessage::Request {
+                        request, channel, ..
+                    },
+            } => {
+                debug!("Answering {:?} from {:?}", request, peer);
+                let response = respond(blockchain, request);
+                if self
+                    .swarm
+                    .behaviour_mut()
+                    .send_response(channel, response)
+                    .is_err()
+                {
+                    warn!("Failed to answer {:?}, the connection is closed", peer);
+                }
+                None
+            }
+            RequestResponseEvent::Message {
+                message:
+                    RequestResponseMessage::Response {
+                        request_id,
+                        response,
+                    },
+                ..
+            } => Some((request_id, Ok(response))),
+            RequestResponseEvent::OutboundFailure {
+                request_id, error, ..
+            } => Some((request_id, Err(SyncError::Outbound(format!("{:?}", error))))),
+            RequestResponseEvent::InboundFailure { peer, error, .. } => {
+                warn!("Failed to answer a request from {:?}: {:?}", peer, error);
+                None
+            }
+            RequestResponseEvent::ResponseSent { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::BlockchainBuilder;
+    use libp2p::identity::Keypair::Ed25519;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_respond() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut blockchain = Blockchain::new(&ed25519_key);
+        for i in 0..3 {
+            blockchain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        let tip = blockchain.get_block_by_ordinal(3).unwrap().clone();
+
+        assert_eq!(
+            SyncResponse::Tip {
+                ordinal: 3,
+                hash: tip.header.hash()
+            },
+            respond(&blockchain, SyncRequest::GetTip)
+        );
+        assert_eq!(
+            SyncResponse::Block(Some(tip.clone())),
+            respond(&blockchain, SyncRequest::GetBlockByHash(tip.header.hash()))
+        );
+        assert_eq!(
+            SyncResponse::Block(None),
+            respond(
+                &blockchain,
+                SyncRequest::GetBlockByHash(HashDigest::new(b"unknown"))
+            )
+        );
+        assert_eq!(
+            SyncResponse::Blocks(vec![
+                blockchain.get_block_by_ordinal(2).unwrap().clone(),
+                tip
+            ]),
+            respond(
+                &blockchain,
+                SyncRequest::GetBlocksInRange { from: 2, to: 10 }
+            )
+        );
+
+        let response = respond(
+            &blockchain,
+            SyncRequest::GetBlocksInRange {
+                from: 0,
+                to: Ordinal::MAX,
+            },
+        );
+        assert_eq!(
+            Ok(response.clone()),
+            SyncResponse::decode(&mut &response.encode()[..]).map_err(|e| e.to_string())
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_catch_up() -> Result<(), String> {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_key = match keypair.clone() {
+            Ed25519(some) => some,
+            _ => return Err("Key format is wrong".to_string()),
+        };
+        let mut peer_chain = Blockchain::new(&ed25519_key);
+        for i in 0..10 {
+            peer_chain
+                .add_block(format!("payload {}", i).into_bytes(), keypair.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        let mut blockchain = BlockchainBuilder::new(&ed25519_key)
+            .genesis(peer_chain.get_block_by_ordinal(0).unwrap().clone())
+            .build()
+            .map_err(|e| e.to_string())?;
+        let peer_chain: SharedBlockchain = Arc::new(Mutex::new(peer_chain));
+
+        let mut peer = BlockSync::new(identity::ed25519::Keypair::generate());
+        let mut node = BlockSync::new(identity::ed25519::Keypair::generate());
+        let address = peer
+            .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .await
+            .map_err(|e| e.to_string())?;
+        let peer_id = peer.local_peer_id();
+        node.add_peer_address(&peer_id, address);
+
+        let height = tokio::select! {
+            _ = peer.serve(peer_chain.clone()) => {
+                return Err("the peer stopped serving".to_string())
+            }
+            result = node.catch_up(&peer_id, &mut blockchain) => result.map_err(|e| e.to_string())?,
+        };
+
+        assert_eq!(10, height);
+        let peer_chain = peer_chain.lock().await;
+        assert_eq!(peer_chain.height(), blockchain.height());
+        assert_eq!(
+            peer_chain.get_block_by_ordinal(10),
+            blockchain.get_block_by_ordinal(10)
+        );
+        Ok(())
+    }
+}
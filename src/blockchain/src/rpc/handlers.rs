@@ -0,0 +1,145 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use futures::{SinkExt, StreamExt};
+use log::{debug, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::value::RawValue;
+use tokio::sync::{broadcast, oneshot};
+use warp::ws::{Message, WebSocket, Ws};
+use warp::{Rejection, Reply};
+
+use super::model::{
+    GetBlockByHashParams, GetBlockByOrdinalParams, RpcError, RpcRequest, RpcResponse,
+    SubmitTransactionParams, SubscribeBlocksQuery,
+};
+use crate::blockchain::{SharedBlockchain, TransactionOutcome};
+use crate::structures::block::Block;
+use crate::structures::wire_format::WireFormat;
+
+pub async fn handle_rpc(
+    request: RpcRequest,
+    blockchain: SharedBlockchain,
+) -> Result<impl Reply, Rejection> {
+    let id = request.id.clone();
+    let response = match dispatch(request, blockchain).await {
+        Ok(result) => RpcResponse::result(id, result),
+        Err(error) => RpcResponse::error(id, error),
+    };
+
+    Ok(warp::reply::json(&response))
+}
+
+pub async fn handle_subscribe_blocks(
+    ws: Ws,
+    query: SubscribeBlocksQuery,
+    block_sender: broadcast::Sender<Block>,
+) -> Result<impl Reply, Rejection> {
+    // subscribe before the upgrade so no block appended after the handshake is missed
+    let blocks = block_sender.subscribe();
+    Ok(ws.on_upgrade(move |socket| forward_blocks(socket, blocks, query.format)))
+}
+
+async fn forward_blocks(
+    mut socket: WebSocket,
+    mut blocks: broadcast::Receiver<Block>,
+    format: WireFormat,
+) {
+    loop {
+        tokio::select! {
+            block = blocks.recv() => match block {
+                Ok(block) => {
+                    let encoded = match format.encode(&block) {
+                        Ok(encoded) => encoded,
+                        Err(e) => {
+                            warn!("Failed to serialize block {}: {}", block.header.ordinal, e);
+                            continue;
+                        }
+                    };
+                    let message = match format {
+                        WireFormat::Json => Message::text(String::from_utf8_lossy(&encoded)),
+                        WireFormat::Cbor => Message::binary(encoded),
+                    };
+                    if let Err(e) = socket.send(message).await {
+                        debug!("Block subscriber went away: {}", e);
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Block subscriber lagged behind, skipped {} blocks", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            message = socket.next() => match message {
+                Some(Ok(message)) if !message.is_close() => {}
+                _ => {
+                    debug!("Block subscriber disconnected");
+                    break;
+                }
+            },
+        }
+    }
+}
+
+async fn dispatch(
+    request: RpcRequest,
+    blockchain: SharedBlockchain,
+) -> Result<Box<RawValue>, RpcError> {
+    debug!("Handling rpc method {:?}", request.method);
+
+    match request.method.as_str() {
+        "get_block_by_hash" => {
+            let params: GetBlockByHashParams = parse_params(request.params)?;
+            let blockchain = blockchain.lock().await;
+            to_result(blockchain.get_block_by_hash(&params.hash))
+        }
+        "get_block_by_ordinal" => {
+            let params: GetBlockByOrdinalParams = parse_params(request.params)?;
+            let blockchain = blockchain.lock().await;
+            to_result(blockchain.get_block_by_ordinal(params.ordinal))
+        }
+        "height" => to_result(blockchain.lock().await.height()),
+        "submit_transaction" => {
+            let params: SubmitTransactionParams = parse_params(request.params)?;
+            let transaction = params.transaction;
+            // an invalid transaction is rejected right away, before submit_transaction returns
+            let (sender, mut outcome) = oneshot::channel();
+            blockchain
+                .lock()
+                .await
+                .submit_transaction(transaction.clone(), move |_, outcome| {
+                    let _ = sender.send(outcome);
+                })
+                .map_err(|e| RpcError::invalid_params(e.to_string()))?;
+            if let Ok(TransactionOutcome::Rejected { reason }) = outcome.try_recv() {
+                return Err(RpcError::invalid_params(reason));
+            }
+            to_result(transaction.hash())
+        }
+        "pending_count" => to_result(blockchain.lock().await.pending_count()),
+        method => Err(RpcError::method_not_found(method)),
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Option<Box<RawValue>>) -> Result<T, RpcError> {
+    let params = params.as_ref().map_or("null", |params| params.get());
+    serde_json::from_str(params).map_err(|e| RpcError::invalid_params(e.to_string()))
+}
+
+fn to_result<T: Serialize>(value: T) -> Result<Box<RawValue>, RpcError> {
+    serde_json::value::to_raw_value(&value).map_err(|e| RpcError::internal_error(e.to_string()))
+}
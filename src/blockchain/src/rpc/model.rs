@@ -0,0 +1,120 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+use crate::crypto::hash_algorithm::HashDigest;
+use crate::structures::header::Ordinal;
+use crate::structures::transaction::Transaction;
+use crate::structures::wire_format::WireFormat;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    pub id: Value,
+    pub method: String,
+    // kept raw, like the result of the response, since transactions contain u128 fields
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Box<RawValue>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    // kept raw since blocks contain u128 fields which serde_json::Value can not represent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Box<RawValue>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl RpcResponse {
+    pub fn result(id: Value, result: Box<RawValue>) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn error(id: Value, error: RpcError) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Error object as defined by the JSON-RPC 2.0 specification
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn method_not_found(method: &str) -> Self {
+        Self {
+            code: -32601,
+            message: format!("Method not found: {}", method),
+        }
+    }
+
+    pub fn invalid_params(message: String) -> Self {
+        Self {
+            code: -32602,
+            message,
+        }
+    }
+
+    pub fn internal_error(message: String) -> Self {
+        Self {
+            code: -32603,
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GetBlockByHashParams {
+    pub hash: HashDigest,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GetBlockByOrdinalParams {
+    pub ordinal: Ordinal,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SubmitTransactionParams {
+    /// The transaction, signed by its submitter
+    pub transaction: Transaction,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SubscribeBlocksQuery {
+    /// Encoding of the forwarded blocks, CBOR blocks are sent as binary messages
+    #[serde(default)]
+    pub format: WireFormat,
+}
@@ -0,0 +1,268 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use tokio::sync::broadcast;
+use warp::Filter;
+
+use super::handlers::{handle_rpc, handle_subscribe_blocks};
+use super::model::{RpcRequest, SubscribeBlocksQuery};
+use crate::blockchain::SharedBlockchain;
+use crate::structures::block::Block;
+
+/// Number of blocks buffered for a block subscriber before it starts lagging
+pub const BLOCK_BROADCAST_CAPACITY: usize = 64;
+
+/// Register a block listener which forwards every appended block to the returned broadcast channel
+pub async fn make_block_broadcast(blockchain: &SharedBlockchain) -> broadcast::Sender<Block> {
+    let (block_sender, _) = broadcast::channel(BLOCK_BROADCAST_CAPACITY);
+    let sender = block_sender.clone();
+    blockchain
+        .lock()
+        .await
+        .add_block_listener(move |block: &Block| {
+            // sending only fails when nobody is subscribed, which is fine
+            let _ = sender.send(block.clone());
+        });
+    block_sender
+}
+
+pub fn make_rpc_routes(
+    blockchain: SharedBlockchain,
+    block_sender: broadcast::Sender<Block>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let blockchain_filter = warp::any().map(move || blockchain.clone());
+    let block_sender_filter = warp::any().map(move || block_sender.clone());
+
+    let rpc = warp::path::end()
+        .and(warp::post())
+        .and(warp::body::content_length_limit(1024 * 64))
+        .and(warp::body::json::<RpcRequest>())
+        .and(blockchain_filter)
+        .and_then(handle_rpc);
+
+    let subscribe_blocks = warp::path!("blocks")
+        .and(warp::path::end())
+        .and(warp::ws())
+        .and(warp::query::<SubscribeBlocksQuery>())
+        .and(block_sender_filter)
+        .and_then(handle_subscribe_blocks);
+
+    warp::any().and(rpc.or(subscribe_blocks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::Blockchain;
+    use crate::crypto::hash_algorithm::HashDigest;
+    use crate::rpc::model::{RpcResponse, SubmitTransactionParams, JSONRPC_VERSION};
+    use crate::structures::block::Block;
+    use crate::structures::transaction::{network_salt, TransactionBuilder, TransactionType};
+    use crate::structures::wire_format::WireFormat;
+    use libp2p::identity;
+    use serde::Serialize;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    async fn rpc_call(url: &str, method: &str, params: impl Serialize) -> RpcResponse {
+        reqwest::Client::new()
+            .post(url)
+            .json(&RpcRequest {
+                jsonrpc: JSONRPC_VERSION.to_string(),
+                id: json!(1),
+                method: method.to_string(),
+                params: Some(serde_json::value::to_raw_value(&params).unwrap()),
+            })
+            .send()
+            .await
+            .expect("rpc request to be sent")
+            .json()
+            .await
+            .expect("rpc response to be json")
+    }
+
+    #[tokio::test]
+    async fn test_rpc_submit_transaction() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let blockchain: SharedBlockchain = Arc::new(Mutex::new(Blockchain::new(&keypair)));
+        let block_sender = make_block_broadcast(&blockchain).await;
+
+        let (address, server) = warp::serve(make_rpc_routes(blockchain.clone(), block_sender))
+            .bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let url = format!("http://{}/", address);
+
+        let client_key = identity::ed25519::Keypair::generate();
+        let transaction = TransactionBuilder::new(TransactionType::Create)
+            .payload(b"Hello RPC".to_vec())
+            .sign(&client_key)
+            .unwrap();
+        let response = rpc_call(
+            &url,
+            "submit_transaction",
+            SubmitTransactionParams {
+                transaction: transaction.clone(),
+            },
+        )
+        .await;
+        assert_eq!(None, response.error);
+        let hash: HashDigest = serde_json::from_str(response.result.unwrap().get()).unwrap();
+        assert_eq!(transaction.hash(), hash);
+
+        // the transaction is pending, committing it is left to the block producer
+        let response = rpc_call(&url, "pending_count", Value::Null).await;
+        assert_eq!("1", response.result.unwrap().get());
+        let response = rpc_call(&url, "height", Value::Null).await;
+        assert_eq!("0", response.result.unwrap().get());
+
+        let other_network = TransactionBuilder::new(TransactionType::Create)
+            .payload(b"Hello Other Network".to_vec())
+            .network_salt(network_salt("other"))
+            .sign(&client_key)
+            .unwrap();
+        let response = rpc_call(
+            &url,
+            "submit_transaction",
+            SubmitTransactionParams {
+                transaction: other_network,
+            },
+        )
+        .await;
+        assert_eq!(-32602, response.error.unwrap().code);
+        let response = rpc_call(&url, "pending_count", Value::Null).await;
+        assert_eq!("1", response.result.unwrap().get());
+
+        blockchain
+            .lock()
+            .await
+            .produce_block(&keypair)
+            .await
+            .unwrap();
+        let response = rpc_call(&url, "height", Value::Null).await;
+        assert_eq!("1", response.result.unwrap().get());
+        let response = rpc_call(&url, "pending_count", Value::Null).await;
+        assert_eq!("0", response.result.unwrap().get());
+
+        // a committed transaction can not be replayed
+        let response = rpc_call(
+            &url,
+            "submit_transaction",
+            SubmitTransactionParams { transaction },
+        )
+        .await;
+        assert_eq!(-32602, response.error.unwrap().code);
+    }
+
+    #[tokio::test]
+    async fn test_rpc_unknown_method() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_keypair = match keypair.clone() {
+            identity::Keypair::Ed25519(some) => some,
+            _ => panic!("Key format is wrong"),
+        };
+        let blockchain: SharedBlockchain = Arc::new(Mutex::new(Blockchain::new(&ed25519_keypair)));
+        let block_sender = make_block_broadcast(&blockchain).await;
+
+        let filter = make_rpc_routes(blockchain, block_sender);
+        let response = warp::test::request()
+            .method("POST")
+            .path("/")
+            .json(&json!({ "jsonrpc": "2.0", "id": 7, "method": "unknown" }))
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        let response: RpcResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(json!(7), response.id);
+        assert_eq!(-32601, response.error.unwrap().code);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_blocks() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_keypair = match keypair.clone() {
+            identity::Keypair::Ed25519(some) => some,
+            _ => panic!("Key format is wrong"),
+        };
+        let blockchain: SharedBlockchain = Arc::new(Mutex::new(Blockchain::new(&ed25519_keypair)));
+        let block_sender = make_block_broadcast(&blockchain).await;
+
+        let filter = make_rpc_routes(blockchain.clone(), block_sender.clone());
+        let mut client = warp::test::ws()
+            .path("/blocks")
+            .handshake(filter)
+            .await
+            .expect("websocket handshake to succeed");
+        assert_eq!(1, block_sender.receiver_count());
+
+        blockchain
+            .lock()
+            .await
+            .add_block(b"Hello WebSocket".to_vec(), keypair)
+            .await
+            .unwrap();
+
+        let message = client.recv().await.expect("block to be forwarded");
+        let block: Block = serde_json::from_str(message.to_str().unwrap()).unwrap();
+        assert_eq!(
+            blockchain.lock().await.get_block_by_ordinal(1),
+            Some(&block)
+        );
+
+        drop(client);
+        for _ in 0..100 {
+            if block_sender.receiver_count() == 0 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(0, block_sender.receiver_count());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_blocks_cbor() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let ed25519_keypair = match keypair.clone() {
+            identity::Keypair::Ed25519(some) => some,
+            _ => panic!("Key format is wrong"),
+        };
+        let blockchain: SharedBlockchain = Arc::new(Mutex::new(Blockchain::new(&ed25519_keypair)));
+        let block_sender = make_block_broadcast(&blockchain).await;
+
+        let filter = make_rpc_routes(blockchain.clone(), block_sender);
+        let mut client = warp::test::ws()
+            .path("/blocks?format=cbor")
+            .handshake(filter)
+            .await
+            .expect("websocket handshake to succeed");
+
+        blockchain
+            .lock()
+            .await
+            .add_block(b"Hello CBOR".to_vec(), keypair)
+            .await
+            .unwrap();
+
+        let message = client.recv().await.expect("block to be forwarded");
+        assert!(message.is_binary());
+        let block: Block = WireFormat::Cbor.decode(message.as_bytes()).unwrap();
+        assert_eq!(
+            blockchain.lock().await.get_block_by_ordinal(1),
+            Some(&block)
+        );
+    }
+}
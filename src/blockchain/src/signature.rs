@@ -16,12 +16,76 @@
 
 use aleph_bft::SignatureSet;
 use codec::{Decode, Encode};
-use libp2p::core::identity::ed25519::Keypair;
+use libp2p::core::identity::ed25519::{Keypair, PublicKey};
+use libp2p::identity;
 use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
 
+use crate::structures::header::Address;
+
 pub type Error = ed25519_dalek::SignatureError;
 
+/// Why a `Signer` did not produce a signature
+#[derive(Debug, Clone, thiserror::Error, PartialEq, Eq)]
+pub enum SigningError {
+    #[error("The signer failed to sign: {0}")]
+    Failed(String),
+    #[error("The signer returned {0} bytes, which are not an Ed25519 signature")]
+    MalformedSignature(usize),
+}
+
+/// What a signature is made for. The tag of the domain prefixes the signed message, so a
+/// signature made for one domain never verifies in another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureDomain {
+    Block,
+    Transaction,
+}
+
+impl SignatureDomain {
+    pub fn tag(&self) -> &'static [u8] {
+        match self {
+            SignatureDomain::Block => b"block",
+            SignatureDomain::Transaction => b"tx",
+        }
+    }
+
+    fn separate(&self, msg: &[u8]) -> Vec<u8> {
+        [self.tag(), msg].concat()
+    }
+}
+
+/// Signs with an Ed25519 key on behalf of its holder. The private key may never be in the
+/// process, e.g. when it is kept in a hardware security module or a remote key management
+/// service, only the signatures are. Such a signer may fail, or return garbage, which is
+/// reported rather than signed with.
+pub trait Signer {
+    /// The 64 bytes Ed25519 signature of `msg`
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, SigningError>;
+
+    fn public_key(&self) -> PublicKey;
+}
+
+impl Signer for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, SigningError> {
+        Ok(Keypair::sign(self, msg))
+    }
+
+    fn public_key(&self) -> PublicKey {
+        self.public()
+    }
+}
+
+impl<S: Signer + ?Sized> Signer for &S {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, SigningError> {
+        (**self).sign(msg)
+    }
+
+    fn public_key(&self) -> PublicKey {
+        (**self).public_key()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Decode)]
 pub struct Signature {
     #[codec(encoded_as = "[u8; ed25519_dalek::Signature::BYTE_SIZE]")]
@@ -43,9 +107,40 @@ impl Signature {
     pub fn to_bytes(self) -> [u8; ed25519_dalek::Signature::BYTE_SIZE] {
         self.signature.to_bytes()
     }
-    pub fn new(msg: &[u8], keypair: &Keypair) -> Self {
-        let signed: Vec<u8> = keypair.sign(msg);
-        Signature::from_bytes(&signed).expect("signed data should always be valid")
+    /// Sign `msg` with `signer`, failing when the signer fails or does not return an Ed25519
+    /// signature
+    pub fn new(msg: &[u8], signer: &dyn Signer) -> Result<Self, SigningError> {
+        let signed: Vec<u8> = signer.sign(msg)?;
+        Signature::from_bytes(&signed).map_err(|_| SigningError::MalformedSignature(signed.len()))
+    }
+
+    /// Check the signature was made over `msg` by the private key of `public_key`
+    pub fn verify(&self, msg: &[u8], public_key: &PublicKey) -> bool {
+        public_key.verify(msg, &self.signature.to_bytes())
+    }
+
+    /// Check the signature was made over `msg` by the private key of the Ed25519 key `address`
+    /// was derived from, without the key at hand. False when the key can not be recovered
+    /// from the address.
+    pub fn verify_with_address(&self, msg: &[u8], address: &Address) -> bool {
+        match address.public_key() {
+            Some(identity::PublicKey::Ed25519(public_key)) => self.verify(msg, &public_key),
+            _ => false,
+        }
+    }
+
+    /// Sign `msg` prefixed by the tag of `domain`
+    pub fn new_in(
+        domain: SignatureDomain,
+        msg: &[u8],
+        signer: &dyn Signer,
+    ) -> Result<Self, SigningError> {
+        Signature::new(&domain.separate(msg), signer)
+    }
+
+    /// Check the signature was made over `msg` prefixed by the tag of `domain`
+    pub fn verify_in(&self, domain: SignatureDomain, msg: &[u8], public_key: &PublicKey) -> bool {
+        self.verify(&domain.separate(msg), public_key)
     }
 }
 
@@ -65,6 +160,53 @@ pub type MultiSignature = SignatureSet<Signature>;
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_signature_verify() {
+        let keypair = Keypair::generate();
+        let signature = Signature::new(b"Hello Signature", &keypair).unwrap();
+
+        assert!(signature.verify(b"Hello Signature", &keypair.public()));
+        assert!(!signature.verify(b"Hello Tampered", &keypair.public()));
+        assert!(!signature.verify(b"Hello Signature", &Keypair::generate().public()));
+    }
+
+    #[test]
+    fn test_signature_verify_with_address() {
+        let keypair = Keypair::generate();
+        let address = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let signature = Signature::new(b"Hello Signature", &keypair).unwrap();
+
+        assert!(signature.verify_with_address(b"Hello Signature", &address));
+        assert!(!signature.verify_with_address(b"Hello Tampered", &address));
+        assert!(!signature.verify_with_address(
+            b"Hello Signature",
+            &Address::from(identity::PublicKey::Ed25519(Keypair::generate().public()))
+        ));
+        // the address of a random peer id does not hold a public key
+        assert!(!signature
+            .verify_with_address(b"Hello Signature", &Address::from(libp2p::PeerId::random())));
+    }
+
+    #[test]
+    fn test_signature_domain() {
+        let keypair = Keypair::generate();
+        let signature =
+            Signature::new_in(SignatureDomain::Block, b"Hello Signature", &keypair).unwrap();
+
+        assert!(signature.verify_in(
+            SignatureDomain::Block,
+            b"Hello Signature",
+            &keypair.public()
+        ));
+        assert!(!signature.verify_in(
+            SignatureDomain::Transaction,
+            b"Hello Signature",
+            &keypair.public()
+        ));
+        assert!(!signature.verify(b"Hello Signature", &keypair.public()));
+        assert!(signature.verify(b"blockHello Signature", &keypair.public()));
+    }
+
     #[test]
     fn test_signature_encode() {
         let bytes: [u8; ed25519_dalek::Signature::BYTE_SIZE] = [
@@ -0,0 +1,808 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::blockchain::BlockchainError;
+use crate::crypto::hash_algorithm::HashDigest;
+use crate::structures::block::Block;
+use crate::structures::header::Ordinal;
+
+const BLOCK_FILE_EXTENSION: &str = "json";
+const SEGMENT_FILE_EXTENSION: &str = "jsonl";
+
+/// The path of the file `block` is stored in, named after its ordinal and hash so that every
+/// block gets its own file
+pub fn build_path_for_block(dir: &Path, block: &Block) -> PathBuf {
+    dir.join(format!(
+        "{}_{}.{}",
+        block.header.ordinal,
+        block.header.hash(),
+        BLOCK_FILE_EXTENSION
+    ))
+}
+
+/// Store `block` as JSON in its own file under `dir`, returning the path of the file
+pub fn write_block(dir: &Path, block: &Block) -> io::Result<PathBuf> {
+    let json = serde_json::to_vec(block)?;
+    let path = build_path_for_block(dir, block);
+    write_synced(&path, &json)?;
+    Ok(path)
+}
+
+// Write `bytes` to a staging file next to `path`, sync it and rename it to `path`, so that
+// `path` never holds a partly written file and its content is on disk once it is there
+fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let staging = path.with_extension("tmp");
+    let mut file = fs::File::create(&staging)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&staging, path)
+}
+
+pub fn read_block(path: &Path) -> io::Result<Block> {
+    let bytes = fs::read(path)?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/// The format of a chain exported to move it between nodes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON block per line
+    JsonLines,
+    /// Bincode blocks, each prefixed by its length as a little endian u64
+    Bincode,
+}
+
+/// Append `block` to an export in `format`
+pub fn write_exported_block(
+    writer: &mut impl Write,
+    block: &Block,
+    format: ExportFormat,
+) -> Result<(), BlockchainError> {
+    let storage_failure = |e: io::Error| BlockchainError::StorageFailure(e.to_string());
+    match format {
+        ExportFormat::JsonLines => {
+            serde_json::to_writer(&mut *writer, block)
+                .map_err(|e| BlockchainError::Serialization(e.to_string()))?;
+            writer.write_all(b"\n").map_err(storage_failure)
+        }
+        ExportFormat::Bincode => {
+            let bytes = bincode::serialize(block)
+                .map_err(|e| BlockchainError::Serialization(e.to_string()))?;
+            writer
+                .write_all(&(bytes.len() as u64).to_le_bytes())
+                .map_err(storage_failure)?;
+            writer.write_all(&bytes).map_err(storage_failure)
+        }
+    }
+}
+
+/// Read the next block of an export in `format`, None once the export is exhausted
+pub fn read_exported_block(
+    reader: &mut impl BufRead,
+    format: ExportFormat,
+) -> Result<Option<Block>, BlockchainError> {
+    let storage_failure = |e: io::Error| BlockchainError::StorageFailure(e.to_string());
+    match format {
+        ExportFormat::JsonLines => {
+            let mut line = String::new();
+            if reader.read_line(&mut line).map_err(storage_failure)? == 0 {
+                return Ok(None);
+            }
+            serde_json::from_str(&line)
+                .map(Some)
+                .map_err(|e| BlockchainError::Serialization(e.to_string()))
+        }
+        ExportFormat::Bincode => {
+            if reader.fill_buf().map_err(storage_failure)?.is_empty() {
+                return Ok(None);
+            }
+            let mut length = [0; 8];
+            reader.read_exact(&mut length).map_err(storage_failure)?;
+            let length = usize::try_from(u64::from_le_bytes(length))
+                .map_err(|e| BlockchainError::Serialization(e.to_string()))?;
+            let mut bytes = vec![0; length];
+            reader.read_exact(&mut bytes).map_err(storage_failure)?;
+            bincode::deserialize(&bytes)
+                .map(Some)
+                .map_err(|e| BlockchainError::Serialization(e.to_string()))
+        }
+    }
+}
+
+// The ordinal a block file is named after, None when the file is not a block file
+fn parse_ordinal(path: &Path) -> Option<Ordinal> {
+    if path.extension()? != BLOCK_FILE_EXTENSION {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    let (ordinal, _hash) = stem.split_once('_')?;
+    ordinal.parse().ok()
+}
+
+/// List the block files under `dir` sorted by ordinal, without reading them
+pub fn list_block_files(dir: &Path) -> io::Result<Vec<(Ordinal, PathBuf)>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(ordinal) = parse_ordinal(&path) {
+            files.push((ordinal, path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The path of the segment file holding the blocks with an ordinal within `first..=last`
+pub fn build_path_for_segment(dir: &Path, first: Ordinal, last: Ordinal) -> PathBuf {
+    dir.join(format!("{}-{}.{}", first, last, SEGMENT_FILE_EXTENSION))
+}
+
+/// Store `blocks` as JSON lines in the segment file of the ordinals `first..=last`, the
+/// previous segment file is only replaced once the new one is complete
+pub fn write_segment(
+    dir: &Path,
+    first: Ordinal,
+    last: Ordinal,
+    blocks: &[Block],
+) -> io::Result<PathBuf> {
+    let mut bytes = vec![];
+    for block in blocks {
+        serde_json::to_writer(&mut bytes, block)?;
+        bytes.push(b'\n');
+    }
+    let path = build_path_for_segment(dir, first, last);
+    write_synced(&path, &bytes)?;
+    Ok(path)
+}
+
+pub fn read_segment(path: &Path) -> io::Result<Vec<Block>> {
+    io::BufReader::new(fs::File::open(path)?)
+        .lines()
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+// The ordinals a segment file is named after, None when the file is not a segment file
+fn parse_segment_range(path: &Path) -> Option<(Ordinal, Ordinal)> {
+    if path.extension()? != SEGMENT_FILE_EXTENSION {
+        return None;
+    }
+    let (first, last) = path.file_stem()?.to_str()?.split_once('-')?;
+    Some((first.parse().ok()?, last.parse().ok()?))
+}
+
+/// Whether `path` is named like a segment file, see `build_path_for_segment`
+pub fn is_segment_file(path: &Path) -> bool {
+    parse_segment_range(path).is_some()
+}
+
+/// Rewrite the segment file `path` with only the blocks for which `keep` holds, removing the
+/// file when no block is left
+pub fn retain_in_segment(path: &Path, keep: impl Fn(&Block) -> bool) -> io::Result<()> {
+    let (first, last) = parse_segment_range(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a segment file", path.display()),
+        )
+    })?;
+    let kept: Vec<Block> = read_segment(path)?
+        .into_iter()
+        .filter(|block| keep(block))
+        .collect();
+    if kept.is_empty() {
+        return fs::remove_file(path);
+    }
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    write_segment(dir, first, last, &kept)?;
+    Ok(())
+}
+
+/// List the segment files under `dir` sorted by their ordinals, without reading them
+pub fn list_segment_files(dir: &Path) -> io::Result<Vec<((Ordinal, Ordinal), PathBuf)>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(range) = parse_segment_range(&path) {
+            files.push((range, path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// The block files and segment files under `dir`, sorted by the first ordinal they hold
+fn list_stored_files(dir: &Path) -> io::Result<Vec<(Ordinal, PathBuf)>> {
+    let mut files = list_block_files(dir)?;
+    files.extend(
+        list_segment_files(dir)?
+            .into_iter()
+            .map(|((first, _), path)| (first, path)),
+    );
+    files.sort();
+    Ok(files)
+}
+
+// The blocks of a block file or a segment file
+fn read_stored_file(path: &Path) -> Vec<io::Result<Block>> {
+    if parse_segment_range(path).is_none() {
+        return vec![read_block(path)];
+    }
+    match read_segment(path) {
+        Ok(blocks) => blocks.into_iter().map(Ok).collect(),
+        Err(e) => vec![Err(e)],
+    }
+}
+
+/// Read the blocks stored under `dir` in ordinal order, one file at a time, whether they are
+/// stored in their own file or compacted into segment files
+pub fn read_block_files(dir: &Path) -> impl Iterator<Item = io::Result<Block>> {
+    let (files, listing_error) = match list_stored_files(dir) {
+        Ok(files) => (files, None),
+        Err(e) => (vec![], Some(e)),
+    };
+    listing_error.into_iter().map(Err).chain(
+        files
+            .into_iter()
+            .flat_map(|(_, path)| read_stored_file(&path)),
+    )
+}
+
+/// Merge the block files under `dir` into segment files of `segment_size` consecutive
+/// ordinals, e.g. the blocks 0 to 999 for a size of 1000. A block file is only removed once
+/// its block is written to a segment, the blocks added to the range of an existing segment
+/// since it was written are merged into it.
+pub fn compact_block_files(dir: &Path, segment_size: Ordinal) -> io::Result<()> {
+    if segment_size == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the segment size must be at least 1",
+        ));
+    }
+    let mut groups: BTreeMap<Ordinal, Vec<PathBuf>> = BTreeMap::new();
+    for (ordinal, path) in list_block_files(dir)? {
+        groups.entry(ordinal / segment_size).or_default().push(path);
+    }
+
+    for (index, paths) in groups {
+        let first = index * segment_size;
+        let last = first.saturating_add(segment_size - 1);
+        let segment = build_path_for_segment(dir, first, last);
+        let mut blocks = if segment.is_file() {
+            read_segment(&segment)?
+        } else {
+            vec![]
+        };
+        for path in paths.iter() {
+            blocks.push(read_block(path)?);
+        }
+        // a block file left behind by an interrupted compaction is already in the segment
+        let mut seen = HashSet::new();
+        blocks.retain(|block| seen.insert(block.header.hash()));
+        blocks.sort_by_key(|block| block.header.ordinal);
+
+        write_segment(dir, first, last, &blocks)?;
+        for path in paths {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// The hash part of the name of a block file, see `build_path_for_block`
+pub fn parse_hash(path: &Path) -> Option<&str> {
+    parse_ordinal(path)?;
+    let (_ordinal, hash) = path.file_stem()?.to_str()?.split_once('_')?;
+    Some(hash)
+}
+
+/// Where the blocks of a chain are persisted, so that the chain can be backed by any kind
+/// of storage without touching the core logic
+pub trait BlockStore: Send {
+    /// Store `block`, replacing any block stored with the same hash
+    fn put(&mut self, block: &Block) -> io::Result<()>;
+
+    /// The stored block with `hash`, None when there is no such block
+    fn get(&self, hash: &HashDigest) -> io::Result<Option<Block>>;
+
+    /// The stored blocks with `hashes`, in the same order, like `get` for each of them. Stores
+    /// which scan their storage to look up a block scan it once for all of them.
+    fn get_all(&self, hashes: &[HashDigest]) -> Vec<io::Result<Option<Block>>> {
+        hashes.iter().map(|hash| self.get(hash)).collect()
+    }
+
+    /// The stored blocks in ordinal order
+    fn iter(&self) -> Box<dyn Iterator<Item = io::Result<Block>> + '_>;
+
+    /// Remove the block with `hash`, returning it when it was stored
+    fn remove(&mut self, hash: &HashDigest) -> io::Result<Option<Block>>;
+
+    /// Make sure every block put so far is durably stored
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Group the stored blocks by `segment_size` consecutive ordinals, for stores where
+    /// storing blocks together is cheaper than storing them one by one
+    fn compact(&mut self, _segment_size: Ordinal) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Store every block as JSON in its own file under a directory, see `write_block`, until the
+/// blocks are compacted into segment files, see `compact_block_files`
+#[derive(Debug, Clone)]
+pub struct FileBlockStore {
+    dir: PathBuf,
+}
+
+impl FileBlockStore {
+    /// Store the blocks under `dir`, which must exist
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn find(&self, hash: &HashDigest) -> io::Result<Option<PathBuf>> {
+        let hash = hash.to_string();
+        Ok(list_block_files(&self.dir)?
+            .into_iter()
+            .map(|(_, path)| path)
+            .find(|path| parse_hash(path) == Some(hash.as_str())))
+    }
+
+    // The segment holding the block with `hash`, with the blocks of the segment
+    fn find_in_segments(
+        &self,
+        hash: &HashDigest,
+    ) -> io::Result<Option<((Ordinal, Ordinal), Vec<Block>)>> {
+        for (range, path) in list_segment_files(&self.dir)? {
+            let blocks = read_segment(&path)?;
+            if blocks.iter().any(|block| block.header.hash() == *hash) {
+                return Ok(Some((range, blocks)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl BlockStore for FileBlockStore {
+    fn put(&mut self, block: &Block) -> io::Result<()> {
+        write_block(&self.dir, block)?;
+        Ok(())
+    }
+
+    fn get(&self, hash: &HashDigest) -> io::Result<Option<Block>> {
+        if let Some(path) = self.find(hash)? {
+            return read_block(&path).map(Some);
+        }
+        Ok(self.find_in_segments(hash)?.and_then(|(_, blocks)| {
+            blocks
+                .into_iter()
+                .find(|block| block.header.hash() == *hash)
+        }))
+    }
+
+    fn get_all(&self, hashes: &[HashDigest]) -> Vec<io::Result<Option<Block>>> {
+        let copy = |e: &io::Error| io::Error::new(e.kind(), e.to_string());
+        // the files are listed and the segments read once, instead of once per block by get
+        let listed = list_block_files(&self.dir)
+            .and_then(|block_files| Ok((block_files, list_segment_files(&self.dir)?)));
+        let (block_files, segment_files) = match listed {
+            Ok(listed) => listed,
+            Err(e) => return hashes.iter().map(|_| Err(copy(&e))).collect(),
+        };
+        let block_paths: HashMap<&str, &PathBuf> = block_files
+            .iter()
+            .filter_map(|(_, path)| Some((parse_hash(path)?, path)))
+            .collect();
+        let mut segmented: HashMap<HashDigest, Block> = HashMap::new();
+        let mut unreadable_segment = None;
+        for (_, path) in segment_files {
+            match read_segment(&path) {
+                Ok(blocks) => {
+                    segmented.extend(blocks.into_iter().map(|block| (block.header.hash(), block)))
+                }
+                Err(e) => {
+                    unreadable_segment.get_or_insert(e);
+                }
+            }
+        }
+
+        hashes
+            .iter()
+            .map(|hash| {
+                if let Some(path) = block_paths.get(hash.to_string().as_str()) {
+                    return read_block(path).map(Some);
+                }
+                // like get, a block may be in the segment which can not be read
+                match (segmented.get(hash), &unreadable_segment) {
+                    (Some(block), _) => Ok(Some(block.clone())),
+                    (None, Some(e)) => Err(copy(e)),
+                    (None, None) => Ok(None),
+                }
+            })
+            .collect()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = io::Result<Block>> + '_> {
+        Box::new(read_block_files(&self.dir))
+    }
+
+    fn remove(&mut self, hash: &HashDigest) -> io::Result<Option<Block>> {
+        if let Some(path) = self.find(hash)? {
+            let block = read_block(&path)?;
+            fs::remove_file(path)?;
+            return Ok(Some(block));
+        }
+        let ((first, last), blocks) = match self.find_in_segments(hash)? {
+            Some(segment) => segment,
+            None => return Ok(None),
+        };
+        let (removed, kept): (Vec<Block>, Vec<Block>) = blocks
+            .into_iter()
+            .partition(|block| block.header.hash() == *hash);
+        if kept.is_empty() {
+            fs::remove_file(build_path_for_segment(&self.dir, first, last))?;
+        } else {
+            write_segment(&self.dir, first, last, &kept)?;
+        }
+        Ok(removed.into_iter().next())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // every block file is synced as soon as it is put, syncing the directory makes the
+        // renames of the new files durable as well
+        fs::File::open(&self.dir)?.sync_all()
+    }
+
+    fn compact(&mut self, segment_size: Ordinal) -> io::Result<()> {
+        compact_block_files(&self.dir, segment_size)
+    }
+}
+
+enum WriterMessage {
+    Put(Block),
+    Flush(oneshot::Sender<io::Result<()>>),
+}
+
+/// Store blocks from a dedicated thread, so that queueing a block to be stored never waits
+/// for the disk. The blocks are stored in the order they are queued, and the thread stops once
+/// the writer is dropped and every queued block is stored.
+pub struct BlockWriter {
+    sender: mpsc::UnboundedSender<WriterMessage>,
+}
+
+impl BlockWriter {
+    pub fn spawn(mut store: impl BlockStore + 'static) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        thread::spawn(move || {
+            // the first put that failed since the last flush, which that flush reports
+            let mut failed: Option<io::Error> = None;
+            while let Some(message) = receiver.blocking_recv() {
+                match message {
+                    WriterMessage::Put(block) => {
+                        if let Err(e) = store.put(&block) {
+                            warn!(
+                                "Blockchain: failed to store block {}: {}",
+                                block.header.ordinal, e
+                            );
+                            failed.get_or_insert(e);
+                        }
+                    }
+                    WriterMessage::Flush(done) => {
+                        let flushed = store.flush();
+                        // the flush is abandoned when its caller went away
+                        let _ = done.send(match failed.take() {
+                            Some(e) => Err(e),
+                            None => flushed,
+                        });
+                    }
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    /// Queue `block` to be stored
+    pub fn put(&self, block: Block) -> io::Result<()> {
+        self.sender
+            .send(WriterMessage::Put(block))
+            .map_err(|_| writer_stopped())
+    }
+
+    /// Wait until every block queued so far is durably stored. Fails with the first error of
+    /// the blocks which could not be stored since the last flush.
+    pub async fn flush(&self) -> io::Result<()> {
+        let (done, flushed) = oneshot::channel();
+        self.sender
+            .send(WriterMessage::Flush(done))
+            .map_err(|_| writer_stopped())?;
+        flushed.await.map_err(|_| writer_stopped())?
+    }
+}
+
+fn writer_stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "the block writer stopped")
+}
+
+/// Keep the blocks in memory, e.g. for tests which should not touch the filesystem
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryBlockStore {
+    blocks: HashMap<HashDigest, Block>,
+}
+
+impl InMemoryBlockStore {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+impl BlockStore for InMemoryBlockStore {
+    fn put(&mut self, block: &Block) -> io::Result<()> {
+        self.blocks.insert(block.header.hash(), block.clone());
+        Ok(())
+    }
+
+    fn get(&self, hash: &HashDigest) -> io::Result<Option<Block>> {
+        Ok(self.blocks.get(hash).cloned())
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = io::Result<Block>> + '_> {
+        let mut blocks: Vec<&Block> = self.blocks.values().collect();
+        blocks.sort_by_key(|block| block.header.ordinal);
+        Box::new(blocks.into_iter().cloned().map(Ok))
+    }
+
+    fn remove(&mut self, hash: &HashDigest) -> io::Result<Option<Block>> {
+        Ok(self.blocks.remove(hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::identity;
+
+    #[test]
+    fn test_write_and_list_blocks() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::ed25519::Keypair::generate();
+        let blocks: Vec<Block> = [10, 2, 1]
+            .into_iter()
+            .map(|ordinal| Block::new(HashDigest::new(b""), ordinal, vec![], &keypair).unwrap())
+            .collect();
+        for block in blocks.iter() {
+            write_block(dir.path(), block).map_err(|e| e.to_string())?;
+        }
+        fs::write(dir.path().join("README.md"), b"not a block").map_err(|e| e.to_string())?;
+
+        let files = list_block_files(dir.path()).map_err(|e| e.to_string())?;
+
+        assert_eq!(
+            vec![1, 2, 10],
+            files
+                .iter()
+                .map(|(ordinal, _)| *ordinal)
+                .collect::<Vec<Ordinal>>()
+        );
+        assert_eq!(build_path_for_block(dir.path(), &blocks[0]), files[2].1);
+        assert_eq!(
+            blocks[0],
+            read_block(&files[2].1).map_err(|e| e.to_string())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_block_path() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::ed25519::Keypair::generate();
+        let block = Block::new(HashDigest::new(b""), 3, vec![], &keypair).unwrap();
+
+        let path = write_block(dir.path(), &block).map_err(|e| e.to_string())?;
+
+        assert!(path.is_file());
+        assert_eq!(build_path_for_block(dir.path(), &block), path);
+        assert_eq!(block, read_block(&path).map_err(|e| e.to_string())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_stores() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::ed25519::Keypair::generate();
+        let blocks: Vec<Block> = [2, 0, 1]
+            .into_iter()
+            .map(|ordinal| Block::new(HashDigest::new(b""), ordinal, vec![], &keypair).unwrap())
+            .collect();
+        let stores: Vec<Box<dyn BlockStore>> = vec![
+            Box::new(FileBlockStore::new(dir.path())),
+            Box::new(InMemoryBlockStore::new()),
+        ];
+
+        for mut store in stores {
+            for block in blocks.iter() {
+                store.put(block).map_err(|e| e.to_string())?;
+            }
+            let stored = store
+                .iter()
+                .collect::<io::Result<Vec<Block>>>()
+                .map_err(|e| e.to_string())?;
+            assert_eq!(
+                vec![&blocks[1], &blocks[2], &blocks[0]],
+                stored.iter().collect::<Vec<_>>()
+            );
+
+            let hash = blocks[2].header.hash();
+            assert_eq!(
+                Some(&blocks[2]),
+                store.get(&hash).map_err(|e| e.to_string())?.as_ref()
+            );
+            assert_eq!(
+                Some(&blocks[2]),
+                store.remove(&hash).map_err(|e| e.to_string())?.as_ref()
+            );
+            assert_eq!(None, store.get(&hash).map_err(|e| e.to_string())?);
+            assert_eq!(None, store.remove(&hash).map_err(|e| e.to_string())?);
+            assert_eq!(2, store.iter().count());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_compacted_block_store() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let keypair = identity::ed25519::Keypair::generate();
+        let blocks: Vec<Block> = (0..12)
+            .map(|ordinal| Block::new(HashDigest::new(b""), ordinal, vec![], &keypair).unwrap())
+            .collect();
+        let mut store = FileBlockStore::new(dir.path());
+        for block in blocks[..7].iter() {
+            store.put(block).map_err(|e| e.to_string())?;
+        }
+
+        store.compact(5).map_err(|e| e.to_string())?;
+        assert!(list_block_files(dir.path())
+            .map_err(|e| e.to_string())?
+            .is_empty());
+        // blocks put after the compaction are merged into their segment by the next one
+        for block in blocks[7..].iter() {
+            store.put(block).map_err(|e| e.to_string())?;
+        }
+        assert_eq!(
+            blocks,
+            store
+                .iter()
+                .collect::<io::Result<Vec<Block>>>()
+                .map_err(|e| e.to_string())?
+        );
+        // looked up together, from segments and block files alike
+        let mut hashes: Vec<HashDigest> = blocks.iter().map(|block| block.header.hash()).collect();
+        hashes.push(HashDigest::new(b"unknown"));
+        let mut expected: Vec<Option<Block>> = blocks.iter().cloned().map(Some).collect();
+        expected.push(None);
+        assert_eq!(
+            expected,
+            store
+                .get_all(&hashes)
+                .into_iter()
+                .collect::<io::Result<Vec<Option<Block>>>>()
+                .map_err(|e| e.to_string())?
+        );
+        store.compact(5).map_err(|e| e.to_string())?;
+        assert_eq!(
+            vec![(0, 4), (5, 9), (10, 14)],
+            list_segment_files(dir.path())
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .map(|(range, _)| range)
+                .collect::<Vec<(Ordinal, Ordinal)>>()
+        );
+
+        let hash = blocks[6].header.hash();
+        assert_eq!(
+            Some(&blocks[6]),
+            store.get(&hash).map_err(|e| e.to_string())?.as_ref()
+        );
+        assert_eq!(
+            Some(&blocks[6]),
+            store.remove(&hash).map_err(|e| e.to_string())?.as_ref()
+        );
+        assert_eq!(None, store.get(&hash).map_err(|e| e.to_string())?);
+        assert_eq!(11, store.iter().count());
+        assert!(store.compact(0).is_err());
+        Ok(())
+    }
+
+    // A store whose puts wait until they are let through
+    struct GatedBlockStore {
+        gate: std::sync::mpsc::Receiver<()>,
+        stored: std::sync::Arc<std::sync::Mutex<Vec<Ordinal>>>,
+    }
+
+    impl BlockStore for GatedBlockStore {
+        fn put(&mut self, block: &Block) -> io::Result<()> {
+            self.gate.recv().map_err(|_| writer_stopped())?;
+            self.stored.lock().unwrap().push(block.header.ordinal);
+            Ok(())
+        }
+
+        fn get(&self, _hash: &HashDigest) -> io::Result<Option<Block>> {
+            Ok(None)
+        }
+
+        fn iter(&self) -> Box<dyn Iterator<Item = io::Result<Block>> + '_> {
+            Box::new(std::iter::empty())
+        }
+
+        fn remove(&mut self, _hash: &HashDigest) -> io::Result<Option<Block>> {
+            Ok(None)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_writer() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let (open_gate, gate) = std::sync::mpsc::channel();
+        let stored = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let writer = BlockWriter::spawn(GatedBlockStore {
+            gate,
+            stored: stored.clone(),
+        });
+
+        for ordinal in 1..=3 {
+            let block = Block::new(HashDigest::new(b""), ordinal, vec![], &keypair).unwrap();
+            writer.put(block).map_err(|e| e.to_string())?;
+        }
+        // queueing did not wait for the blocks to be stored
+        assert!(stored.lock().unwrap().is_empty());
+
+        for _ in 1..=3 {
+            open_gate.send(()).map_err(|e| e.to_string())?;
+        }
+        writer.flush().await.map_err(|e| e.to_string())?;
+        assert_eq!(vec![1, 2, 3], *stored.lock().unwrap());
+
+        // a closed gate fails the put, which the next flush reports once
+        drop(open_gate);
+        let block = Block::new(HashDigest::new(b""), 4, vec![], &keypair).unwrap();
+        writer.put(block).map_err(|e| e.to_string())?;
+        assert!(writer.flush().await.is_err());
+        writer.flush().await.map_err(|e| e.to_string())?;
+        assert_eq!(vec![1, 2, 3], *stored.lock().unwrap());
+        Ok(())
+    }
+}
@@ -16,5 +16,9 @@
 
 pub mod block;
 pub mod chain;
+pub mod genesis;
 pub mod header;
+pub mod receipt;
+pub mod state;
 pub mod transaction;
+pub mod wire_format;
@@ -14,25 +14,142 @@
    limitations under the License.
 */
 
-use codec::{Decode, Encode};
+use codec::{Decode, Encode, Input};
 use libp2p::identity;
-use serde::{Deserialize, Serialize};
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
 use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::debug_span;
 
 use super::header::{Address, Header};
-use super::transaction::Transaction;
-use crate::crypto::hash_algorithm::HashDigest;
-use crate::signature::Signature;
+use super::transaction::{Transaction, TransactionError};
+use crate::crypto::hash_algorithm::{canonical_json, hash_value, HashDigest};
+use crate::crypto::merkle::{merkle_proof, merkle_root, MerkleProof};
+use crate::signature::{Signature, SignatureDomain, Signer, SigningError};
 
 pub type BlockSignature = Signature;
 
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum BlockError {
+    #[error("Failed to deserialize the block: {0}")]
+    Deserialization(String),
+    #[error("The hash of the block header does not match its content")]
+    HeaderHashMismatch,
+    #[error("The block is not signed by its committer")]
+    BadBlockSignature,
+    #[error("Transaction {tx_hash} is not signed by its submitter")]
+    BadTransactionSignature { tx_hash: HashDigest },
+    #[error("The hash {tx_hash} of a transaction does not match its content")]
+    TransactionHashMismatch { tx_hash: HashDigest },
+    #[error("The transactions of the block do not match its transactions_hash")]
+    TransactionRootMismatch,
+    #[error("The block is committed by {0:?}, which is not an authority")]
+    UnauthorizedCommitter(Address),
+    #[error("The extensions of the block do not match its extensions_hash")]
+    ExtensionsMismatch,
+    #[error("Committer {committer:?} is not the address of the signing key {signer:?}")]
+    CommitterMismatch { committer: Address, signer: Address },
+    #[error(transparent)]
+    Signing(#[from] SigningError),
+}
+
+/// Extra fields an integrator attaches to a block, e.g. the software version of the committer,
+/// without changing the schema of blocks for everyone. The header commits to them, so they are
+/// covered by the block signature.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Extensions(BTreeMap<String, Value>);
+
+impl Extensions {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // The hash the header commits to, None without extensions so that the header of a block
+    // without extensions keeps its hash
+    fn digest(&self) -> Option<HashDigest> {
+        if self.0.is_empty() {
+            return None;
+        }
+        Some(hash_value(&self.to_value()))
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Object(self.0.clone().into_iter().collect())
+    }
+
+    fn to_json(&self) -> String {
+        String::from_utf8(canonical_json(&self.to_value()))
+            .expect("canonical JSON to be valid UTF-8")
+    }
+}
+
+// binary formats like bincode can not carry values which describe their own type, so they
+// carry the extensions as their canonical JSON text
+impl Serialize for Extensions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            self.0.serialize(serializer)
+        } else {
+            serializer.serialize_str(&self.to_json())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Extensions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            return Ok(Self(BTreeMap::deserialize(deserializer)?));
+        }
+        let json = String::deserialize(deserializer)?;
+        serde_json::from_str(&json)
+            .map(Self)
+            .map_err(de::Error::custom)
+    }
+}
+
+#[allow(clippy::derive_hash_xor_eq)] // equal extensions have the same canonical JSON
+impl Hash for Extensions {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.to_json().hash(state);
+    }
+}
+
+impl Encode for Extensions {
+    fn using_encoded<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+        self.to_json().using_encoded(f)
+    }
+}
+
+impl Decode for Extensions {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+        let json = String::decode(input)?;
+        serde_json::from_str(&json)
+            .map(Self)
+            .map_err(|_| "the extensions are not a JSON object".into())
+    }
+}
+
+/// A block is identified by its header, transactions and signature alone, the key it was
+/// signed with never leaves the keystore of the committer
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Decode, Encode, Hash)]
 pub struct Block {
     pub header: Header,
-    // TODO(fishseabowl): Should be a Merkle Tree to speed up validation with root hash
+    // The header's transactions_hash is the Merkle root of the transaction hashes
     pub transactions: Vec<Transaction>,
     signature: BlockSignature,
+    // The header's extensions_hash commits to the extensions, see `with_extension`
+    #[serde(default)]
+    extensions: Extensions,
 }
 
 impl Block {
@@ -40,29 +157,267 @@ impl Block {
         parent_hash: HashDigest,
         ordinal: u128,
         transactions: Vec<Transaction>,
-        signing_key: &identity::ed25519::Keypair,
-    ) -> Self {
-        let transaction_root = HashDigest::new(&bincode::serialize(&transactions).unwrap());
-        let header = Header::new(
+        signing_key: &dyn Signer,
+    ) -> Result<Self, BlockError> {
+        Self::new_with(
+            parent_hash,
+            ordinal,
+            transactions,
+            signing_key,
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            rand::thread_rng().gen::<u128>(),
+        )
+    }
+
+    /// Build a block with the given timestamp and nonce, so that its hash is reproducible
+    pub fn new_with(
+        parent_hash: HashDigest,
+        ordinal: u128,
+        transactions: Vec<Transaction>,
+        signing_key: &dyn Signer,
+        timestamp: u64,
+        nonce: u128,
+    ) -> Result<Self, BlockError> {
+        let transaction_root = transactions_root(&transactions);
+        let header = Header::new_with(
+            parent_hash,
+            transaction_root,
+            Address::from(identity::PublicKey::Ed25519(signing_key.public_key())),
+            ordinal,
+            timestamp,
+            nonce,
+        );
+        Self::sign(header, transactions, signing_key)
+    }
+
+    /// Build a block with the given timestamp meeting the given difficulty target, see
+    /// `Header::difficulty`
+    pub fn new_with_difficulty(
+        parent_hash: HashDigest,
+        ordinal: u128,
+        transactions: Vec<Transaction>,
+        signing_key: &dyn Signer,
+        timestamp: u64,
+        difficulty: u128,
+    ) -> Result<Self, BlockError> {
+        let transaction_root = transactions_root(&transactions);
+        let header = Header::new_with_difficulty(
             parent_hash,
             transaction_root,
-            Address::from(identity::PublicKey::Ed25519(signing_key.public())),
+            Address::from(identity::PublicKey::Ed25519(signing_key.public_key())),
             ordinal,
+            timestamp,
+            rand::thread_rng().gen::<u128>(),
+            difficulty,
         );
-        Self {
+        Self::sign(header, transactions, signing_key)
+    }
+
+    fn sign(
+        header: Header,
+        transactions: Vec<Transaction>,
+        signing_key: &dyn Signer,
+    ) -> Result<Self, BlockError> {
+        Ok(Self {
             header,
             transactions,
-            signature: Signature::new(&bincode::serialize(&header.hash()).unwrap(), signing_key),
+            signature: Signature::new_in(
+                SignatureDomain::Block,
+                &bincode::serialize(&header.hash()).unwrap(),
+                signing_key,
+            )?,
+            extensions: Extensions::default(),
+        })
+    }
+
+    /// The same block with the extension `key` set to `value`, signed again with
+    /// `signing_key`, which must be the key of the committer. The header commits to the
+    /// extensions, so the block gets another hash.
+    pub fn with_extension(
+        self,
+        key: impl Into<String>,
+        value: Value,
+        signing_key: &dyn Signer,
+    ) -> Result<Self, BlockError> {
+        let signer = Address::from(identity::PublicKey::Ed25519(signing_key.public_key()));
+        if self.header.committer != signer {
+            return Err(BlockError::CommitterMismatch {
+                committer: self.header.committer,
+                signer,
+            });
         }
+        let mut extensions = self.extensions;
+        extensions.0.insert(key.into(), value);
+        let header = self.header.with_extensions_hash(extensions.digest());
+        Ok(Self {
+            extensions,
+            ..Self::sign(header, self.transactions, signing_key)?
+        })
+    }
+
+    /// The value of the extension `key`, None when the block has no such extension
+    pub fn extension(&self, key: &str) -> Option<&Value> {
+        self.extensions.get(key)
+    }
+
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// The block id, the hash of its header
+    pub fn id(&self) -> HashDigest {
+        self.header.hash()
     }
 
     pub fn signature(&self) -> BlockSignature {
         self.signature.clone()
     }
 
-    // After merging Aleph consensus algorithm, it would be implemented
-    pub fn verify(&self) -> bool {
-        true
+    /// Check the block is signed by its committer, its header commits to its transactions and
+    /// every transaction matches its hash and is signed by its submitter
+    pub fn verify(&self) -> Result<(), BlockError> {
+        let _span = debug_span!(
+            "verify_block",
+            ordinal = %self.header.ordinal,
+            hash = ?self.header.hash(),
+            transactions = self.transactions.len()
+        )
+        .entered();
+        if !self.header.verify_hash() {
+            return Err(BlockError::HeaderHashMismatch);
+        }
+        if !self.verify_signature() {
+            return Err(BlockError::BadBlockSignature);
+        }
+        if self.header.extensions_hash() != self.extensions.digest() {
+            return Err(BlockError::ExtensionsMismatch);
+        }
+        if !self.verify_transactions_root() {
+            return Err(BlockError::TransactionRootMismatch);
+        }
+        for trans in self.transactions.iter() {
+            match trans.verify() {
+                Ok(()) => {}
+                Err(TransactionError::HashMismatch(tx_hash)) => {
+                    return Err(BlockError::TransactionHashMismatch { tx_hash })
+                }
+                Err(_) => {
+                    return Err(BlockError::BadTransactionSignature {
+                        tx_hash: trans.hash(),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check only the header of the block: its hash matches its content and it is signed by
+    /// its committer. The transactions are not verified at all, so this is a weaker check than
+    /// `verify`, e.g. to sync headers quickly, which the full verification must follow.
+    pub fn verify_header_only(&self) -> Result<(), BlockError> {
+        if !self.header.verify_hash() {
+            return Err(BlockError::HeaderHashMismatch);
+        }
+        if !self.verify_signature() {
+            return Err(BlockError::BadBlockSignature);
+        }
+        Ok(())
+    }
+
+    /// Check the block passes `verify` and is committed by one of `authorities`, which should
+    /// be the authority set as of the block's position in the chain rather than the latest one
+    pub fn verify_with_authorities(
+        &self,
+        authorities: &HashSet<Address>,
+    ) -> Result<(), BlockError> {
+        if !self.is_committed_by(authorities) {
+            return Err(BlockError::UnauthorizedCommitter(self.header.committer));
+        }
+        self.verify()
+    }
+
+    /// Check the committer of the block is one of `authorities`. The genesis block is exempt,
+    /// its committer being the initial authority.
+    pub fn is_committed_by(&self, authorities: &HashSet<Address>) -> bool {
+        self.header.ordinal == 0 || authorities.contains(&self.header.committer)
+    }
+
+    /// Deserialize a block from untrusted JSON, only returning it once it passed `verify`.
+    /// Trusted input, like our own storage, can be deserialized with serde directly.
+    pub fn from_json_verified(s: &str) -> Result<Block, BlockError> {
+        let block: Block =
+            serde_json::from_str(s).map_err(|e| BlockError::Deserialization(e.to_string()))?;
+        block.verify()?;
+        Ok(block)
+    }
+
+    /// Parse a block out of untrusted bytes, e.g. received from a peer, like
+    /// `from_json_verified`. Any malformed input, invalid UTF-8, JSON not describing a block,
+    /// digests of the wrong size or signatures of the wrong length, is an error rather than a
+    /// panic further down.
+    pub fn try_parse(bytes: &[u8]) -> Result<Block, BlockError> {
+        let json =
+            std::str::from_utf8(bytes).map_err(|e| BlockError::Deserialization(e.to_string()))?;
+        Block::from_json_verified(json)
+    }
+
+    /// Check `raw`, the JSON the block was received as, is exactly the serialization of the
+    /// block, so that no extra field, reordering or alternative encoding of a value survives
+    /// the block being stored and read back
+    pub fn is_canonical(&self, raw: &[u8]) -> bool {
+        serde_json::to_vec(self)
+            .map(|canonical| canonical == raw)
+            .unwrap_or(false)
+    }
+
+    /// Check the block can start a chain: its ordinal is 0, its parent hash is the zero digest
+    /// and it passes `verify`
+    pub fn is_valid_genesis(&self) -> bool {
+        self.header.ordinal == 0
+            && self.header.parent_hash == HashDigest::zero()
+            && self.verify().is_ok()
+    }
+
+    /// Check the header's transactions_hash is the root of the transactions of the block
+    pub fn verify_transactions_root(&self) -> bool {
+        self.header.transactions_hash == transactions_root(&self.transactions)
+    }
+
+    /// Check the block was signed by its committer
+    pub fn verify_signature(&self) -> bool {
+        verify_header_signature(&self.header, &self.signature)
+    }
+
+    /// The cost of verifying the transactions of the block, the sum of their weights
+    pub fn weight(&self) -> u64 {
+        self.transactions.iter().map(|trans| trans.weight()).sum()
+    }
+
+    /// The length of the block serialized as it is stored on disk
+    pub fn byte_size(&self) -> usize {
+        serde_json::to_vec(self)
+            .expect("a block to serialize as JSON")
+            .len()
+    }
+
+    /// The first submitter and nonce pair carried by more than one transaction of the block
+    pub fn find_duplicate_nonce(&self) -> Option<(Address, u128)> {
+        let mut seen = HashSet::new();
+        self.transactions
+            .iter()
+            .map(|trans| (trans.submitter(), trans.nonce()))
+            .find(|pair| !seen.insert(*pair))
+    }
+
+    /// Build the proof that the transaction with `tx_hash` is part of this block, to be checked
+    /// with `verify_inclusion` against the header's `transactions_hash`
+    pub fn inclusion_proof(&self, tx_hash: &HashDigest) -> Option<MerkleProof> {
+        let hashes: Vec<HashDigest> = self.transactions.iter().map(|trans| trans.hash()).collect();
+        let index = hashes.iter().position(|hash| hash == tx_hash)?;
+        merkle_proof(&hashes, index)
     }
 
     pub fn fetch_payload(&self) -> Vec<Vec<u8>> {
@@ -76,9 +431,42 @@ impl Block {
     }
 }
 
+/// Check `signature` was made over the hash of `header` by its committer, without the rest of
+/// the block
+pub fn verify_header_signature(header: &Header, signature: &BlockSignature) -> bool {
+    let public_key = match header.committer.public_key() {
+        Some(identity::PublicKey::Ed25519(public_key)) => public_key,
+        _ => return false,
+    };
+    match bincode::serialize(&header.hash()) {
+        Ok(msg) => signature.verify_in(SignatureDomain::Block, &msg, &public_key),
+        Err(_) => false,
+    }
+}
+
+/// The Merkle root of the hashes of `transactions`
+pub fn transactions_root(transactions: &[Transaction]) -> HashDigest {
+    let hashes: Vec<HashDigest> = transactions.iter().map(|trans| trans.hash()).collect();
+    merkle_root(&hashes)
+}
+
+/// Blocks are ordered by ordinal, and the blocks of competing forks at the same ordinal by
+/// hash, so that any set of blocks sorts the same way on every node. Blocks sharing a header
+/// hash, e.g. a block and a tampered copy of it, are ordered by their encoding, so that only
+/// equal blocks compare equal.
+impl Ord for Block {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.header
+            .ordinal
+            .cmp(&other.header.ordinal)
+            .then_with(|| self.header.hash().cmp(&other.header.hash()))
+            .then_with(|| self.encode().cmp(&other.encode()))
+    }
+}
+
 impl PartialOrd for Block {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.header.ordinal.partial_cmp(&other.header.ordinal)
+        Some(self.cmp(other))
     }
 }
 
@@ -92,8 +480,17 @@ impl Display for Block {
 #[cfg(test)]
 mod tests {
 
-    use super::super::transaction::TransactionType;
+    use super::super::transaction::{TransactionBuilder, TransactionType};
     use super::*;
+    use crate::crypto::merkle::{verify_inclusion, MerkleStep};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // The JSON value of `value`, read back from its serialization since a serde_json Value can
+    // not be serialized into from u128 fields, which must all fit in a u64 to be read back
+    fn json_value<T: Serialize>(value: &T) -> Value {
+        serde_json::from_slice(&serde_json::to_vec(value).unwrap()).unwrap()
+    }
 
     #[test]
     fn test_build_block() -> Result<(), String> {
@@ -105,16 +502,474 @@ mod tests {
             local_id,
             b"Hello First Transaction".to_vec(),
             &keypair,
-        )];
-        let block = Block::new(HashDigest::new(b""), 1, transactions.to_vec(), &keypair);
-        let expected_signature =
-            Signature::new(&bincode::serialize(&block.header.hash()).unwrap(), &keypair);
+        )
+        .unwrap()];
+        let block = Block::new(HashDigest::new(b""), 1, transactions.to_vec(), &keypair).unwrap();
+        let expected_signature = Signature::new_in(
+            SignatureDomain::Block,
+            &bincode::serialize(&block.header.hash()).unwrap(),
+            &keypair,
+        )
+        .unwrap();
 
         assert_eq!(1, block.header.ordinal);
         assert_eq!(expected_signature, block.signature());
         Ok(())
     }
 
+    // Signs through a callback, like a signer asking a remote key management service
+    struct RemoteSigner {
+        public_key: identity::ed25519::PublicKey,
+        sign: Box<dyn Fn(&[u8]) -> Result<Vec<u8>, SigningError>>,
+    }
+
+    impl Signer for RemoteSigner {
+        fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, SigningError> {
+            (self.sign)(msg)
+        }
+
+        fn public_key(&self) -> identity::ed25519::PublicKey {
+            self.public_key.clone()
+        }
+    }
+
+    #[test]
+    fn test_remote_signer() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let signed = Rc::new(Cell::new(0));
+        let signer = RemoteSigner {
+            public_key: keypair.public(),
+            sign: Box::new({
+                let signed = signed.clone();
+                move |msg| {
+                    signed.set(signed.get() + 1);
+                    Ok(keypair.sign(msg))
+                }
+            }),
+        };
+        let local_id = Address::from(identity::PublicKey::Ed25519(signer.public_key()));
+
+        let transaction = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"Hello Remote Signer".to_vec(),
+            &signer,
+        )
+        .map_err(|e| e.to_string())?;
+        let block = Block::new(HashDigest::new(b""), 1, vec![transaction], &signer).unwrap();
+
+        assert_eq!(2, signed.get());
+        assert_eq!(local_id, block.header.committer);
+        assert_eq!(Ok(()), block.verify());
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_signer_failure() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let unavailable = RemoteSigner {
+            public_key: keypair.public(),
+            sign: Box::new(|_| Err(SigningError::Failed("HSM unavailable".to_string()))),
+        };
+        let garbage = RemoteSigner {
+            public_key: keypair.public(),
+            sign: Box::new(|_| Ok(vec![0; 12])),
+        };
+
+        assert_eq!(
+            Err(BlockError::Signing(SigningError::Failed(
+                "HSM unavailable".to_string()
+            ))),
+            Block::new(HashDigest::new(b""), 1, vec![], &unavailable)
+        );
+        assert_eq!(
+            Err(BlockError::Signing(SigningError::MalformedSignature(12))),
+            Block::new(HashDigest::new(b""), 1, vec![], &garbage)
+        );
+        assert_eq!(
+            Err(TransactionError::Signing(SigningError::MalformedSignature(
+                12
+            ))),
+            TransactionBuilder::new(TransactionType::Create)
+                .payload(b"Hello Remote Signer".to_vec())
+                .sign(&garbage)
+        );
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut block = Block::new(HashDigest::new(b""), 1, vec![], &keypair).unwrap();
+        assert!(block.verify_signature());
+
+        block.signature = Signature::new(b"something else", &keypair).unwrap();
+        assert!(!block.verify_signature());
+    }
+
+    #[test]
+    fn test_transaction_signature_fails_block_verification() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut block = Block::new(HashDigest::new(b""), 1, vec![], &keypair).unwrap();
+        let msg = bincode::serialize(&block.header.hash()).unwrap();
+
+        block.signature = Signature::new(&msg, &keypair).unwrap();
+        assert!(!block.verify_signature());
+
+        block.signature = Signature::new_in(SignatureDomain::Transaction, &msg, &keypair).unwrap();
+        assert!(!block.verify_signature());
+        assert_eq!(Err(BlockError::BadBlockSignature), block.verify());
+    }
+
+    #[test]
+    fn test_find_duplicate_nonce() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let transaction = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"Hello Twice".to_vec(),
+            &keypair,
+        )
+        .unwrap();
+        let other = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"Hello Once".to_vec(),
+            &keypair,
+        )
+        .unwrap();
+
+        let block = Block::new(
+            HashDigest::new(b""),
+            1,
+            vec![transaction.clone(), other.clone()],
+            &keypair,
+        )
+        .unwrap();
+        assert_eq!(None, block.find_duplicate_nonce());
+
+        let block = Block::new(
+            HashDigest::new(b""),
+            1,
+            vec![transaction.clone(), other, transaction.clone()],
+            &keypair,
+        )
+        .unwrap();
+        assert_eq!(
+            Some((local_id, transaction.nonce())),
+            block.find_duplicate_nonce()
+        );
+    }
+
+    #[test]
+    fn test_byte_size() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let empty = Block::new(HashDigest::new(b""), 1, vec![], &keypair).unwrap();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let transaction = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"Hello First Transaction".to_vec(),
+            &keypair,
+        )
+        .unwrap();
+        let full = Block::new(HashDigest::new(b""), 1, vec![transaction], &keypair).unwrap();
+
+        assert!(empty.byte_size() > 0);
+        assert!(full.byte_size() > empty.byte_size());
+    }
+
+    #[test]
+    fn test_new_with_is_deterministic() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let build =
+            || Block::new_with(HashDigest::new(b""), 1, vec![], &keypair, 1656633600, 42).unwrap();
+
+        let block = build();
+
+        assert_eq!(block.id(), build().id());
+        assert_eq!(block, build());
+        assert_eq!(1656633600, block.header.timestamp);
+        assert_ne!(
+            block.id(),
+            Block::new_with(HashDigest::new(b""), 1, vec![], &keypair, 1656633600, 43)
+                .unwrap()
+                .id()
+        );
+    }
+
+    #[test]
+    fn test_equality_ignores_keystore() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        // the same key loaded into a separate keystore
+        let mut encoded = keypair.encode();
+        let reloaded =
+            identity::ed25519::Keypair::decode(&mut encoded).map_err(|e| e.to_string())?;
+
+        let block =
+            Block::new_with(HashDigest::new(b""), 1, vec![], &keypair, 1656633600, 42).unwrap();
+        let same =
+            Block::new_with(HashDigest::new(b""), 1, vec![], &reloaded, 1656633600, 42).unwrap();
+        let received: Block =
+            serde_json::from_slice(&serde_json::to_vec(&block).map_err(|e| e.to_string())?)
+                .map_err(|e| e.to_string())?;
+
+        assert_eq!(block, same);
+        assert_eq!(block, received);
+        assert_eq!(Ok(()), received.verify());
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialized_block_carries_no_private_key() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let transactions = vec![Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"Hello Public Block".to_vec(),
+            &keypair,
+        )
+        .map_err(|e| e.to_string())?];
+        let block = Block::new(HashDigest::new(b""), 1, transactions, &keypair).unwrap();
+
+        let secret = keypair.secret();
+        let contains_secret = |bytes: &[u8]| {
+            bytes
+                .windows(secret.as_ref().len())
+                .any(|w| w == secret.as_ref())
+        };
+        let bincode_bytes = bincode::serialize(&block).map_err(|e| e.to_string())?;
+        assert!(!contains_secret(&bincode_bytes));
+        assert!(!contains_secret(&block.encode()));
+
+        // the committer address is all it takes to verify the block
+        let received: Block = bincode::deserialize(&bincode_bytes).map_err(|e| e.to_string())?;
+        assert_eq!(
+            Some(identity::PublicKey::Ed25519(keypair.public())),
+            received.header.committer.public_key()
+        );
+        assert_eq!(Ok(()), received.verify());
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_canonical() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let block =
+            Block::new_with(HashDigest::new(b""), 1, vec![], &keypair, 1656633600, 0).unwrap();
+        let json = serde_json::to_vec(&block).map_err(|e| e.to_string())?;
+        assert!(block.is_canonical(&json));
+
+        // a JSON value sorts the fields of the block by name
+        let reordered = serde_json::to_vec(&json_value(&block)).unwrap();
+        assert_eq!(block, serde_json::from_slice::<Block>(&reordered).unwrap());
+        assert!(!block.is_canonical(&reordered));
+        assert!(!block.is_canonical(&[json.as_slice(), b" "].concat()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_json_verified() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let block =
+            Block::new_with(HashDigest::new(b""), 1, vec![], &keypair, 1656633600, 0).unwrap();
+        let json = serde_json::to_string(&block).map_err(|e| e.to_string())?;
+        assert_eq!(Ok(block.clone()), Block::from_json_verified(&json));
+
+        let mut tampered = json_value(&block);
+        tampered["header"]["timestamp"] = serde_json::json!(block.header.timestamp + 1);
+        assert_eq!(
+            Err(BlockError::HeaderHashMismatch),
+            Block::from_json_verified(&tampered.to_string())
+        );
+        assert!(matches!(
+            Block::from_json_verified("{}"),
+            Err(BlockError::Deserialization(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extensions() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let plain =
+            Block::new_with(HashDigest::new(b""), 1, vec![], &keypair, 1656633600, 0).unwrap();
+        assert_eq!(None, plain.header.extensions_hash());
+
+        let block = plain
+            .clone()
+            .with_extension("version", serde_json::json!("1.2.3"), &keypair)
+            .unwrap()
+            .with_extension("region", serde_json::json!({"zone": "eu"}), &keypair)
+            .unwrap();
+        assert_eq!(Ok(()), block.verify());
+        assert_ne!(plain.id(), block.id());
+        assert!(block.header.extensions_hash().is_some());
+        assert_eq!(
+            Some(&serde_json::json!("1.2.3")),
+            block.extension("version")
+        );
+        assert_eq!(None, block.extension("missing"));
+
+        // extensions unknown to the reader are carried over by every format
+        let json = serde_json::to_string(&block).map_err(|e| e.to_string())?;
+        assert_eq!(Ok(block.clone()), Block::from_json_verified(&json));
+        let bincode_bytes = bincode::serialize(&block).map_err(|e| e.to_string())?;
+        assert_eq!(
+            block,
+            bincode::deserialize::<Block>(&bincode_bytes).map_err(|e| e.to_string())?
+        );
+        assert_eq!(
+            block,
+            Block::decode(&mut &block.encode()[..]).map_err(|e| e.to_string())?
+        );
+
+        let mut tampered = json_value(&block);
+        tampered["extensions"]["version"] = serde_json::json!("6.6.6");
+        assert_eq!(
+            Err(BlockError::ExtensionsMismatch),
+            Block::from_json_verified(&tampered.to_string())
+        );
+        let mut stripped = json_value(&block);
+        stripped.as_object_mut().unwrap().remove("extensions");
+        assert_eq!(
+            Err(BlockError::ExtensionsMismatch),
+            Block::from_json_verified(&stripped.to_string())
+        );
+
+        let other = identity::ed25519::Keypair::generate();
+        assert_eq!(
+            Err(BlockError::CommitterMismatch {
+                committer: block.header.committer,
+                signer: Address::from(identity::PublicKey::Ed25519(other.public()))
+            }),
+            block.with_extension("version", serde_json::json!("6.6.6"), &other)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_try_parse() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let transaction = Transaction::new_with(
+            TransactionType::Create,
+            local_id,
+            b"Hello Untrusted Peer".to_vec(),
+            1656633600,
+            1,
+            &keypair,
+        )
+        .map_err(|e| e.to_string())?;
+        let block = Block::new_with(
+            HashDigest::new(b""),
+            1,
+            vec![transaction],
+            &keypair,
+            1656633600,
+            0,
+        )
+        .unwrap();
+        let json = serde_json::to_vec(&block).map_err(|e| e.to_string())?;
+        assert_eq!(Ok(block.clone()), Block::try_parse(&json));
+
+        let is_malformed =
+            |bytes: &[u8]| matches!(Block::try_parse(bytes), Err(BlockError::Deserialization(_)));
+        for len in [0, 1, json.len() / 2, json.len() - 1] {
+            assert!(is_malformed(&json[..len]));
+        }
+        assert!(is_malformed(&[0xff, 0xfe, 0x00, 0x7b]));
+        assert!(is_malformed(b"[1, 2, 3]"));
+
+        let value = json_value(&block);
+        let tx_hash = block.transactions[0].hash().to_string();
+        let mut short_digest = value.clone();
+        short_digest["transactions"][0]["hash"] =
+            serde_json::json!(format!("{}04deadbeef", &tx_hash[..2]));
+        assert!(is_malformed(&serde_json::to_vec(&short_digest).unwrap()));
+        let mut oversized_digest = value.clone();
+        oversized_digest["transactions"][0]["hash"] =
+            serde_json::json!(format!("{}21{}", &tx_hash[..2], "00".repeat(33)));
+        assert!(is_malformed(
+            &serde_json::to_vec(&oversized_digest).unwrap()
+        ));
+        let mut short_signature = value;
+        short_signature["signature"]["signature"] = serde_json::json!([0; 10]);
+        assert!(is_malformed(&serde_json::to_vec(&short_signature).unwrap()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_rejects_swapped_transactions() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let transaction = |payload: &[u8]| {
+            Transaction::new(
+                TransactionType::Create,
+                local_id,
+                payload.to_vec(),
+                &keypair,
+            )
+            .unwrap()
+        };
+        let mut block = Block::new(
+            HashDigest::new(b""),
+            1,
+            vec![transaction(b"committed")],
+            &keypair,
+        )
+        .unwrap();
+        assert!(block.verify_transactions_root());
+        assert_eq!(Ok(()), block.verify());
+
+        block.transactions = vec![transaction(b"swapped")];
+
+        assert!(!block.verify_transactions_root());
+        assert_eq!(Err(BlockError::TransactionRootMismatch), block.verify());
+    }
+
+    #[test]
+    fn test_verify_rejects_bad_signatures() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let transaction = |payload: &[u8], nonce: u128| {
+            Transaction::new_with(
+                TransactionType::Create,
+                local_id,
+                payload.to_vec(),
+                1656633600,
+                nonce,
+                &keypair,
+            )
+            .map_err(|e| e.to_string())
+        };
+
+        let mut block = Block::new(HashDigest::new(b""), 1, vec![], &keypair).unwrap();
+        block.signature = Block::new(HashDigest::new(b""), 2, vec![], &keypair)
+            .unwrap()
+            .signature;
+        assert_eq!(Err(BlockError::BadBlockSignature), block.verify());
+
+        // graft the signature of another transaction onto the transaction
+        let mut forged = json_value(&transaction(b"forged", 1)?);
+        forged["signature"] = json_value(&transaction(b"other", 2)?.signature());
+        let forged: Transaction = serde_json::from_value(forged).map_err(|e| e.to_string())?;
+        let tx_hash = forged.hash();
+        let block = Block::new(
+            HashDigest::new(b""),
+            1,
+            vec![transaction(b"genuine", 3)?, forged],
+            &keypair,
+        )
+        .unwrap();
+        assert_eq!(
+            Err(BlockError::BadTransactionSignature { tx_hash }),
+            block.verify()
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_fetch_payload() -> Result<(), String> {
         let keypair = identity::ed25519::Keypair::generate();
@@ -125,8 +980,9 @@ mod tests {
             local_id,
             b"Hello First Transaction".to_vec(),
             &keypair,
-        )];
-        let block = Block::new(HashDigest::new(b""), 1, transactions.to_vec(), &keypair);
+        )
+        .unwrap()];
+        let block = Block::new(HashDigest::new(b""), 1, transactions.to_vec(), &keypair).unwrap();
 
         assert_eq!(
             b"Hello First Transaction".to_vec(),
@@ -134,4 +990,98 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_inclusion_proof() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+
+        let transactions: Vec<Transaction> = (0..5)
+            .map(|i| {
+                Transaction::new(
+                    TransactionType::Create,
+                    local_id,
+                    format!("Transaction {}", i).into_bytes(),
+                    &keypair,
+                )
+                .unwrap()
+            })
+            .collect();
+        let block = Block::new(HashDigest::new(b""), 1, transactions.to_vec(), &keypair).unwrap();
+        let middle = transactions[2].hash();
+
+        let proof = block.inclusion_proof(&middle).unwrap();
+        assert!(verify_inclusion(
+            &middle,
+            &proof,
+            &block.header.transactions_hash
+        ));
+        assert!(!verify_inclusion(
+            &transactions[1].hash(),
+            &proof,
+            &block.header.transactions_hash
+        ));
+
+        let mut tampered = proof.clone();
+        tampered.path[0] = match tampered.path[0] {
+            MerkleStep::Left(_) => MerkleStep::Left(HashDigest::new(b"tampered")),
+            MerkleStep::Right(_) => MerkleStep::Right(HashDigest::new(b"tampered")),
+        };
+        assert!(!verify_inclusion(
+            &middle,
+            &tampered,
+            &block.header.transactions_hash
+        ));
+
+        assert_eq!(None, block.inclusion_proof(&HashDigest::new(b"unknown")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_ordering() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let genesis = Block::new(HashDigest::zero(), 0, vec![], &keypair).unwrap();
+        let fork_a = Block::new(genesis.header.hash(), 1, vec![], &keypair).unwrap();
+        let fork_b = Block::new(genesis.header.hash(), 1, vec![], &keypair).unwrap();
+        let child = Block::new(fork_a.header.hash(), 2, vec![], &keypair).unwrap();
+        assert_ne!(fork_a, fork_b);
+        assert_ne!(Ordering::Equal, fork_a.cmp(&fork_b));
+        assert_eq!(Ordering::Equal, fork_a.cmp(&fork_a.clone()));
+        let mut resigned = fork_a.clone();
+        resigned.signature = fork_b.signature.clone();
+        assert_ne!(fork_a, resigned);
+        assert_ne!(Ordering::Equal, fork_a.cmp(&resigned));
+
+        let (low, high) = if fork_a.header.hash() < fork_b.header.hash() {
+            (&fork_a, &fork_b)
+        } else {
+            (&fork_b, &fork_a)
+        };
+        let expected = vec![genesis.clone(), low.clone(), high.clone(), child.clone()];
+        let mut blocks = vec![child.clone(), high.clone(), genesis.clone(), low.clone()];
+        blocks.sort();
+        assert_eq!(expected, blocks);
+        let mut blocks = vec![low.clone(), child, genesis, high.clone(), low.clone()];
+        blocks.sort();
+        blocks.dedup();
+        assert_eq!(expected, blocks);
+    }
+
+    #[test]
+    fn test_is_valid_genesis() {
+        let keypair = identity::ed25519::Keypair::generate();
+
+        let genesis = Block::new(HashDigest::zero(), 0, vec![], &keypair).unwrap();
+        assert!(genesis.is_valid_genesis());
+
+        let not_zero_parent = Block::new(HashDigest::new(b""), 0, vec![], &keypair).unwrap();
+        assert!(!not_zero_parent.is_valid_genesis());
+
+        let not_first = Block::new(HashDigest::zero(), 1, vec![], &keypair).unwrap();
+        assert!(!not_first.is_valid_genesis());
+
+        let mut unsigned = genesis;
+        unsigned.signature = not_first.signature;
+        assert!(!unsigned.is_valid_genesis());
+    }
 }
@@ -16,20 +16,169 @@
 
 use codec::{Decode, Encode};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ops::Deref;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::debug_span;
 
-use super::block::Block;
+use super::block::{Block, BlockError};
+use super::header::{Address, Header, Ordinal};
+use super::state::{apply_authority_transactions, apply_revocations, revoked_at, Revocations};
+use super::transaction::{Transaction, TransactionError};
+use crate::crypto::hash_algorithm::HashDigest;
+
+/// The last block removed from the chain by pruning
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Decode, Encode, Hash, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub ordinal: Ordinal,
+    pub hash: HashDigest,
+}
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum ChainError {
+    #[error("The chain has no blocks")]
+    Empty,
+    #[error("The genesis block {actual} is not the expected genesis {expected}")]
+    GenesisMismatch {
+        expected: HashDigest,
+        actual: HashDigest,
+    },
+    #[error("Block {0} does not link to its parent")]
+    BrokenLinkage(Ordinal),
+    #[error("Expected a block with ordinal {expected}, got {actual}")]
+    UnexpectedOrdinal { expected: Ordinal, actual: Ordinal },
+    #[error("The genesis block has a non-zero parent hash {0}")]
+    GenesisParentNotZero(HashDigest),
+    #[error("Block {ordinal} fails verification: {error}")]
+    InvalidBlock { ordinal: Ordinal, error: BlockError },
+    #[error("Block {0} is not signed by its committer")]
+    InvalidBlockSignature(Ordinal),
+    #[error("The hash of the header of block {0} does not match its content")]
+    HeaderHashMismatch(Ordinal),
+    #[error("The transactions of block {0} do not match its transactions_hash")]
+    TransactionsRootMismatch(Ordinal),
+    #[error("Block {ordinal} carries several transactions of {submitter:?} with nonce {nonce}")]
+    DuplicateNonce {
+        ordinal: Ordinal,
+        submitter: Address,
+        nonce: u128,
+    },
+    #[error("Block {ordinal} carries an invalid transaction: {error}")]
+    InvalidTransaction {
+        ordinal: Ordinal,
+        error: TransactionError,
+    },
+    #[error("The chain diverges from the finalized block {0}")]
+    FinalityViolation(Ordinal),
+    #[error("Block {ordinal} is committed by {committer:?}, which is not an authority")]
+    UnauthorizedCommitter {
+        ordinal: Ordinal,
+        committer: Address,
+    },
+    #[error("Block {ordinal} is committed by {committer:?} after its key was revoked")]
+    RevokedCommitter {
+        ordinal: Ordinal,
+        committer: Address,
+    },
+}
+
+impl ChainError {
+    /// The ordinal of the block the error is about, None when it is about the whole chain
+    pub fn ordinal(&self) -> Option<Ordinal> {
+        match self {
+            ChainError::Empty => None,
+            ChainError::GenesisMismatch { .. } | ChainError::GenesisParentNotZero(_) => Some(0),
+            ChainError::UnexpectedOrdinal { actual, .. } => Some(*actual),
+            ChainError::BrokenLinkage(ordinal)
+            | ChainError::InvalidBlockSignature(ordinal)
+            | ChainError::HeaderHashMismatch(ordinal)
+            | ChainError::TransactionsRootMismatch(ordinal)
+            | ChainError::FinalityViolation(ordinal)
+            | ChainError::DuplicateNonce { ordinal, .. }
+            | ChainError::InvalidTransaction { ordinal, .. }
+            | ChainError::InvalidBlock { ordinal, .. }
+            | ChainError::UnauthorizedCommitter { ordinal, .. }
+            | ChainError::RevokedCommitter { ordinal, .. } => Some(*ordinal),
+        }
+    }
+}
+
+/// Figures about a chain gathered while validating it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainStats {
+    pub height: Ordinal,
+    pub transactions: usize,
+    pub authorities: usize,
+}
+
+/// The outcome of verifying one block of a chain, see `Chain::verify_all`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockVerification {
+    pub ordinal: Ordinal,
+    pub hash: HashDigest,
+    pub result: Result<(), ChainError>,
+}
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone, Decode, Encode, Hash, PartialEq, Eq)]
 pub struct Chain {
     // The block sequence is always sorted by the ordinal, guaranteed by the hash and parent hash
     blocks: Vec<Block>,
+    // The last pruned block, which the first block after genesis links to once pruned
+    #[serde(default)]
+    checkpoint: Option<Checkpoint>,
+}
+
+/// A read-only view of a chain at a point in time, see `Blockchain::snapshot`. Cloning it or
+/// handing it to another thread does not copy the blocks, and the blocks appended to the chain
+/// afterwards do not show up in it.
+#[derive(Debug, Clone)]
+pub struct ChainSnapshot {
+    chain: Arc<Chain>,
+}
+
+impl ChainSnapshot {
+    pub fn new(chain: Arc<Chain>) -> Self {
+        Self { chain }
+    }
+
+    /// The ordinal of the last block of the chain when the snapshot was taken
+    pub fn height(&self) -> Ordinal {
+        self.chain
+            .blocks
+            .last()
+            .map(|block| block.header.ordinal)
+            .unwrap_or_default()
+    }
+}
+
+impl Deref for ChainSnapshot {
+    type Target = Chain;
+
+    fn deref(&self) -> &Chain {
+        &self.chain
+    }
 }
 
 impl Chain {
+    /// A chain holding only `genesis`, the state every chain of a blockchain starts from,
+    /// unlike the empty `Default` chain
+    pub fn genesis_only(genesis: Block) -> Self {
+        Self {
+            blocks: vec![genesis],
+            checkpoint: None,
+        }
+    }
+
     pub fn blocks(&self) -> Vec<Block> {
         self.blocks.clone()
     }
 
+    /// Iterate over the blocks of the chain in ordinal order, without cloning them
+    pub fn iter(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.iter()
+    }
+
     pub fn len(&self) -> usize {
         self.blocks.len()
     }
@@ -42,8 +191,396 @@ impl Chain {
     }
 
     pub fn last_block(&self) -> Option<Block> {
-        self.blocks().last().cloned()
+        self.blocks.last().cloned()
+    }
+
+    /// The number of transactions across all blocks of the chain
+    pub fn transaction_count(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|block| block.transactions.len())
+            .sum()
+    }
+
+    /// Whether a block of the chain carries the transaction with `hash`. The transactions of
+    /// pruned blocks are not found.
+    pub fn contains_transaction(&self, hash: &HashDigest) -> bool {
+        self.get_transaction(hash).is_some()
+    }
+
+    /// The transaction with `hash` from the block of the chain carrying it. The transactions
+    /// of pruned blocks are not found.
+    pub fn get_transaction(&self, hash: &HashDigest) -> Option<&Transaction> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .find(|trans| trans.hash() == *hash)
+    }
+
+    /// The median timestamp of the last `n` blocks of the chain, the "median time past" the
+    /// timestamp of the next block must be above. None when `n` is 0.
+    pub fn median_time_past(&self, n: usize) -> Option<u64> {
+        median_time(
+            self.blocks
+                .iter()
+                .skip(self.blocks.len().saturating_sub(n))
+                .map(|block| block.header.timestamp),
+        )
+    }
+
+    /// The sum of the difficulty of the blocks of the chain, which fork choice compares chains
+    /// by. Blocks committed by authorities have difficulty 1, so for them it is the length of
+    /// the chain. Blocks removed by pruning do not count.
+    pub fn total_difficulty(&self) -> u128 {
+        self.difficulty_from(0)
+    }
+
+    /// The sum of the difficulty of the blocks with an ordinal of at least `ordinal`
+    pub fn difficulty_from(&self, ordinal: Ordinal) -> u128 {
+        self.blocks
+            .iter()
+            .filter(|block| block.header.ordinal >= ordinal)
+            .map(|block| block.header.difficulty())
+            .sum()
+    }
+
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        self.checkpoint
+    }
+
+    /// Remove all blocks, except genesis, preceding the last `keep_last` blocks and return them.
+    /// The tip is always kept, a `keep_last` of 0 keeps it like 1 does. The last removed block
+    /// is remembered as the checkpoint so the linkage of the retained blocks can still be
+    /// verified.
+    pub fn prune(&mut self, keep_last: u128) -> Vec<Block> {
+        let tip_ordinal = match self.blocks.last() {
+            Some(block) => block.header.ordinal,
+            None => return vec![],
+        };
+        let cutoff = tip_ordinal
+            .saturating_add(1)
+            .saturating_sub(keep_last.max(1));
+        let (pruned, retained): (Vec<Block>, Vec<Block>) = self
+            .blocks
+            .drain(..)
+            .partition(|block| block.header.ordinal != 0 && block.header.ordinal < cutoff);
+        self.blocks = retained;
+
+        if let Some(last_pruned) = pruned.last() {
+            self.checkpoint = Some(Checkpoint {
+                ordinal: last_pruned.header.ordinal,
+                hash: last_pruned.header.hash(),
+            });
+        }
+        pruned
+    }
+
+    /// Remove the blocks following the block at `ordinal` and return them in chain order
+    pub fn truncate(&mut self, ordinal: Ordinal) -> Vec<Block> {
+        let kept = self
+            .blocks
+            .partition_point(|block| block.header.ordinal <= ordinal);
+        self.blocks.split_off(kept)
+    }
+
+    /// Check that every block follows its predecessor, by ordinal and parent hash. A gap in
+    /// the ordinals is only accepted when it is bridged by the pruning checkpoint.
+    pub fn verify_linkage(&self) -> bool {
+        let _span = debug_span!("verify_linkage", blocks = self.blocks.len()).entered();
+        self.first_broken_link().is_none()
+    }
+
+    // The ordinal of the first block which does not link to its predecessor
+    fn first_broken_link(&self) -> Option<Ordinal> {
+        self.first_broken_pair().map(|(_, child)| child.ordinal)
+    }
+
+    // The headers of the first block which does not link to its predecessor and of that
+    // predecessor
+    fn first_broken_pair(&self) -> Option<(&Header, &Header)> {
+        self.blocks
+            .windows(2)
+            .map(|pair| (&pair[0].header, &pair[1].header))
+            .find(|(parent, child)| !self.links(parent, child))
+    }
+
+    // Whether `child` follows `parent`, directly or through the pruning checkpoint
+    fn links(&self, parent: &Header, child: &Header) -> bool {
+        if child.ordinal == parent.ordinal + 1 {
+            return child.parent_hash == parent.hash();
+        }
+        match self.checkpoint {
+            Some(checkpoint) => {
+                parent.ordinal < checkpoint.ordinal
+                    && child.ordinal == checkpoint.ordinal + 1
+                    && child.parent_hash == checkpoint.hash
+            }
+            None => false,
+        }
+    }
+
+    /// Check the chain is consistent on its own: it starts with a genesis block with a zero
+    /// parent hash, every other block follows its predecessor, by ordinal and parent hash, and
+    /// every block passes `Block::verify`. Unlike `validate_chain`, whether the committers are
+    /// authorities is not checked. A gap in the ordinals is only accepted when it is bridged by
+    /// the pruning checkpoint.
+    pub fn verify_integrity(&self) -> Result<(), ChainError> {
+        let _span = debug_span!("verify_integrity", blocks = self.blocks.len()).entered();
+        let genesis = self.blocks.first().ok_or(ChainError::Empty)?;
+        if genesis.header.ordinal != 0 {
+            return Err(ChainError::UnexpectedOrdinal {
+                expected: 0,
+                actual: genesis.header.ordinal,
+            });
+        }
+        if genesis.header.parent_hash != HashDigest::zero() {
+            return Err(ChainError::GenesisParentNotZero(genesis.header.parent_hash));
+        }
+        if let Some((parent, child)) = self.first_broken_pair() {
+            // the ordinals of a link bridged by the checkpoint are not consecutive
+            let bridged = self.checkpoint.map_or(false, |checkpoint| {
+                parent.ordinal < checkpoint.ordinal && child.ordinal == checkpoint.ordinal + 1
+            });
+            if child.ordinal != parent.ordinal + 1 && !bridged {
+                return Err(ChainError::UnexpectedOrdinal {
+                    expected: parent.ordinal + 1,
+                    actual: child.ordinal,
+                });
+            }
+            return Err(ChainError::BrokenLinkage(child.ordinal));
+        }
+        for block in self.blocks.iter() {
+            block.verify().map_err(|error| ChainError::InvalidBlock {
+                ordinal: block.header.ordinal,
+                error,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// The ordinals of the blocks whose transactions do not match the transactions_hash of
+    /// their header. Only the Merkle roots are recomputed, no signature is verified, so it is
+    /// a cheap first integrity pass over a chain, e.g. right after importing it.
+    pub fn check_transaction_roots(&self) -> Vec<Ordinal> {
+        self.blocks
+            .iter()
+            .filter(|block| !block.verify_transactions_root())
+            .map(|block| block.header.ordinal)
+            .collect()
+    }
+
+    /// Check the blocks link to each other and their headers pass `Block::verify_header_only`,
+    /// leaving the transactions unverified. This is a weaker check than `validate_chain`, which
+    /// must still follow before the chain is trusted.
+    pub fn validate_headers_only(&self) -> Result<(), ChainError> {
+        if self.blocks.is_empty() {
+            return Err(ChainError::Empty);
+        }
+        if let Some(ordinal) = self.first_broken_link() {
+            return Err(ChainError::BrokenLinkage(ordinal));
+        }
+        for block in self.blocks.iter() {
+            let ordinal = block.header.ordinal;
+            block.verify_header_only().map_err(|e| match e {
+                BlockError::HeaderHashMismatch => ChainError::HeaderHashMismatch(ordinal),
+                _ => ChainError::InvalidBlockSignature(ordinal),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Verify every block of the chain, rather than stopping at the first invalid one like
+    /// `validate_chain` does, and report the outcome of each block
+    pub fn verify_all(&self) -> Vec<BlockVerification> {
+        let _span = debug_span!("verify_all", blocks = self.blocks.len()).entered();
+        let mut authorities = HashSet::new();
+        let mut revocations = Revocations::new();
+        let mut parent: Option<&Header> = None;
+        let mut verifications = Vec::with_capacity(self.blocks.len());
+        for block in self.blocks.iter() {
+            let ordinal = block.header.ordinal;
+            let result = match parent {
+                Some(parent) if !self.links(parent, &block.header) => {
+                    Err(ChainError::BrokenLinkage(ordinal))
+                }
+                _ => check_block(block)
+                    .and_then(|_| check_committer(&authorities, block))
+                    .and_then(|_| check_revocations(&revocations, block)),
+            };
+            apply_authority_transactions(&mut authorities, block);
+            apply_revocations(&mut revocations, block);
+            parent = Some(&block.header);
+            verifications.push(BlockVerification {
+                ordinal,
+                hash: block.header.hash(),
+                result,
+            });
+        }
+        verifications
+    }
+
+    pub fn get_block_by_hash(&self, hash: &HashDigest) -> Option<&Block> {
+        self.blocks
+            .iter()
+            .find(|block| block.header.hash() == *hash)
     }
+
+    /// Look up a block by ordinal, returning `None` when it is out of range. The chain may not
+    /// start at ordinal 0 once it has been pruned, so the ordinal is first used as an offset from
+    /// the first block and otherwise searched for within the sorted blocks.
+    pub fn get_block_by_ordinal(&self, ordinal: Ordinal) -> Option<&Block> {
+        let first_ordinal = self.blocks.first()?.header.ordinal;
+        let index = usize::try_from(ordinal.checked_sub(first_ordinal)?).ok()?;
+        match self.blocks.get(index) {
+            Some(block) if block.header.ordinal == ordinal => Some(block),
+            _ => self
+                .blocks
+                .binary_search_by(|block| block.header.ordinal.cmp(&ordinal))
+                .ok()
+                .map(|index| &self.blocks[index]),
+        }
+    }
+
+    /// The last block both chains share, by ordinal and hash, walking back from the tip. None
+    /// when they do not even share their genesis block. Blocks link to their parent by hash,
+    /// so the chains share every block before it too.
+    pub fn common_ancestor(&self, other: &Chain) -> Option<(Ordinal, HashDigest)> {
+        self.blocks
+            .iter()
+            .rev()
+            .find(|block| {
+                other
+                    .get_block_by_ordinal(block.header.ordinal)
+                    .map(|theirs| theirs.header.hash() == block.header.hash())
+                    .unwrap_or(false)
+            })
+            .map(|block| (block.header.ordinal, block.header.hash()))
+    }
+
+    /// The blocks of this chain and of `other` following their common ancestor, see
+    /// `common_ancestor`, every block of both chains when they have none
+    pub fn divergence<'a>(&'a self, other: &'a Chain) -> (Vec<&'a Block>, Vec<&'a Block>) {
+        let ancestor = self.common_ancestor(other).map(|(ordinal, _)| ordinal);
+        let unique = |chain: &'a Chain| -> Vec<&'a Block> {
+            chain
+                .iter()
+                .filter(|block| ancestor.map_or(true, |ordinal| block.header.ordinal > ordinal))
+                .collect()
+        };
+        (unique(self), unique(other))
+    }
+
+    /// The blocks with an ordinal within `from_ordinal..=to_ordinal`, clamped to the blocks
+    /// available
+    pub fn blocks_in_range(&self, from_ordinal: Ordinal, to_ordinal: Ordinal) -> Vec<&Block> {
+        let start = self
+            .blocks
+            .partition_point(|block| block.header.ordinal < from_ordinal);
+        let end = self
+            .blocks
+            .partition_point(|block| block.header.ordinal <= to_ordinal);
+        if start >= end {
+            return vec![];
+        }
+        self.blocks[start..end].iter().collect()
+    }
+}
+
+/// The median of `timestamps`, the later of the two middle ones when there is an even number
+/// of them. None when there are none.
+pub fn median_time(timestamps: impl Iterator<Item = u64>) -> Option<u64> {
+    let mut timestamps: Vec<u64> = timestamps.collect();
+    timestamps.sort_unstable();
+    timestamps.get(timestamps.len() / 2).copied()
+}
+
+/// Validate `chain` without side effects: it must start with `expected_genesis`, every block
+/// must link to its parent, be signed by its committer, carry valid transactions and be
+/// committed by an authority of the chain at that point.
+pub fn validate_chain(chain: &Chain, expected_genesis: &Block) -> Result<ChainStats, ChainError> {
+    let genesis = chain.blocks.first().ok_or(ChainError::Empty)?;
+    if genesis != expected_genesis {
+        return Err(ChainError::GenesisMismatch {
+            expected: expected_genesis.header.hash(),
+            actual: genesis.header.hash(),
+        });
+    }
+    if let Some(ordinal) = chain.first_broken_link() {
+        return Err(ChainError::BrokenLinkage(ordinal));
+    }
+
+    let mut authorities = HashSet::new();
+    let mut revocations = Revocations::new();
+    let mut transactions = 0;
+    for block in chain.blocks.iter() {
+        check_block(block)?;
+        // the authority set as of the block, before its own transactions change it
+        check_committer(&authorities, block)?;
+        check_revocations(&revocations, block)?;
+        apply_authority_transactions(&mut authorities, block);
+        apply_revocations(&mut revocations, block);
+        transactions += block.transactions.len();
+    }
+
+    Ok(ChainStats {
+        height: chain
+            .blocks
+            .last()
+            .map(|block| block.header.ordinal)
+            .unwrap_or_default(),
+        transactions,
+        authorities: authorities.len(),
+    })
+}
+
+// Check `block` is signed by its committer and carries valid transactions
+fn check_block(block: &Block) -> Result<(), ChainError> {
+    let ordinal = block.header.ordinal;
+    if !block.verify_signature() {
+        return Err(ChainError::InvalidBlockSignature(ordinal));
+    }
+    if !block.verify_transactions_root() {
+        return Err(ChainError::TransactionsRootMismatch(ordinal));
+    }
+    if let Some((submitter, nonce)) = block.find_duplicate_nonce() {
+        return Err(ChainError::DuplicateNonce {
+            ordinal,
+            submitter,
+            nonce,
+        });
+    }
+    for trans in block.transactions.iter() {
+        trans
+            .verify()
+            .map_err(|error| ChainError::InvalidTransaction { ordinal, error })?;
+    }
+    Ok(())
+}
+
+// Check `block` is committed by one of `authorities`, genesis being exempt
+fn check_committer(authorities: &HashSet<Address>, block: &Block) -> Result<(), ChainError> {
+    if !block.is_committed_by(authorities) {
+        return Err(ChainError::UnauthorizedCommitter {
+            ordinal: block.header.ordinal,
+            committer: block.header.committer,
+        });
+    }
+    Ok(())
+}
+
+// Check neither `block` nor its transactions were signed with a key once it was revoked
+fn check_revocations(revocations: &Revocations, block: &Block) -> Result<(), ChainError> {
+    let ordinal = block.header.ordinal;
+    let committer = block.header.committer;
+    if revoked_at(revocations, &committer, block.header.timestamp).is_some() {
+        return Err(ChainError::RevokedCommitter { ordinal, committer });
+    }
+    for trans in block.transactions.iter() {
+        trans
+            .verify_not_revoked(revocations)
+            .map_err(|error| ChainError::InvalidTransaction { ordinal, error })?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -51,9 +588,13 @@ mod tests {
     use crate::{
         crypto::hash_algorithm::HashDigest,
         structures::{
-            block::Block,
-            chain::Chain,
+            block::{Block, BlockError},
+            chain::{
+                median_time, validate_chain, BlockVerification, Chain, ChainError, ChainStats,
+                Checkpoint,
+            },
             header::Address,
+            state::replay_authorities,
             transaction::{Transaction, TransactionType},
         },
     };
@@ -73,10 +614,11 @@ mod tests {
             local_id,
             data.as_bytes().to_vec(),
             &keypair,
-        );
+        )
+        .unwrap();
         transactions.push(transaction);
         assert_eq!(0, chain.len());
-        let block = Block::new(HashDigest::new(b""), 0, transactions, &keypair);
+        let block = Block::new(HashDigest::new(b""), 0, transactions, &keypair).unwrap();
         chain.add_block(block);
         assert_eq!(1, chain.len());
         Ok(())
@@ -96,7 +638,7 @@ mod tests {
         let mut chain: Chain = Default::default();
         let keypair = identity::ed25519::Keypair::generate();
         let transactions = vec![];
-        let block = Block::new(HashDigest::new(b""), 0, transactions, &keypair);
+        let block = Block::new(HashDigest::new(b""), 0, transactions, &keypair).unwrap();
         chain.add_block(block);
         assert_eq!(false, chain.is_empty());
 
@@ -108,7 +650,7 @@ mod tests {
         let mut chain: Chain = Default::default();
         let keypair = identity::ed25519::Keypair::generate();
         let transactions = vec![];
-        let block = Block::new(HashDigest::new(b""), 0, transactions, &keypair);
+        let block = Block::new(HashDigest::new(b""), 0, transactions, &keypair).unwrap();
         chain.add_block(block);
         assert_eq!(chain.len(), chain.blocks().len());
 
@@ -120,7 +662,7 @@ mod tests {
         let mut chain: Chain = Default::default();
         let keypair = identity::ed25519::Keypair::generate();
         let transactions = vec![];
-        let block = Block::new(HashDigest::new(b""), 0, transactions, &keypair);
+        let block = Block::new(HashDigest::new(b""), 0, transactions, &keypair).unwrap();
         chain.add_block(block.clone());
         chain.add_block(block);
         assert_eq!(2, chain.len());
@@ -128,16 +670,533 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_genesis_only() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let genesis = Block::new(HashDigest::new(b""), 0, vec![], &keypair).unwrap();
+        let mut chain = Chain::genesis_only(genesis.clone());
+        assert_eq!(1, chain.len());
+        assert!(!chain.is_empty());
+        assert_eq!(Some(genesis.clone()), chain.last_block());
+
+        chain.add_block(Block::new(genesis.header.hash(), 1, vec![], &keypair).unwrap());
+        assert_eq!(2, chain.len());
+        assert!(validate_chain(&chain, &genesis).is_ok());
+
+        Ok(())
+    }
+
     #[test]
     fn test_get_last_block() -> Result<(), String> {
         let mut chain: Chain = Default::default();
         assert_eq!(None, chain.last_block());
         let keypair = identity::ed25519::Keypair::generate();
         let transactions = vec![];
-        let block = Block::new(HashDigest::new(b""), 0, transactions, &keypair);
+        let block = Block::new(HashDigest::new(b""), 0, transactions, &keypair).unwrap();
         chain.add_block(block.clone());
         assert_eq!(block, chain.last_block().unwrap());
 
         Ok(())
     }
+
+    #[test]
+    fn test_get_block_by_ordinal_without_genesis() -> Result<(), String> {
+        let mut chain: Chain = Default::default();
+        let keypair = identity::ed25519::Keypair::generate();
+        for ordinal in 5..8 {
+            chain.add_block(Block::new(HashDigest::new(b""), ordinal, vec![], &keypair).unwrap());
+        }
+
+        assert_eq!(None, chain.get_block_by_ordinal(0));
+        assert_eq!(None, chain.get_block_by_ordinal(4));
+        assert_eq!(5, chain.get_block_by_ordinal(5).unwrap().header.ordinal);
+        assert_eq!(7, chain.get_block_by_ordinal(7).unwrap().header.ordinal);
+        assert_eq!(None, chain.get_block_by_ordinal(8));
+        assert_eq!(None, chain.get_block_by_ordinal(u128::MAX));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune() -> Result<(), String> {
+        let mut chain: Chain = Default::default();
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut parent_hash = HashDigest::new(b"");
+        for ordinal in 0..6 {
+            let block = Block::new(parent_hash, ordinal, vec![], &keypair).unwrap();
+            parent_hash = block.header.hash();
+            chain.add_block(block);
+        }
+        let pruned_tip = chain.get_block_by_ordinal(3).unwrap().header.hash();
+
+        let pruned = chain.prune(2);
+
+        assert_eq!(
+            vec![1, 2, 3],
+            pruned
+                .iter()
+                .map(|block| block.header.ordinal)
+                .collect::<Vec<u128>>()
+        );
+        assert_eq!(3, chain.len());
+        assert!(chain.get_block_by_ordinal(0).is_some());
+        assert_eq!(None, chain.get_block_by_ordinal(3));
+        assert_eq!(4, chain.get_block_by_ordinal(4).unwrap().header.ordinal);
+        assert_eq!(5, chain.get_block_by_ordinal(5).unwrap().header.ordinal);
+        assert_eq!(
+            Some(Checkpoint {
+                ordinal: 3,
+                hash: pruned_tip
+            }),
+            chain.checkpoint()
+        );
+        assert!(chain.verify_linkage());
+
+        // the tip is kept even when no block is to be kept
+        let tip = chain.get_block_by_ordinal(5).unwrap().header.hash();
+        assert_eq!(1, chain.prune(0).len());
+        assert_eq!(2, chain.len());
+        assert_eq!(tip, chain.last_block().unwrap().header.hash());
+        assert!(chain.verify_linkage());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_linkage_rejects_gap() -> Result<(), String> {
+        let mut chain: Chain = Default::default();
+        let keypair = identity::ed25519::Keypair::generate();
+        let genesis = Block::new(HashDigest::new(b""), 0, vec![], &keypair).unwrap();
+        let orphan = Block::new(genesis.header.hash(), 2, vec![], &keypair).unwrap();
+        chain.add_block(genesis);
+        chain.add_block(orphan);
+
+        assert!(!chain.verify_linkage());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocks_in_range() -> Result<(), String> {
+        let mut chain: Chain = Default::default();
+        let keypair = identity::ed25519::Keypair::generate();
+        for ordinal in 0..5 {
+            chain.add_block(Block::new(HashDigest::new(b""), ordinal, vec![], &keypair).unwrap());
+        }
+        let ordinals = |blocks: Vec<&Block>| -> Vec<u128> {
+            blocks.iter().map(|block| block.header.ordinal).collect()
+        };
+
+        assert_eq!(vec![1, 2, 3], ordinals(chain.blocks_in_range(1, 3)));
+        assert_eq!(vec![3, 4], ordinals(chain.blocks_in_range(3, 100)));
+        assert_eq!(
+            vec![0, 1, 2, 3, 4],
+            ordinals(chain.blocks_in_range(0, u128::MAX))
+        );
+        assert!(chain.blocks_in_range(3, 1).is_empty());
+        assert!(chain.blocks_in_range(5, 10).is_empty());
+
+        Ok(())
+    }
+
+    fn build_chain(keypair: &identity::ed25519::Keypair, length: u128) -> Chain {
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut chain: Chain = Default::default();
+        let mut parent_hash = HashDigest::new(b"");
+        for ordinal in 0..length {
+            let transaction = Transaction::new(
+                TransactionType::Create,
+                local_id,
+                format!("Transaction {}", ordinal).into_bytes(),
+                keypair,
+            )
+            .unwrap();
+            let block = Block::new(parent_hash, ordinal, vec![transaction], keypair).unwrap();
+            parent_hash = block.header.hash();
+            chain.add_block(block);
+        }
+        chain
+    }
+
+    #[test]
+    fn test_verify_integrity() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let genesis = Block::new(HashDigest::zero(), 0, vec![], &keypair).unwrap();
+        let block_1 = Block::new(genesis.header.hash(), 1, vec![], &keypair).unwrap();
+        let block_2 = Block::new(block_1.header.hash(), 2, vec![], &keypair).unwrap();
+        let chain_of = |blocks: &[&Block]| {
+            let mut chain = Chain::default();
+            for block in blocks {
+                chain.add_block((*block).clone());
+            }
+            chain
+        };
+
+        chain_of(&[&genesis, &block_1, &block_2])
+            .verify_integrity()
+            .map_err(|e| e.to_string())?;
+        assert_eq!(Err(ChainError::Empty), Chain::default().verify_integrity());
+
+        let unlinked = Block::new(HashDigest::new(b"unlinked"), 2, vec![], &keypair).unwrap();
+        assert_eq!(
+            Err(ChainError::BrokenLinkage(2)),
+            chain_of(&[&genesis, &block_1, &unlinked]).verify_integrity()
+        );
+        assert_eq!(
+            Err(ChainError::UnexpectedOrdinal {
+                expected: 1,
+                actual: 2
+            }),
+            chain_of(&[&genesis, &block_2]).verify_integrity()
+        );
+
+        let mut tampered = block_2.clone();
+        tampered.header.timestamp += 1;
+        assert_eq!(
+            Err(ChainError::InvalidBlock {
+                ordinal: 2,
+                error: BlockError::HeaderHashMismatch
+            }),
+            chain_of(&[&genesis, &block_1, &tampered]).verify_integrity()
+        );
+
+        let orphan_genesis = Block::new(HashDigest::new(b"parent"), 0, vec![], &keypair).unwrap();
+        assert_eq!(
+            Err(ChainError::GenesisParentNotZero(HashDigest::new(b"parent"))),
+            Chain::genesis_only(orphan_genesis).verify_integrity()
+        );
+
+        let mut pruned = chain_of(&[&genesis, &block_1, &block_2]);
+        pruned.prune(1);
+        pruned.verify_integrity().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_median_time_past() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut chain = Chain::genesis_only(
+            Block::new_with(HashDigest::zero(), 0, vec![], &keypair, 1_000, 0).unwrap(),
+        );
+        for (ordinal, timestamp) in [(1, 1_030), (2, 1_010), (3, 1_020)] {
+            let parent_hash = chain.last_block().unwrap().header.hash();
+            chain.add_block(
+                Block::new_with(parent_hash, ordinal, vec![], &keypair, timestamp, 0).unwrap(),
+            );
+        }
+
+        assert_eq!(None, chain.median_time_past(0));
+        assert_eq!(Some(1_020), chain.median_time_past(1));
+        assert_eq!(Some(1_020), chain.median_time_past(3));
+        assert_eq!(Some(1_020), chain.median_time_past(4));
+        assert_eq!(Some(1_020), chain.median_time_past(11));
+        assert_eq!(Some(3), median_time([4, 1, 3, 2].into_iter()));
+        assert_eq!(None, median_time(std::iter::empty()));
+    }
+
+    #[test]
+    fn test_common_ancestor() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let chain = build_chain(&keypair, 5);
+        let tip = chain.last_block().ok_or("empty chain")?;
+
+        // fully shared
+        let shared = chain.clone();
+        assert_eq!(Some((4, tip.header.hash())), chain.common_ancestor(&shared));
+        assert_eq!(
+            (Vec::<&Block>::new(), Vec::<&Block>::new()),
+            chain.divergence(&shared)
+        );
+
+        // one chain is ahead of the other
+        let mut behind = chain.clone();
+        behind.truncate(2);
+        let fork_hash = chain.get_block_by_ordinal(2).unwrap().header.hash();
+        assert_eq!(Some((2, fork_hash)), chain.common_ancestor(&behind));
+        assert_eq!(Some((2, fork_hash)), behind.common_ancestor(&chain));
+        let (ours, theirs) = chain.divergence(&behind);
+        assert_eq!(chain.blocks_in_range(3, 4), ours);
+        assert!(theirs.is_empty());
+
+        // forked at 2
+        let mut forked = behind.clone();
+        let mut parent_hash = fork_hash;
+        for ordinal in 3..6 {
+            let block = Block::new(parent_hash, ordinal, vec![], &keypair).unwrap();
+            parent_hash = block.header.hash();
+            forked.add_block(block);
+        }
+        assert_eq!(Some((2, fork_hash)), chain.common_ancestor(&forked));
+        let (ours, theirs) = chain.divergence(&forked);
+        assert_eq!(chain.blocks_in_range(3, 4), ours);
+        assert_eq!(forked.blocks_in_range(3, 5), theirs);
+
+        // disjoint
+        let disjoint = build_chain(&keypair, 3);
+        assert_eq!(None, chain.common_ancestor(&disjoint));
+        let (ours, theirs) = chain.divergence(&disjoint);
+        assert_eq!(5, ours.len());
+        assert_eq!(3, theirs.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_chain() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let chain = build_chain(&keypair, 4);
+        let genesis = chain.get_block_by_ordinal(0).unwrap().clone();
+
+        assert_eq!(
+            Ok(ChainStats {
+                height: 3,
+                transactions: 4,
+                authorities: 1
+            }),
+            validate_chain(&chain, &genesis)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_total_difficulty() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut chain = build_chain(&keypair, 4);
+        assert_eq!(4, chain.total_difficulty());
+
+        let tip = chain.last_block().unwrap();
+        chain.add_block(
+            Block::new_with_difficulty(
+                tip.header.hash(),
+                4,
+                vec![],
+                &keypair,
+                tip.header.timestamp,
+                3,
+            )
+            .unwrap(),
+        );
+        assert_eq!(7, chain.total_difficulty());
+        assert_eq!(4, chain.difficulty_from(3));
+    }
+
+    #[test]
+    fn test_chain_error_ordinal() {
+        assert_eq!(None, ChainError::Empty.ordinal());
+        assert_eq!(Some(3), ChainError::BrokenLinkage(3).ordinal());
+        assert_eq!(
+            Some(0),
+            ChainError::GenesisMismatch {
+                expected: HashDigest::new(b"ours"),
+                actual: HashDigest::new(b"theirs")
+            }
+            .ordinal()
+        );
+    }
+
+    #[test]
+    fn test_validate_chain_wrong_genesis() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let chain = build_chain(&keypair, 2);
+        let other_genesis = Block::new(HashDigest::new(b""), 0, vec![], &keypair).unwrap();
+
+        assert_eq!(
+            Err(ChainError::GenesisMismatch {
+                expected: other_genesis.header.hash(),
+                actual: chain.get_block_by_ordinal(0).unwrap().header.hash()
+            }),
+            validate_chain(&chain, &other_genesis)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_chain_broken_linkage() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut chain = build_chain(&keypair, 3);
+        let genesis = chain.get_block_by_ordinal(0).unwrap().clone();
+        chain.add_block(Block::new(HashDigest::new(b"unrelated"), 3, vec![], &keypair).unwrap());
+
+        assert_eq!(
+            Err(ChainError::BrokenLinkage(3)),
+            validate_chain(&chain, &genesis)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_chain_unauthorized_committer() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut chain = build_chain(&keypair, 2);
+        let genesis = chain.get_block_by_ordinal(0).unwrap().clone();
+        let parent_hash = chain.last_block().unwrap().header.hash();
+        let intruder = identity::ed25519::Keypair::generate();
+        chain.add_block(Block::new(parent_hash, 2, vec![], &intruder).unwrap());
+
+        assert_eq!(
+            Err(ChainError::UnauthorizedCommitter {
+                ordinal: 2,
+                committer: Address::from(identity::PublicKey::Ed25519(intruder.public()))
+            }),
+            validate_chain(&chain, &genesis)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_chain_removed_authority() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let other = identity::ed25519::Keypair::generate();
+        let other_id = Address::from(identity::PublicKey::Ed25519(other.public()));
+        let authority_transaction = |type_id| {
+            Transaction::new(type_id, local_id, other_id.to_bytes(), &keypair)
+                .map_err(|e| e.to_string())
+        };
+
+        // the other authority is added by block 1, commits block 2, is removed by block 3 and
+        // still commits block 4
+        let mut blocks = vec![Block::new(HashDigest::new(b""), 0, vec![], &keypair).unwrap()];
+        for (ordinal, transactions, committer) in [
+            (
+                1,
+                vec![authority_transaction(TransactionType::AddAuthority)?],
+                &keypair,
+            ),
+            (2, vec![], &other),
+            (
+                3,
+                vec![authority_transaction(TransactionType::RemoveAuthority)?],
+                &keypair,
+            ),
+            (4, vec![], &other),
+        ] {
+            let parent_hash = blocks.last().unwrap().header.hash();
+            blocks.push(Block::new(parent_hash, ordinal, transactions, committer).unwrap());
+        }
+
+        assert_eq!(
+            Ok(()),
+            blocks[2].verify_with_authorities(&replay_authorities(&blocks[..2]))
+        );
+        assert_eq!(
+            Err(BlockError::UnauthorizedCommitter(other_id)),
+            blocks[4].verify_with_authorities(&replay_authorities(&blocks[..4]))
+        );
+        // against the latest authority set the older block would be rejected as well
+        assert_eq!(
+            Err(BlockError::UnauthorizedCommitter(other_id)),
+            blocks[2].verify_with_authorities(&replay_authorities(&blocks))
+        );
+
+        let mut chain: Chain = Default::default();
+        for block in blocks[..4].iter() {
+            chain.add_block(block.clone());
+        }
+        assert!(validate_chain(&chain, &blocks[0]).is_ok());
+        chain.add_block(blocks[4].clone());
+        assert_eq!(
+            Err(ChainError::UnauthorizedCommitter {
+                ordinal: 4,
+                committer: other_id
+            }),
+            validate_chain(&chain, &blocks[0])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_transaction_roots() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let chain = build_chain(&keypair, 6);
+        assert!(chain.check_transaction_roots().is_empty());
+
+        let mut blocks = chain.blocks();
+        blocks[4].transactions = blocks[3].transactions.clone();
+        let mut tampered: Chain = Default::default();
+        for block in blocks {
+            tampered.add_block(block);
+        }
+        assert_eq!(vec![4], tampered.check_transaction_roots());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_headers_only() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let chain = build_chain(&keypair, 4);
+        let genesis = chain.get_block_by_ordinal(0).unwrap().clone();
+        assert_eq!(Ok(()), chain.validate_headers_only());
+
+        // the transactions of block 2 are not the ones its header commits to, which only the
+        // full validation notices
+        let mut blocks = chain.blocks();
+        blocks[2].transactions = blocks[1].transactions.clone();
+        assert_eq!(Ok(()), blocks[2].verify_header_only());
+        let mut unverified: Chain = Default::default();
+        for block in blocks.iter() {
+            unverified.add_block(block.clone());
+        }
+        assert_eq!(Ok(()), unverified.validate_headers_only());
+        assert_eq!(
+            Err(ChainError::TransactionsRootMismatch(2)),
+            validate_chain(&unverified, &genesis)
+        );
+
+        let intruder = identity::ed25519::Keypair::generate();
+        blocks[3].header.committer = Address::from(identity::PublicKey::Ed25519(intruder.public()));
+        let mut forged: Chain = Default::default();
+        for block in blocks {
+            forged.add_block(block);
+        }
+        assert_eq!(
+            Err(ChainError::HeaderHashMismatch(3)),
+            forged.validate_headers_only()
+        );
+        assert_eq!(
+            Err(ChainError::Empty),
+            Chain::default().validate_headers_only()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_all() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let intruder = identity::ed25519::Keypair::generate();
+        let mut blocks = build_chain(&keypair, 8).blocks();
+        blocks[2].transactions = build_chain(&keypair, 1).blocks()[0].transactions.clone();
+        blocks[5].header.committer = Address::from(identity::PublicKey::Ed25519(intruder.public()));
+        let mut chain: Chain = Default::default();
+        for block in blocks.iter() {
+            chain.add_block(block.clone());
+        }
+
+        let verifications = chain.verify_all();
+
+        assert_eq!(8, verifications.len());
+        let failures: Vec<&BlockVerification> = verifications
+            .iter()
+            .filter(|verification| verification.result.is_err())
+            .collect();
+        assert_eq!(
+            vec![
+                &BlockVerification {
+                    ordinal: 2,
+                    hash: blocks[2].header.hash(),
+                    result: Err(ChainError::TransactionsRootMismatch(2))
+                },
+                &BlockVerification {
+                    ordinal: 5,
+                    hash: blocks[5].header.hash(),
+                    result: Err(ChainError::InvalidBlockSignature(5))
+                },
+            ],
+            failures
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,132 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use libp2p::identity;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use super::block::{Block, BlockError};
+use super::header::Address;
+use super::transaction::{Transaction, TransactionBuilder, TransactionError, TransactionType};
+use crate::crypto::hash_algorithm::HashDigest;
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum GenesisError {
+    #[error("Failed to read the authority key {path}: {reason}")]
+    UnreadableAuthorityKey { path: PathBuf, reason: String },
+    #[error("The authority key {0} is not a protobuf encoded public key")]
+    InvalidAuthorityKey(PathBuf),
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
+    #[error(transparent)]
+    Block(#[from] BlockError),
+}
+
+/// Read the address of an authority from the file of its protobuf encoded public key
+pub fn read_authority(path: &Path) -> Result<Address, GenesisError> {
+    let bytes = fs::read(path).map_err(|e| GenesisError::UnreadableAuthorityKey {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    let public_key = identity::PublicKey::from_protobuf_encoding(&bytes)
+        .map_err(|_| GenesisError::InvalidAuthorityKey(path.to_path_buf()))?;
+    Ok(Address::from(public_key))
+}
+
+/// Read the addresses of the authorities from the files of their public keys, as given by
+/// the `initial_authorities` of the node arguments
+pub fn read_authorities(paths: &[PathBuf]) -> Result<Vec<Address>, GenesisError> {
+    paths.iter().map(|path| read_authority(path)).collect()
+}
+
+/// Generate the genesis block of a network, committed by `keypair` and carrying one
+/// `AddAuthority` transaction per address of `authorities`, salted with `network_salt` (empty
+/// for a network without an id). The block only depends on its inputs, so every operator
+/// generating it from the same inputs gets the same genesis.
+pub fn generate_genesis(
+    keypair: &identity::ed25519::Keypair,
+    authorities: Vec<Address>,
+    network_salt: Vec<u8>,
+    timestamp: u64,
+    nonce: u128,
+) -> Result<Block, GenesisError> {
+    let transactions = authorities
+        .iter()
+        .enumerate()
+        .map(|(index, authority)| {
+            TransactionBuilder::new(TransactionType::AddAuthority)
+                .payload(authority.to_bytes())
+                .timestamp(timestamp)
+                // every transaction of the committer needs its own nonce
+                .nonce(nonce.wrapping_add(index as u128 + 1))
+                .network_salt(network_salt.clone())
+                .sign(keypair)
+        })
+        .collect::<Result<Vec<Transaction>, TransactionError>>()?;
+
+    Ok(Block::new_with(
+        HashDigest::zero(),
+        0,
+        transactions,
+        keypair,
+        timestamp,
+        nonce,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structures::state::replay_authorities;
+    use crate::structures::transaction::network_salt;
+
+    #[test]
+    fn test_generate_genesis() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let authorities: Vec<Address> = (0..3)
+            .map(|_| Address::from(identity::Keypair::generate_ed25519().public()))
+            .collect();
+
+        let genesis = generate_genesis(&keypair, authorities.clone(), vec![], 1_660_000_000, 42)
+            .map_err(|e| e.to_string())?;
+        let again = generate_genesis(&keypair, authorities.clone(), vec![], 1_660_000_000, 42)
+            .map_err(|e| e.to_string())?;
+
+        assert_eq!(
+            serde_json::to_vec(&genesis).map_err(|e| e.to_string())?,
+            serde_json::to_vec(&again).map_err(|e| e.to_string())?
+        );
+        assert_eq!(Ok(()), genesis.verify());
+        assert!(genesis.is_valid_genesis());
+        assert_eq!(0, genesis.header.ordinal);
+        assert_eq!(4, replay_authorities(vec![&genesis]).len());
+
+        let salted = generate_genesis(
+            &keypair,
+            authorities,
+            network_salt("alpha"),
+            1_660_000_000,
+            42,
+        )
+        .map_err(|e| e.to_string())?;
+        assert_ne!(genesis.header.hash(), salted.header.hash());
+        for trans in salted.transactions.iter() {
+            assert_eq!(Ok(()), trans.verify_network(&network_salt("alpha")));
+        }
+        Ok(())
+    }
+}
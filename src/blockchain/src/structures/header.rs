@@ -31,6 +31,47 @@ pub struct Address {
     peer_id: Multihash,
 }
 
+impl Address {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.peer_id.to_bytes()
+    }
+
+    /// Recover the public key the address was derived from. Only keys small enough to be
+    /// inlined in the peer id, like Ed25519 keys, can be recovered.
+    pub fn public_key(&self) -> Option<identity::PublicKey> {
+        // the identity multihash code, the digest of which is the encoded public key itself
+        const IDENTITY_CODE: u64 = 0x00;
+        if self.peer_id.code() != IDENTITY_CODE {
+            return None;
+        }
+        identity::PublicKey::from_protobuf_encoding(self.peer_id.digest()).ok()
+    }
+
+    /// The libp2p peer id of the address, None when the address does not hold a valid peer id
+    pub fn to_peer_id(&self) -> Option<PeerId> {
+        PeerId::from_multihash(self.peer_id).ok()
+    }
+
+    /// The address of `peer_id`, once checked it is the peer id of `public_key`, e.g. to look
+    /// up the authority a gossip peer stands for from the key it presented
+    pub fn from_peer_id(peer_id: PeerId, public_key: &identity::PublicKey) -> Option<Address> {
+        if PeerId::from_public_key(public_key) != peer_id {
+            return None;
+        }
+        Some(Address::from(peer_id))
+    }
+}
+
+impl TryFrom<&[u8]> for Address {
+    type Error = multihash::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Self {
+            peer_id: Multihash::from_bytes(bytes)?,
+        })
+    }
+}
+
 impl From<identity::PublicKey> for Address {
     fn from(key: identity::PublicKey) -> Address {
         Self {
@@ -49,6 +90,17 @@ impl From<PeerId> for Address {
 
 pub type Ordinal = u128;
 
+// the difficulty of the blocks committed by authorities
+const AUTHORITY_DIFFICULTY: u128 = 1;
+
+fn default_difficulty() -> u128 {
+    AUTHORITY_DIFFICULTY
+}
+
+fn is_authority_difficulty(difficulty: &u128) -> bool {
+    *difficulty == AUTHORITY_DIFFICULTY
+}
+
 // this struct exists only for generating a hash
 #[derive(Serialize)]
 struct PartialHeader {
@@ -58,6 +110,13 @@ struct PartialHeader {
     timestamp: u64,
     ordinal: Ordinal,
     nonce: u128,
+    // left out of the hash at the default difficulty, so the hash of the headers built before
+    // headers carried a difficulty does not change
+    #[serde(skip_serializing_if = "is_authority_difficulty")]
+    difficulty: u128,
+    // left out of the hash when the block has no extensions, for the same reason
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extensions_hash: Option<HashDigest>,
 }
 
 impl From<Header> for PartialHeader {
@@ -69,6 +128,8 @@ impl From<Header> for PartialHeader {
             timestamp: header.timestamp,
             ordinal: header.ordinal,
             nonce: header.nonce,
+            difficulty: header.difficulty,
+            extensions_hash: header.extensions_hash,
         }
     }
 }
@@ -86,7 +147,7 @@ pub struct Header {
     /// [block]: crate::structures::block::Block
     /// [hash]: crate::structures::header::Header::hash
     pub parent_hash: HashDigest,
-    /// 256-bit Keccak Hash of the [`Block`][block]'s [`transactions`][transactions]
+    /// Merkle root of the hashes of the [`Block`][block]'s [`transactions`][transactions]
     ///
     /// [block]: crate::structures::block::Block
     /// [transactions]: crate::structures::block::Block::transactions
@@ -99,6 +160,16 @@ pub struct Header {
     pub ordinal: Ordinal,
     /// Adds a salt to harden
     nonce: u128,
+    /// The difficulty target the block was built for, always 1 for the blocks committed by
+    /// authorities, so that the total difficulty of their chain is its length
+    #[serde(default = "default_difficulty")]
+    difficulty: u128,
+    /// The hash of the extensions of the [`Block`][block], None when it has none, so that
+    /// the block signature covers them
+    ///
+    /// [block]: crate::structures::block::Block
+    #[serde(default)]
+    extensions_hash: Option<HashDigest>,
     /// The block id, 256-bit Keccak Hash of the Current Block Header, excluding itself
     hash: HashDigest,
 }
@@ -110,16 +181,57 @@ impl Header {
         committer: Address,
         ordinal: u128,
     ) -> Self {
-        let partial = PartialHeader {
+        Self::new_with(
             parent_hash,
             transactions_hash,
             committer,
-            timestamp: SystemTime::now()
+            ordinal,
+            SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            rand::thread_rng().gen::<u128>(),
+        )
+    }
+
+    /// Build a header with the given timestamp and nonce, so that its hash is reproducible
+    pub fn new_with(
+        parent_hash: HashDigest,
+        transactions_hash: HashDigest,
+        committer: Address,
+        ordinal: u128,
+        timestamp: u64,
+        nonce: u128,
+    ) -> Self {
+        Self::new_with_difficulty(
+            parent_hash,
+            transactions_hash,
+            committer,
+            ordinal,
+            timestamp,
+            nonce,
+            AUTHORITY_DIFFICULTY,
+        )
+    }
+
+    pub fn new_with_difficulty(
+        parent_hash: HashDigest,
+        transactions_hash: HashDigest,
+        committer: Address,
+        ordinal: u128,
+        timestamp: u64,
+        nonce: u128,
+        difficulty: u128,
+    ) -> Self {
+        let partial = PartialHeader {
+            parent_hash,
+            transactions_hash,
+            committer,
+            timestamp,
             ordinal,
-            nonce: rand::thread_rng().gen::<u128>(),
+            nonce,
+            difficulty,
+            extensions_hash: None,
         };
         Self {
             parent_hash: partial.parent_hash,
@@ -128,13 +240,44 @@ impl Header {
             timestamp: partial.timestamp,
             ordinal: partial.ordinal,
             nonce: partial.nonce,
+            difficulty: partial.difficulty,
+            extensions_hash: partial.extensions_hash,
+            hash: calculate_hash(&partial).unwrap(),
+        }
+    }
+
+    /// The same header committing to the extensions with `extensions_hash`, and so with
+    /// another hash
+    pub fn with_extensions_hash(&self, extensions_hash: Option<HashDigest>) -> Self {
+        let partial = PartialHeader {
+            extensions_hash,
+            ..PartialHeader::from(*self)
+        };
+        Self {
+            extensions_hash,
             hash: calculate_hash(&partial).unwrap(),
+            ..*self
         }
     }
 
     pub fn hash(&self) -> HashDigest {
         self.hash
     }
+
+    pub fn difficulty(&self) -> u128 {
+        self.difficulty
+    }
+
+    pub fn extensions_hash(&self) -> Option<HashDigest> {
+        self.extensions_hash
+    }
+
+    /// Check the hash of the header was computed from its current content
+    pub fn verify_hash(&self) -> bool {
+        calculate_hash(&PartialHeader::from(*self))
+            .map(|hash| hash == self.hash)
+            .unwrap_or(false)
+    }
 }
 
 #[cfg(test)]
@@ -155,4 +298,92 @@ mod tests {
         assert_eq!(5, header.ordinal);
         assert_eq!(expected_hash, header.hash());
     }
+
+    #[test]
+    fn test_verify_hash() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut header = Header::new(HashDigest::new(b""), HashDigest::new(b""), local_id, 5);
+        assert!(header.verify_hash());
+
+        header.ordinal = 6;
+        assert!(!header.verify_hash());
+    }
+
+    #[test]
+    fn test_header_hash_fixture() {
+        // The hash stored in the fixture was computed when the fixture was created, recomputing
+        // it guards against any change to the hashing or serialization of headers. The fixture
+        // is an arbitrary header, not a genesis header: its parent hash is not zero and its
+        // committer is made up.
+        let header: Header =
+            serde_json::from_str(include_str!("../../tests/resources/header.json")).unwrap();
+
+        let partial: PartialHeader = header.into();
+        assert_eq!(header.hash(), calculate_hash(&partial).unwrap());
+        assert_eq!(
+            "22726c69106e3fddaca3b799138cdc6567f773774184ba36afd3123b4b7ddd93",
+            hex::encode(header.hash().to_slice())
+        );
+    }
+
+    #[test]
+    fn test_header_difficulty() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let header = Header::new_with(
+            HashDigest::new(b""),
+            HashDigest::new(b""),
+            local_id,
+            5,
+            1656633600,
+            42,
+        );
+        let harder = Header::new_with_difficulty(
+            HashDigest::new(b""),
+            HashDigest::new(b""),
+            local_id,
+            5,
+            1656633600,
+            42,
+            2,
+        );
+
+        assert_eq!(1, header.difficulty());
+        assert_eq!(2, harder.difficulty());
+        assert_ne!(header.hash(), harder.hash());
+        assert!(harder.verify_hash());
+
+        // read back as a JSON value, serde_json can not serialize u128 fields into one
+        let mut json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&header).unwrap()).unwrap();
+        json.as_object_mut().unwrap().remove("difficulty");
+        assert_eq!(header, serde_json::from_value(json).unwrap());
+    }
+
+    #[test]
+    fn test_address_peer_id() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let peer_id = PeerId::from(keypair.public());
+        let address = Address::from(keypair.public());
+
+        assert_eq!(Some(peer_id), address.to_peer_id());
+        assert_eq!(
+            Some(address),
+            Address::from_peer_id(peer_id, &keypair.public())
+        );
+        assert_eq!(
+            None,
+            Address::from_peer_id(peer_id, &identity::Keypair::generate_ed25519().public())
+        );
+        assert_eq!(Some(address), address.to_peer_id().map(Address::from));
+    }
+
+    #[test]
+    fn test_address_public_key() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let address = Address::from(keypair.public());
+
+        assert_eq!(Some(keypair.public()), address.public_key());
+    }
 }
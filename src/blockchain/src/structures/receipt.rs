@@ -0,0 +1,66 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use serde::{Deserialize, Serialize};
+
+use super::block::{verify_header_signature, BlockSignature};
+use super::chain::Checkpoint;
+use super::header::Header;
+use crate::crypto::hash_algorithm::HashDigest;
+use crate::crypto::merkle::{verify_inclusion, MerkleProof};
+
+/// Proof that a transaction is part of a block of a chain, which a light client can verify
+/// knowing only a checkpoint of the chain it trusts, e.g. the finalized block, see
+/// `Blockchain::receipt_for`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    pub tx_hash: HashDigest,
+    /// The header of the block carrying the transaction
+    pub header: Header,
+    /// The signature of the block by its committer
+    pub signature: BlockSignature,
+    /// The path from the transaction to the transactions_hash of the header
+    pub proof: MerkleProof,
+    /// The headers of the blocks following the block up to the checkpoint, linking the block
+    /// to the checkpoint
+    pub descendants: Vec<Header>,
+}
+
+impl Receipt {
+    /// Check the block of the receipt carries the transaction, is signed by its committer and
+    /// is the block of `checkpoint` or one of its ancestors. Blocks link to their parent by
+    /// hash, so a trusted checkpoint vouches for the blocks before it, without the headers
+    /// back to genesis nor the authority set of the time.
+    pub fn verify(&self, checkpoint: &Checkpoint) -> bool {
+        if !verify_inclusion(&self.tx_hash, &self.proof, &self.header.transactions_hash)
+            || !self.header.verify_hash()
+            || !verify_header_signature(&self.header, &self.signature)
+        {
+            return false;
+        }
+        let mut parent = &self.header;
+        for header in self.descendants.iter() {
+            if header.ordinal != parent.ordinal + 1
+                || header.parent_hash != parent.hash()
+                || !header.verify_hash()
+            {
+                return false;
+            }
+            parent = header;
+        }
+        parent.ordinal == checkpoint.ordinal && parent.hash() == checkpoint.hash
+    }
+}
@@ -0,0 +1,218 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use super::block::Block;
+use super::header::{Address, Ordinal};
+use super::transaction::TransactionType;
+use crate::crypto::hash_algorithm::HashDigest;
+
+/// The authority set derived from the chain, together with the tip it reflects
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StateSnapshot {
+    pub authorities: HashSet<Address>,
+    pub tip_hash: HashDigest,
+    pub tip_ordinal: Ordinal,
+    #[serde(default)]
+    pub revocations: Revocations,
+}
+
+/// The revoked keys, by address, with the timestamp from which each is revoked
+pub type Revocations = HashMap<Address, u64>;
+
+/// Apply the `AddAuthority` and `RemoveAuthority` transactions of `block` to `authorities`.
+/// The committer of the genesis block is the initial authority.
+pub fn apply_authority_transactions(authorities: &mut HashSet<Address>, block: &Block) {
+    if block.header.ordinal == 0 {
+        authorities.insert(block.header.committer);
+    }
+    for trans in block.transactions.iter() {
+        let type_id = trans.type_id();
+        if type_id != TransactionType::AddAuthority && type_id != TransactionType::RemoveAuthority {
+            continue;
+        }
+        let authority = match Address::try_from(trans.payload().as_slice()) {
+            Ok(authority) => authority,
+            Err(e) => {
+                warn!(
+                    "Ignoring {:?} transaction with an invalid address: {}",
+                    type_id, e
+                );
+                continue;
+            }
+        };
+        if type_id == TransactionType::AddAuthority {
+            authorities.insert(authority);
+        } else {
+            authorities.remove(&authority);
+        }
+    }
+}
+
+/// The authority whose turn it is to commit the block at `ordinal`, rotating over the
+/// authorities sorted by address so every node agrees on the order
+pub fn round_robin_committer(authorities: &HashSet<Address>, ordinal: Ordinal) -> Option<Address> {
+    if authorities.is_empty() {
+        return None;
+    }
+    let mut sorted: Vec<&Address> = authorities.iter().collect();
+    sorted.sort_by_key(|authority| authority.to_bytes());
+    let turn = (ordinal % sorted.len() as Ordinal) as usize;
+    Some(*sorted[turn])
+}
+
+/// Record the keys revoked by the `RevokeKey` transactions of `block` in `revocations`. A key
+/// is revoked from the timestamp of the earliest transaction revoking it.
+pub fn apply_revocations(revocations: &mut Revocations, block: &Block) {
+    for trans in block.transactions.iter() {
+        if trans.type_id() != TransactionType::RevokeKey {
+            continue;
+        }
+        let revoked = match Address::try_from(trans.payload().as_slice()) {
+            Ok(revoked) => revoked,
+            Err(e) => {
+                warn!(
+                    "Ignoring RevokeKey transaction with an invalid address: {}",
+                    e
+                );
+                continue;
+            }
+        };
+        revocations
+            .entry(revoked)
+            .and_modify(|revoked_at| *revoked_at = (*revoked_at).min(trans.timestamp()))
+            .or_insert_with(|| trans.timestamp());
+    }
+}
+
+/// The timestamp the key of `address` is revoked from, when it is revoked by `timestamp`
+pub fn revoked_at(revocations: &Revocations, address: &Address, timestamp: u64) -> Option<u64> {
+    revocations
+        .get(address)
+        .copied()
+        .filter(|revoked_at| timestamp >= *revoked_at)
+}
+
+/// Derive the revoked keys by replaying `blocks` in order
+pub fn replay_revocations<'a>(blocks: impl IntoIterator<Item = &'a Block>) -> Revocations {
+    let mut revocations = Revocations::new();
+    for block in blocks {
+        apply_revocations(&mut revocations, block);
+    }
+    revocations
+}
+
+/// The authorities permitted to commit the block at `ordinal`, `elapsed` seconds after its
+/// parent. Every `committer_timeout` seconds the turn is extended to the next authority in
+/// the rotation, so an offline committer does not stall the chain, while the authorities
+/// whose turn it was stay permitted. Without a timeout only the round-robin committer is.
+pub fn permitted_committers(
+    authorities: &HashSet<Address>,
+    ordinal: Ordinal,
+    elapsed: u64,
+    committer_timeout: Option<u64>,
+) -> Vec<Address> {
+    let skipped = match committer_timeout {
+        Some(timeout) if timeout > 0 => elapsed / timeout,
+        _ => 0,
+    };
+    // past a full rotation every authority is permitted already
+    let skipped = skipped.min(authorities.len().saturating_sub(1) as u64) as Ordinal;
+    (0..=skipped)
+        .filter_map(|offset| round_robin_committer(authorities, ordinal.saturating_add(offset)))
+        .collect()
+}
+
+/// Derive the authority set by replaying `blocks` in order
+pub fn replay_authorities<'a>(blocks: impl IntoIterator<Item = &'a Block>) -> HashSet<Address> {
+    let mut authorities = HashSet::new();
+    for block in blocks {
+        apply_authority_transactions(&mut authorities, block);
+    }
+    authorities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structures::transaction::Transaction;
+    use libp2p::identity;
+
+    #[test]
+    fn test_replay_authorities() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let first = Address::from(identity::Keypair::generate_ed25519().public());
+        let second = Address::from(identity::Keypair::generate_ed25519().public());
+
+        let add = Block::new(
+            HashDigest::new(b""),
+            0,
+            vec![
+                Transaction::new(
+                    TransactionType::AddAuthority,
+                    local_id,
+                    first.to_bytes(),
+                    &keypair,
+                )
+                .unwrap(),
+                Transaction::new(
+                    TransactionType::AddAuthority,
+                    local_id,
+                    second.to_bytes(),
+                    &keypair,
+                )
+                .unwrap(),
+                Transaction::new(
+                    TransactionType::AddAuthority,
+                    local_id,
+                    b"not an address".to_vec(),
+                    &keypair,
+                )
+                .unwrap(),
+            ],
+            &keypair,
+        )
+        .unwrap();
+        let remove = Block::new(
+            add.header.hash(),
+            1,
+            vec![Transaction::new(
+                TransactionType::RemoveAuthority,
+                local_id,
+                first.to_bytes(),
+                &keypair,
+            )
+            .unwrap()],
+            &keypair,
+        )
+        .unwrap();
+
+        assert_eq!(
+            HashSet::from([local_id, first, second]),
+            replay_authorities(vec![&add])
+        );
+        assert_eq!(
+            HashSet::from([local_id, second]),
+            replay_authorities(vec![&add, &remove])
+        );
+
+        Ok(())
+    }
+}
@@ -19,14 +19,52 @@ use libp2p::identity;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
 
 use super::header::Address;
-use crate::crypto::hash_algorithm::HashDigest;
-use crate::signature::Signature;
+use super::state::{revoked_at, Revocations};
+use crate::crypto::hash_algorithm::{canonical_json, HashDigest};
+use crate::signature::{Signature, SignatureDomain, Signer, SigningError};
+
+/// The weight of verifying a signature, counted like hashing as many bytes of payload
+pub const SIGNATURE_WEIGHT: u64 = 1024;
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum TransactionError {
+    #[error("Submitter {submitter:?} is not the address of the signing key {signer:?}")]
+    SubmitterMismatch { submitter: Address, signer: Address },
+    #[error("The public key of submitter {0:?} can not be recovered from its address")]
+    UnknownSubmitterKey(Address),
+    #[error("The signature was not made by submitter {0:?}")]
+    InvalidSignature(Address),
+    #[error("Failed to serialize the transaction: {0}")]
+    Serialization(String),
+    #[error("The hash {0} of the transaction does not match its content")]
+    HashMismatch(HashDigest),
+    #[error("The transaction {0} was built for another network")]
+    NetworkMismatch(HashDigest),
+    #[error("The key of submitter {submitter:?} is revoked since {revoked_at}")]
+    RevokedKey { submitter: Address, revoked_at: u64 },
+    #[error(transparent)]
+    Signing(#[from] SigningError),
+}
+
+impl From<bincode::Error> for TransactionError {
+    fn from(err: bincode::Error) -> TransactionError {
+        TransactionError::Serialization(err.to_string())
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq, Copy, Decode, Encode)]
 pub enum TransactionType {
     Create,
+    /// Add the address carried in the payload to the authority set
+    AddAuthority,
+    /// Remove the address carried in the payload from the authority set
+    RemoveAuthority,
+    /// Revoke the key of the address carried in the payload, what it signs from the timestamp
+    /// of the transaction on is rejected
+    RevokeKey,
 }
 
 // Temporary structure to be able to calculate the hash of a transaction
@@ -37,13 +75,37 @@ struct PartialTransaction {
     timestamp: u64,
     payload: Vec<u8>,
     nonce: u128,
+    priority: u64,
+    // left out of the hash when empty, so the hash of the transactions built before
+    // transactions carried dependencies does not change
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    depends_on: Vec<HashDigest>,
+    // left out of the hash when empty, like for the transactions built before transactions
+    // were scoped to a network
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    network_salt: Vec<u8>,
+}
+
+/// The salt scoping the transactions to the network `network_id`, which makes the hash, and
+/// so the signature, of a transaction differ from one network to another
+pub fn network_salt(network_id: &str) -> Vec<u8> {
+    HashDigest::new(network_id.as_bytes()).to_slice().to_vec()
 }
 
 impl PartialTransaction {
-    fn convert_to_transaction(
-        self,
-        ed25519_keypair: &identity::ed25519::Keypair,
-    ) -> Result<Transaction, bincode::Error> {
+    // Sign the transaction with `signer`, which must sign with the key of the submitter
+    fn sign(self, signer: &dyn Signer) -> Result<Transaction, TransactionError> {
+        let address = Address::from(identity::PublicKey::Ed25519(signer.public_key()));
+        if self.submitter != address {
+            return Err(TransactionError::SubmitterMismatch {
+                submitter: self.submitter,
+                signer: address,
+            });
+        }
+        self.convert_to_transaction(signer)
+    }
+
+    fn convert_to_transaction(self, signer: &dyn Signer) -> Result<Transaction, TransactionError> {
         let hash = calculate_hash(&self)?;
         Ok(Transaction {
             type_id: self.type_id,
@@ -51,8 +113,15 @@ impl PartialTransaction {
             timestamp: self.timestamp,
             payload: self.payload,
             nonce: self.nonce,
+            priority: self.priority,
+            depends_on: self.depends_on,
+            network_salt: self.network_salt,
             hash,
-            signature: Signature::new(&bincode::serialize(&hash)?, ed25519_keypair),
+            signature: Signature::new_in(
+                SignatureDomain::Transaction,
+                &bincode::serialize(&hash)?,
+                signer,
+            )?,
         })
     }
 }
@@ -65,6 +134,9 @@ impl From<Transaction> for PartialTransaction {
             timestamp: transaction.timestamp,
             payload: transaction.payload,
             nonce: transaction.nonce,
+            priority: transaction.priority,
+            depends_on: transaction.depends_on,
+            network_salt: transaction.network_salt,
         }
     }
 }
@@ -78,6 +150,108 @@ fn calculate_hash(
 
 pub type TransactionSignature = Signature;
 
+/// Build a transaction field by field. The submitter defaults to the address of the signing
+/// key, the timestamp to the current time and the nonce to a random one.
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    type_id: TransactionType,
+    submitter: Option<Address>,
+    payload: Vec<u8>,
+    priority: u64,
+    depends_on: Vec<HashDigest>,
+    timestamp: Option<u64>,
+    nonce: Option<u128>,
+    network_salt: Vec<u8>,
+}
+
+impl TransactionBuilder {
+    pub fn new(type_id: TransactionType) -> Self {
+        Self {
+            type_id,
+            submitter: None,
+            payload: vec![],
+            priority: 0,
+            depends_on: vec![],
+            timestamp: None,
+            nonce: None,
+            network_salt: vec![],
+        }
+    }
+
+    pub fn submitter(&mut self, submitter: Address) -> &mut Self {
+        self.submitter = Some(submitter);
+        self
+    }
+
+    pub fn payload(&mut self, payload: Vec<u8>) -> &mut Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Set the payload to `payload` serialized as canonical JSON, see `canonical_json`. The
+    /// hash of a transaction covers the bytes of its payload, so nodes building the same
+    /// logical JSON payload get the same transaction hash only when it is canonical.
+    pub fn json_payload(&mut self, payload: &serde_json::Value) -> &mut Self {
+        self.payload = canonical_json(payload);
+        self
+    }
+
+    /// See `Transaction::new_with_priority`
+    pub fn priority(&mut self, priority: u64) -> &mut Self {
+        self.priority = priority;
+        self
+    }
+
+    /// See `Transaction::new_with_dependencies`
+    pub fn depends_on(&mut self, depends_on: Vec<HashDigest>) -> &mut Self {
+        self.depends_on = depends_on;
+        self
+    }
+
+    /// Unix timestamp in seconds
+    pub fn timestamp(&mut self, timestamp: u64) -> &mut Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn nonce(&mut self, nonce: u128) -> &mut Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    /// Scope the transaction to a network, see `network_salt`. Empty by default, for the
+    /// transactions of a chain without a network id.
+    pub fn network_salt(&mut self, network_salt: Vec<u8>) -> &mut Self {
+        self.network_salt = network_salt;
+        self
+    }
+
+    /// Build the transaction signed by `signer`, which must sign with the key of the
+    /// submitter. With an explicit timestamp and nonce its hash is reproducible.
+    pub fn sign(&self, signer: &dyn Signer) -> Result<Transaction, TransactionError> {
+        PartialTransaction {
+            type_id: self.type_id,
+            submitter: self.submitter.unwrap_or_else(|| {
+                Address::from(identity::PublicKey::Ed25519(signer.public_key()))
+            }),
+            timestamp: self.timestamp.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+            }),
+            payload: self.payload.clone(),
+            nonce: self
+                .nonce
+                .unwrap_or_else(|| rand::thread_rng().gen::<u128>()),
+            priority: self.priority,
+            depends_on: self.depends_on.clone(),
+            network_salt: self.network_salt.clone(),
+        }
+        .sign(signer)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq, Decode, Encode)]
 pub struct Transaction {
     type_id: TransactionType,
@@ -85,35 +259,168 @@ pub struct Transaction {
     timestamp: u64,
     payload: Vec<u8>,
     nonce: u128, // Adds a salt to harden
+    // Transactions with a higher priority are included in blocks first
+    #[serde(default)]
+    priority: u64,
+    // The hashes of the transactions which must be settled before this one may be included
+    // in a block
+    #[serde(default)]
+    depends_on: Vec<HashDigest>,
+    // Scopes the transaction to a network, see `network_salt`
+    #[serde(default)]
+    network_salt: Vec<u8>,
     hash: HashDigest,
     signature: TransactionSignature,
 }
 impl Transaction {
+    /// Build a transaction signed by `signer`, which must sign with the key of `submitter`
     pub fn new(
         type_id: TransactionType,
         submitter: Address,
         payload: Vec<u8>,
-        ed25519_keypair: &identity::ed25519::Keypair,
-    ) -> Self {
-        let partial_transaction = PartialTransaction {
-            type_id,
-            submitter,
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            payload,
-            nonce: rand::thread_rng().gen::<u128>(),
+        signer: &dyn Signer,
+    ) -> Result<Self, TransactionError> {
+        Self::new_with_priority(type_id, submitter, payload, 0, signer)
+    }
+
+    /// Build a transaction like `new`, which is included in blocks ahead of the pending
+    /// transactions of a lower `priority`
+    pub fn new_with_priority(
+        type_id: TransactionType,
+        submitter: Address,
+        payload: Vec<u8>,
+        priority: u64,
+        signer: &dyn Signer,
+    ) -> Result<Self, TransactionError> {
+        TransactionBuilder::new(type_id)
+            .submitter(submitter)
+            .payload(payload)
+            .priority(priority)
+            .sign(signer)
+    }
+
+    /// Build a transaction like `new`, which may only be included in a block once every
+    /// transaction of `depends_on` is settled in the chain, e.g. the publication of a version
+    /// of a package depending on the publication of the previous version
+    pub fn new_with_dependencies(
+        type_id: TransactionType,
+        submitter: Address,
+        payload: Vec<u8>,
+        depends_on: Vec<HashDigest>,
+        signer: &dyn Signer,
+    ) -> Result<Self, TransactionError> {
+        TransactionBuilder::new(type_id)
+            .submitter(submitter)
+            .payload(payload)
+            .depends_on(depends_on)
+            .sign(signer)
+    }
+
+    /// Build a transaction with the given timestamp and nonce, so that its hash is reproducible
+    pub fn new_with(
+        type_id: TransactionType,
+        submitter: Address,
+        payload: Vec<u8>,
+        timestamp: u64,
+        nonce: u128,
+        signer: &dyn Signer,
+    ) -> Result<Self, TransactionError> {
+        TransactionBuilder::new(type_id)
+            .submitter(submitter)
+            .payload(payload)
+            .timestamp(timestamp)
+            .nonce(nonce)
+            .sign(signer)
+    }
+
+    /// Check the hash of the transaction was computed from its current content and the
+    /// transaction was signed by the key of its submitter
+    pub fn verify(&self) -> Result<(), TransactionError> {
+        if calculate_hash(&PartialTransaction::from(self.clone()))? != self.hash {
+            return Err(TransactionError::HashMismatch(self.hash));
+        }
+        self.verify_submitter()
+    }
+
+    /// Check the transaction passes `verify` and was not signed once the key of its submitter
+    /// was revoked. The transactions timestamped before the revocation remain valid.
+    pub fn verify_with_revocations(
+        &self,
+        revocations: &Revocations,
+    ) -> Result<(), TransactionError> {
+        self.verify()?;
+        self.verify_not_revoked(revocations)
+    }
+
+    /// Check the transaction is timestamped before the revocation of the key of its submitter,
+    /// if it is revoked
+    pub fn verify_not_revoked(&self, revocations: &Revocations) -> Result<(), TransactionError> {
+        match revoked_at(revocations, &self.submitter, self.timestamp) {
+            Some(revoked_at) => Err(TransactionError::RevokedKey {
+                submitter: self.submitter,
+                revoked_at,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Check the transaction was signed by the key of its submitter
+    pub fn verify_submitter(&self) -> Result<(), TransactionError> {
+        let public_key = match self.submitter.public_key() {
+            Some(identity::PublicKey::Ed25519(public_key)) => public_key,
+            _ => return Err(TransactionError::UnknownSubmitterKey(self.submitter)),
         };
-        partial_transaction
-            .convert_to_transaction(ed25519_keypair)
-            .unwrap()
+        if self.signature.verify_in(
+            SignatureDomain::Transaction,
+            &bincode::serialize(&self.hash)?,
+            &public_key,
+        ) {
+            Ok(())
+        } else {
+            Err(TransactionError::InvalidSignature(self.submitter))
+        }
+    }
+
+    pub fn submitter(&self) -> Address {
+        self.submitter
+    }
+
+    pub fn nonce(&self) -> u128 {
+        self.nonce
+    }
+
+    pub fn priority(&self) -> u64 {
+        self.priority
+    }
+
+    pub fn depends_on(&self) -> &[HashDigest] {
+        &self.depends_on
+    }
+
+    pub fn network_salt(&self) -> &[u8] {
+        &self.network_salt
+    }
+
+    /// Check the transaction was built for the network scoped by `network_salt`
+    pub fn verify_network(&self, network_salt: &[u8]) -> Result<(), TransactionError> {
+        if self.network_salt != network_salt {
+            return Err(TransactionError::NetworkMismatch(self.hash));
+        }
+        Ok(())
+    }
+
+    pub fn type_id(&self) -> TransactionType {
+        self.type_id
     }
 
     pub fn hash(&self) -> HashDigest {
         self.hash
     }
 
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
     pub fn signature(&self) -> TransactionSignature {
         self.signature.clone()
     }
@@ -121,6 +428,17 @@ impl Transaction {
     pub fn payload(&self) -> Vec<u8> {
         self.payload.clone()
     }
+
+    /// The size of the payload in bytes, without cloning it like `payload` does
+    pub fn payload_len(&self) -> usize {
+        self.payload.len()
+    }
+
+    /// The cost of verifying the transaction, its signature and the bytes of its payload
+    /// hashed along the way
+    pub fn weight(&self) -> u64 {
+        SIGNATURE_WEIGHT + self.payload.len() as u64
+    }
 }
 
 #[cfg(test)]
@@ -137,16 +455,193 @@ mod tests {
             local_id,
             b"Hello First Transaction".to_vec(),
             &keypair,
-        );
+        )
+        .unwrap();
         let partial: PartialTransaction = transaction.clone().into();
         let expected_hash = calculate_hash(&partial).unwrap();
-        let expected_signature =
-            Signature::new(&bincode::serialize(&expected_hash).unwrap(), &keypair);
+        let expected_signature = Signature::new_in(
+            SignatureDomain::Transaction,
+            &bincode::serialize(&expected_hash).unwrap(),
+            &keypair,
+        )
+        .unwrap();
 
         assert_eq!(expected_hash, transaction.hash());
         assert_eq!(expected_signature, transaction.signature());
     }
 
+    #[test]
+    fn test_transaction_priority() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+
+        let transaction = Transaction::new_with_priority(
+            TransactionType::Create,
+            local_id,
+            b"Hello Urgent Transaction".to_vec(),
+            7,
+            &keypair,
+        )
+        .unwrap();
+        let mut partial: PartialTransaction = transaction.clone().into();
+        assert_eq!(7, transaction.priority());
+        assert_eq!(calculate_hash(&partial).unwrap(), transaction.hash());
+
+        // the priority is signed along with the rest of the transaction
+        partial.priority = 0;
+        assert_ne!(calculate_hash(&partial).unwrap(), transaction.hash());
+    }
+
+    #[test]
+    fn test_transaction_dependencies() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let dependency = HashDigest::new(b"Hello Version 1");
+
+        let transaction = Transaction::new_with_dependencies(
+            TransactionType::Create,
+            local_id,
+            b"Hello Version 2".to_vec(),
+            vec![dependency],
+            &keypair,
+        )
+        .unwrap();
+        assert_eq!(&[dependency], transaction.depends_on());
+        assert_eq!(Ok(()), transaction.verify());
+
+        // the dependencies are signed along with the rest of the transaction
+        let mut tampered = transaction.clone();
+        tampered.depends_on.clear();
+        assert_eq!(
+            Err(TransactionError::HashMismatch(transaction.hash())),
+            tampered.verify()
+        );
+
+        // serde_json can not serialize the u128 nonce into a JSON value, so the field is cut
+        // out of the JSON text
+        let json = serde_json::to_string(&tampered).unwrap();
+        assert!(json.contains("\"depends_on\":[],"));
+        let without_dependencies: Transaction =
+            serde_json::from_str(&json.replace("\"depends_on\":[],", "")).unwrap();
+        assert!(without_dependencies.depends_on().is_empty());
+    }
+
+    #[test]
+    fn test_transaction_builder() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let dependency = HashDigest::new(b"Hello Version 1");
+
+        let mut builder = TransactionBuilder::new(TransactionType::Create);
+        builder
+            .payload(b"Hello Version 2".to_vec())
+            .priority(3)
+            .depends_on(vec![dependency])
+            .timestamp(1656633600)
+            .nonce(42);
+        let transaction = builder.sign(&keypair).unwrap();
+
+        assert_eq!(local_id, transaction.submitter());
+        assert_eq!(1656633600, transaction.timestamp());
+        assert_eq!(42, transaction.nonce());
+        assert_eq!(3, transaction.priority());
+        assert_eq!(&[dependency], transaction.depends_on());
+        assert_eq!(Ok(()), transaction.verify());
+        // an explicit timestamp and nonce make the hash reproducible
+        assert_eq!(transaction.hash(), builder.sign(&keypair).unwrap().hash());
+        assert_ne!(
+            transaction.hash(),
+            builder.nonce(43).sign(&keypair).unwrap().hash()
+        );
+
+        let other = Address::from(identity::PublicKey::Ed25519(
+            identity::ed25519::Keypair::generate().public(),
+        ));
+        assert_eq!(
+            Err(TransactionError::SubmitterMismatch {
+                submitter: other,
+                signer: local_id
+            }),
+            builder.submitter(other).sign(&keypair)
+        );
+    }
+
+    #[test]
+    fn test_json_payload() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let texts = [
+            r#"{"package":{"name":"alpine","version":"1.0"},"tags":["latest"]}"#,
+            r#"{ "tags": ["latest"], "package": { "version": "1.0", "name": "alpine" } }"#,
+        ];
+        let sign = |builder: &mut TransactionBuilder| {
+            builder
+                .timestamp(1656633600)
+                .nonce(42)
+                .sign(&keypair)
+                .map_err(|e| e.to_string())
+        };
+
+        let mut hashes = vec![];
+        for text in texts {
+            let value: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+            let transaction =
+                sign(TransactionBuilder::new(TransactionType::Create).json_payload(&value))?;
+            assert_eq!(texts[0].as_bytes().to_vec(), transaction.payload());
+            hashes.push(transaction.hash());
+        }
+        assert_eq!(hashes[0], hashes[1]);
+
+        // the raw bytes of the same payload in another key order hash differently
+        let raw = sign(
+            TransactionBuilder::new(TransactionType::Create).payload(texts[1].as_bytes().to_vec()),
+        )?;
+        assert_ne!(hashes[0], raw.hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_network_salt() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let mut builder = TransactionBuilder::new(TransactionType::Create);
+        builder
+            .payload(b"Hello Network".to_vec())
+            .timestamp(1656633600)
+            .nonce(42);
+        let unscoped = builder.sign(&keypair).unwrap();
+        let alpha = builder
+            .network_salt(network_salt("alpha"))
+            .sign(&keypair)
+            .unwrap();
+        let beta = builder
+            .network_salt(network_salt("beta"))
+            .sign(&keypair)
+            .unwrap();
+
+        assert_ne!(alpha.hash(), beta.hash());
+        assert_ne!(unscoped.hash(), alpha.hash());
+        assert_eq!(Ok(()), alpha.verify());
+        assert_eq!(Ok(()), alpha.verify_network(&network_salt("alpha")));
+        assert_eq!(
+            Err(TransactionError::NetworkMismatch(alpha.hash())),
+            alpha.verify_network(&network_salt("beta"))
+        );
+        // an empty salt leaves the hash as it was before transactions were scoped
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        assert_eq!(
+            Transaction::new_with(
+                TransactionType::Create,
+                local_id,
+                b"Hello Network".to_vec(),
+                1656633600,
+                42,
+                &keypair
+            )
+            .unwrap()
+            .hash(),
+            unscoped.hash()
+        );
+    }
+
     #[test]
     fn test_payload() {
         let keypair = identity::ed25519::Keypair::generate();
@@ -157,8 +652,151 @@ mod tests {
             local_id,
             b"Hello First Transaction".to_vec(),
             &keypair,
-        );
+        )
+        .unwrap();
 
         assert_eq!(b"Hello First Transaction".to_vec(), transaction.payload());
     }
+
+    #[test]
+    fn test_transaction_submitter_mismatch() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let other_id = Address::from(identity::Keypair::generate_ed25519().public());
+
+        let result = Transaction::new(
+            TransactionType::Create,
+            other_id,
+            b"Hello Impersonation".to_vec(),
+            &keypair,
+        );
+
+        assert_eq!(
+            Err(TransactionError::SubmitterMismatch {
+                submitter: other_id,
+                signer: local_id
+            }),
+            result
+        );
+    }
+
+    #[test]
+    fn test_verify_submitter() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let transaction = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"Hello First Transaction".to_vec(),
+            &keypair,
+        )
+        .unwrap();
+        assert_eq!(Ok(()), transaction.verify_submitter());
+
+        let other_id = Address::from(identity::Keypair::generate_ed25519().public());
+        let mut impersonated = transaction;
+        impersonated.submitter = other_id;
+        assert_eq!(
+            Err(TransactionError::InvalidSignature(other_id)),
+            impersonated.verify_submitter()
+        );
+    }
+
+    #[test]
+    fn test_block_signature_fails_transaction_verification() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut transaction = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"Hello First Transaction".to_vec(),
+            &keypair,
+        )
+        .unwrap();
+        let msg = bincode::serialize(&transaction.hash()).unwrap();
+
+        transaction.signature = Signature::new_in(SignatureDomain::Block, &msg, &keypair).unwrap();
+        assert_eq!(
+            Err(TransactionError::InvalidSignature(local_id)),
+            transaction.verify_submitter()
+        );
+    }
+
+    #[test]
+    fn test_weight() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let transaction = Transaction::new(TransactionType::Create, local_id, vec![], &keypair)
+            .map_err(|e| e.to_string())?;
+        let heavier = Transaction::new(TransactionType::Create, local_id, vec![0; 100], &keypair)
+            .map_err(|e| e.to_string())?;
+
+        assert_eq!(SIGNATURE_WEIGHT, transaction.weight());
+        assert_eq!(SIGNATURE_WEIGHT + 100, heavier.weight());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let transaction = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"Hello First Transaction".to_vec(),
+            &keypair,
+        )
+        .unwrap();
+        assert_eq!(Ok(()), transaction.verify());
+
+        let mut tampered_payload = transaction.clone();
+        tampered_payload.payload = b"Hello Tampered Transaction".to_vec();
+        assert_eq!(Ok(()), tampered_payload.verify_submitter());
+        assert_eq!(
+            Err(TransactionError::HashMismatch(transaction.hash())),
+            tampered_payload.verify()
+        );
+
+        let mut tampered_signature = transaction;
+        tampered_signature.signature =
+            Signature::new_in(SignatureDomain::Transaction, b"something else", &keypair).unwrap();
+        assert_eq!(
+            Err(TransactionError::InvalidSignature(local_id)),
+            tampered_signature.verify()
+        );
+    }
+
+    #[test]
+    fn test_verify_with_revocations() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let signed_at = |timestamp| {
+            Transaction::new_with(
+                TransactionType::Create,
+                local_id,
+                b"Hello Revocation".to_vec(),
+                timestamp,
+                42,
+                &keypair,
+            )
+            .unwrap()
+        };
+        let revocations = Revocations::from([(local_id, 1656633600)]);
+
+        assert_eq!(
+            Ok(()),
+            signed_at(1656633599).verify_with_revocations(&revocations)
+        );
+        assert_eq!(
+            Err(TransactionError::RevokedKey {
+                submitter: local_id,
+                revoked_at: 1656633600
+            }),
+            signed_at(1656633600).verify_with_revocations(&revocations)
+        );
+        assert_eq!(
+            Ok(()),
+            signed_at(1656633600).verify_with_revocations(&Revocations::new())
+        );
+    }
 }
@@ -0,0 +1,95 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum WireFormatError {
+    #[error("JSON encoding failed: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("CBOR encoding failed: {0}")]
+    CborEncode(#[from] ciborium::ser::Error<std::io::Error>),
+    #[error("CBOR decoding failed: {0}")]
+    CborDecode(#[from] ciborium::de::Error<std::io::Error>),
+}
+
+/// The encoding of blocks and transactions exchanged with peers and clients.
+/// Hashes are always computed over the bincode serialization, whatever the wire format.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    /// Human readable, the format used on disk
+    #[default]
+    Json,
+    /// Compact binary encoding, preferred for gossip
+    Cbor,
+}
+
+impl WireFormat {
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, WireFormatError> {
+        match self {
+            WireFormat::Json => Ok(serde_json::to_vec(value)?),
+            WireFormat::Cbor => {
+                let mut bytes = vec![];
+                ciborium::ser::into_writer(value, &mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, WireFormatError> {
+        match self {
+            WireFormat::Json => Ok(serde_json::from_slice(bytes)?),
+            WireFormat::Cbor => Ok(ciborium::de::from_reader(bytes)?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::hash_algorithm::HashDigest;
+    use crate::structures::{
+        block::Block,
+        header::Address,
+        transaction::{Transaction, TransactionType},
+    };
+    use libp2p::identity;
+
+    #[test]
+    fn test_block_cbor_round_trip() -> Result<(), String> {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let transaction = Transaction::new(
+            TransactionType::Create,
+            local_id,
+            b"Hello CBOR".to_vec(),
+            &keypair,
+        )
+        .unwrap();
+        let block = Block::new(HashDigest::new(b""), 1, vec![transaction], &keypair).unwrap();
+
+        let cbor = WireFormat::Cbor.encode(&block).map_err(|e| e.to_string())?;
+        let decoded: Block = WireFormat::Cbor.decode(&cbor).map_err(|e| e.to_string())?;
+
+        assert_eq!(block, decoded);
+        assert_eq!(block.header.hash(), decoded.header.hash());
+        assert!(cbor.len() < WireFormat::Json.encode(&block).unwrap().len());
+        Ok(())
+    }
+}